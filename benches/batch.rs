@@ -0,0 +1,90 @@
+//! Measures how fast the game and the batch simulator step:
+//!
+//! ```text
+//! cargo bench --bench batch -- [--games N] [--steps N] [--threads N]
+//! ```
+//!
+//! Steps `--games` games (1024 by default) for `--steps` steps (2000 by
+//! default) on one thread and on `--threads` (all cores by default),
+//! restarting crashed games, and prints the steps per second next to those
+//! of the game.
+
+use std::time::Instant;
+
+use flappy::args::{Args, exit};
+use flappy::batch::BatchSim;
+use flappy::env::{Action, FlappyEnv, RewardConfig};
+
+fn main() {
+    let args = Args::from_env();
+    let value = |flag: &str, default: u64| {
+        args.parse(flag, default)
+            .unwrap_or_else(|error| exit(&error))
+    };
+    let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    let games = value("--games", 1024) as usize;
+    let steps = value("--steps", 2000);
+    let threads = value("--threads", threads as u64) as usize;
+
+    let env_rate = bench_env(steps.max(1) * 10);
+    println!("{:>10}  {:>12.0} steps/s", "game", env_rate);
+    let single = bench_batch(games, steps, 1);
+    println!("{:>10}  {:>12.0} steps/s", "batch", single);
+    let parallel = bench_batch(games, steps, threads);
+    println!(
+        "{:>10}  {:>12.0} steps/s  ({threads} threads)",
+        "batch", parallel
+    );
+}
+
+//flap every 22 steps, a little apart between games
+fn flap(step: u64, game: usize) -> bool {
+    (step + game as u64).is_multiple_of(22)
+}
+
+/// Steps per second of a single [`FlappyEnv`].
+fn bench_env(steps: u64) -> f64 {
+    let mut env = FlappyEnv::default();
+    env.reset(0);
+    let mut seed = 0;
+    let start = Instant::now();
+    for step in 0..steps {
+        let action = if flap(step, 0) {
+            Action::Flap
+        } else {
+            Action::Idle
+        };
+        let (_, _, done, _) = env.step(action);
+        if done {
+            seed += 1;
+            env.reset(seed);
+        }
+    }
+    steps as f64 / start.elapsed().as_secs_f64()
+}
+
+/// Steps per second, summed over all games, of a [`BatchSim`] of `games`
+/// games on `threads` threads.
+fn bench_batch(games: usize, steps: u64, threads: usize) -> f64 {
+    let mut batch = BatchSim::new(0..games as u64, RewardConfig::default());
+    let mut seed = games as u64;
+    let mut flaps = vec![false; games];
+    let start = Instant::now();
+    for step in 0..steps {
+        for (game, flap_now) in flaps.iter_mut().enumerate() {
+            *flap_now = flap(step, game);
+        }
+        if threads > 1 {
+            batch.step_parallel(&flaps, threads);
+        } else {
+            batch.step(&flaps);
+        }
+        for game in 0..games {
+            if batch.done(game) {
+                batch.reset(game, seed);
+                seed += 1;
+            }
+        }
+    }
+    (games as u64 * steps) as f64 / start.elapsed().as_secs_f64()
+}
@@ -0,0 +1,259 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::path::Path;
+
+use bevy_ecs::prelude::*;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::animation::Animator;
+use crate::asset_loader::BIRD_CLIPS;
+use crate::error::{Error, Result};
+use crate::game::{GameState, GameStates};
+use crate::player::{Controller, Player};
+use crate::simulation::{GameEvent, GameEvents, TICK, Tick};
+use crate::sprite::Sprite;
+
+pub const ACHIEVEMENTS_PATH: &str = "assets/achievements.json";
+pub const ACHIEVEMENT_PROGRESS_PATH: &str = "achievements.progress.json";
+/// Seconds an unlocked achievement stays on screen.
+pub const TOAST_SECONDS: f32 = 3.0;
+
+/// One achievement of `assets/achievements.json`:
+///
+/// ```json
+/// { "id": "flap_1000", "name": "FLAP 1000 TIMES",
+///   "condition": { "type": "flaps", "at_least": 1000 } }
+/// ```
+///
+/// `id` is what the progress file remembers, so names can change freely.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Achievement {
+    pub id: String,
+    pub name: String,
+    pub condition: Condition,
+}
+
+/// What unlocks an achievement. Only birds flown from the keyboard count, and
+/// the game doesn't track achievements in practice or scripted games.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Condition {
+    /// Reaching a score in a single round.
+    Score { at_least: u32 },
+    /// Flapping this often, over all rounds together.
+    Flaps { at_least: u64 },
+    /// Crashing no later than this many seconds into a round.
+    CrashWithin { seconds: f32 },
+    /// Having flown a bird of every colour.
+    EveryColour,
+}
+
+/// Which achievements are unlocked, and what the ones that take more than a
+/// round have counted so far.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AchievementProgress {
+    pub unlocked: BTreeSet<String>,
+    pub flaps: u64,
+    /// Names of the bird clips flown.
+    pub colours: BTreeSet<String>,
+}
+
+impl AchievementProgress {
+    /// Reads the progress saved at `path`, or starts anew when there is none.
+    pub fn load(path: &str) -> Result<Self> {
+        if !Path::new(path).exists() {
+            return Ok(Self::default());
+        }
+        let json = std::fs::read_to_string(path).map_err(|error| Error::asset(path, error))?;
+        serde_json::from_str(&json).map_err(|error| Error::asset(path, error))
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(|error| Error::write(path, error))?;
+        std::fs::write(path, json).map_err(|error| Error::write(path, error))
+    }
+}
+
+/// The achievements, what was unlocked of them and the ones waiting to be
+/// announced.
+#[derive(Resource, Debug, Clone)]
+pub struct Achievements {
+    pub list: Vec<Achievement>,
+    pub progress: AchievementProgress,
+    progress_path: String,
+    /// Names of the achievements to announce, the one on screen first.
+    pub toasts: VecDeque<String>,
+    /// Seconds the first toast has been on screen.
+    pub toast_time: f32,
+    /// Whether the achievements screen is open.
+    pub screen_open: bool,
+    /// Whether there is progress that was not saved yet.
+    dirty: bool,
+}
+
+impl Achievements {
+    /// Loads the achievements at `path` and the progress saved at
+    /// `progress_path`.
+    pub fn load(path: &str, progress_path: &str) -> Result<Self> {
+        let json = std::fs::read_to_string(path).map_err(|error| Error::asset(path, error))?;
+        let list: Vec<Achievement> =
+            serde_json::from_str(&json).map_err(|error| Error::asset(path, error))?;
+        let mut ids = BTreeSet::new();
+        for achievement in &list {
+            if !ids.insert(&achievement.id) {
+                return Err(Error::asset(
+                    path,
+                    format!("{} is there twice", achievement.id),
+                ));
+            }
+        }
+
+        Ok(Self {
+            list,
+            progress: AchievementProgress::load(progress_path)?,
+            progress_path: progress_path.to_string(),
+            toasts: VecDeque::new(),
+            toast_time: 0.0,
+            screen_open: false,
+            dirty: false,
+        })
+    }
+
+    pub fn unlocked(&self, achievement: &Achievement) -> bool {
+        self.progress.unlocked.contains(&achievement.id)
+    }
+
+    /// Unlocks every achievement that is still locked and whose condition
+    /// `met` says is met, and announces it. Returns whether any unlocked.
+    fn unlock_where(&mut self, met: impl Fn(&Condition) -> bool) -> bool {
+        let mut unlocked = false;
+        for achievement in &self.list {
+            if met(&achievement.condition) && self.progress.unlocked.insert(achievement.id.clone())
+            {
+                self.toasts.push_back(achievement.name.clone());
+                unlocked = true;
+            }
+        }
+        self.dirty |= unlocked;
+        unlocked
+    }
+
+    /// Counts what the birds flown from the keyboard did in `events`,
+    /// `seconds` into the round, and unlocks what they earned. `keyboard`
+    /// has the index of each of those birds with the name of its clip.
+    /// Returns whether anything unlocked.
+    pub fn count(
+        &mut self,
+        events: &[GameEvent],
+        seconds: f32,
+        keyboard: &[(usize, Option<&str>)],
+    ) -> bool {
+        let bird = |index: usize| keyboard.iter().find(|(player, _clip)| *player == index);
+        let mut unlocked = false;
+        for event in events {
+            match *event {
+                GameEvent::Flap { player, .. } => {
+                    let Some((_player, clip)) = bird(player) else {
+                        continue;
+                    };
+                    self.progress.flaps += 1;
+                    self.dirty = true;
+                    if let Some(clip) = clip {
+                        self.progress.colours.insert(clip.to_string());
+                    }
+                    let flaps = self.progress.flaps;
+                    let colours = BIRD_CLIPS
+                        .iter()
+                        .all(|clip| self.progress.colours.contains(clip.name()));
+                    unlocked |= self.unlock_where(|condition| match condition {
+                        Condition::Flaps { at_least } => flaps >= *at_least,
+                        Condition::EveryColour => colours,
+                        _ => false,
+                    });
+                }
+                GameEvent::Score { player, score } if bird(player).is_some() => {
+                    unlocked |= self.unlock_where(|condition| {
+                        matches!(condition, Condition::Score { at_least } if score >= *at_least)
+                    });
+                }
+                GameEvent::Crash { player, .. } if bird(player).is_some() => {
+                    unlocked |= self.unlock_where(|condition| {
+                        matches!(condition, Condition::CrashWithin { seconds: within } if seconds <= *within)
+                    });
+                }
+                _ => {}
+            }
+        }
+        unlocked
+    }
+
+    fn save(&mut self) {
+        self.dirty = false;
+        if let Err(error) = self.progress.save(&self.progress_path) {
+            eprintln!("{error}");
+        }
+    }
+}
+
+/// Counts flaps, scores, crashes and bird colours of the birds flown from
+/// the keyboard and unlocks what they earned. Progress is saved when a round
+/// ends or something unlocks. Runs before the events are drained by
+/// [`play_sounds`](crate::player::play_sounds).
+pub fn track_achievements(
+    game_state: Res<GameState>,
+    events: Res<GameEvents>,
+    tick: Res<Tick>,
+    mut achievements: ResMut<Achievements>,
+    player_query: Query<(&Player, &Controller, Option<&Animator>)>,
+) {
+    let keyboard: Vec<(usize, Option<&str>)> = player_query
+        .iter()
+        .filter(|(_player, controller, _animator)| **controller == Controller::Keyboard)
+        .map(|(player, _controller, animator)| {
+            (player.index, animator.map(|animator| animator.clip.name()))
+        })
+        .collect();
+    let unlocked = achievements.count(&events.0, tick.0 as f32 * TICK, &keyboard);
+
+    if achievements.dirty && (game_state.is_over() || unlocked) {
+        achievements.save();
+    }
+
+    if !achievements.toasts.is_empty() {
+        achievements.toast_time += get_frame_time();
+        if achievements.toast_time >= TOAST_SECONDS {
+            achievements.toasts.pop_front();
+            achievements.toast_time = 0.0;
+        }
+    }
+}
+
+/// A on the start screen opens the list of achievements, A, Escape or the
+/// space bar close it again. The game waits `Paused` and the birds hide
+/// while it is open. Runs after [`check_inputs`](crate::game::check_inputs),
+/// so the key that closes it doesn't also start a round.
+pub fn achievement_screen(
+    mut game_state: ResMut<GameState>,
+    mut achievements: ResMut<Achievements>,
+    mut player_query: Query<&mut Sprite, With<Player>>,
+) {
+    let toggled = is_key_pressed(KeyCode::A);
+    let open = if achievements.screen_open {
+        !(toggled || is_key_pressed(KeyCode::Escape) || is_key_pressed(KeyCode::Space))
+    } else {
+        toggled && game_state.game_state == GameStates::PressStart
+    };
+    if open == achievements.screen_open {
+        return;
+    }
+
+    achievements.screen_open = open;
+    game_state.game_state = if open {
+        GameStates::Paused
+    } else {
+        GameStates::PressStart
+    };
+    for mut sprite in &mut player_query {
+        sprite.visible = !open;
+    }
+}
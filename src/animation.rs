@@ -0,0 +1,117 @@
+use bevy_ecs::prelude::*;
+use macroquad::prelude::*;
+
+use crate::asset_loader::{AssetLoader, ClipHandle};
+
+/// One frame of an animation clip: where it is in the texture and how long
+/// it stays on screen.
+///
+/// `offset` and `size` describe where a trimmed frame sits inside its
+/// untrimmed size, so every frame of a clip lines up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipFrame {
+    pub source: Rect,
+    pub offset: Vec2,
+    pub size: Vec2,
+    pub duration: f32,
+}
+
+/// A named sequence of frames from one texture. Clips live in the
+/// `AssetLoader` and are only read; playback state is in [`Animator`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationClip {
+    pub name: String,
+    pub texture: String,
+    pub frames: Vec<ClipFrame>,
+}
+
+impl AnimationClip {
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+}
+
+/// Playback state of one entity's animation.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Animator {
+    pub clip: ClipHandle,
+    pub frame: usize,
+    pub timer: f32,
+    pub speed: f32,
+    pub looping: bool,
+    pub playing: bool,
+}
+
+impl Animator {
+    pub fn new(clip: ClipHandle) -> Self {
+        Self {
+            clip,
+            frame: 0,
+            timer: 0.0,
+            speed: 1.0,
+            looping: true,
+            playing: true,
+        }
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    pub fn once(mut self) -> Self {
+        self.looping = false;
+        self
+    }
+
+    /// Switches to another clip from its first frame. Playing the current
+    /// clip again keeps its progress.
+    pub fn play(&mut self, clip: ClipHandle) {
+        if self.clip != clip {
+            self.clip = clip;
+            self.frame = 0;
+            self.timer = 0.0;
+        }
+        self.playing = true;
+    }
+
+    /// Moves `delta` seconds forward through `clip`, stopping on the last
+    /// frame when not looping.
+    pub fn advance(&mut self, clip: &AnimationClip, delta: f32) {
+        if !self.playing || clip.frames.is_empty() {
+            return;
+        }
+
+        self.frame = self.frame.min(clip.frames.len() - 1);
+        self.timer += delta * self.speed;
+        while self.timer >= clip.frames[self.frame].duration {
+            let duration = clip.frames[self.frame].duration;
+            if duration <= 0.0 {
+                break;
+            }
+            self.timer -= duration;
+
+            if self.frame + 1 < clip.frames.len() {
+                self.frame += 1;
+            } else if self.looping {
+                self.frame = 0;
+            } else {
+                self.timer = 0.0;
+                self.playing = false;
+                break;
+            }
+        }
+    }
+
+    pub fn current_frame<'a>(&self, clip: &'a AnimationClip) -> Option<&'a ClipFrame> {
+        clip.frames.get(self.frame).or(clip.frames.last())
+    }
+}
+
+pub fn update_animators(mut query: Query<&mut Animator>, assets: Res<AssetLoader>) {
+    let delta = get_frame_time();
+    for mut animator in &mut query {
+        let clip = assets.get_clip(animator.clip);
+        animator.advance(clip, delta);
+    }
+}
@@ -0,0 +1,90 @@
+//! Flags on the command line, shared by the game and the tools in `src/bin`.
+
+use std::str::FromStr;
+
+/// Flags that can't be given together: each flag with the flags it can't be
+/// combined with.
+pub type Conflicts<'a> = [(&'a str, &'a [&'a str])];
+
+/// The command line, read as flags that may be followed by a value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Args(Vec<String>);
+
+impl Args {
+    pub fn new(args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self(args.into_iter().map(Into::into).collect())
+    }
+
+    /// The arguments this process was started with.
+    pub fn from_env() -> Self {
+        Self(std::env::args().collect())
+    }
+
+    pub fn has(&self, flag: &str) -> bool {
+        self.0.iter().any(|arg| arg == flag)
+    }
+
+    /// The value after `flag`, `None` when the flag isn't given. Another flag
+    /// is not a value.
+    pub fn value(&self, flag: &str) -> Result<Option<&str>, String> {
+        match self.0.iter().position(|arg| arg == flag) {
+            None => Ok(None),
+            Some(index) => self
+                .0
+                .get(index + 1)
+                .filter(|value| !value.starts_with("--"))
+                .map(|value| Some(value.as_str()))
+                .ok_or(format!("{flag} needs a value")),
+        }
+    }
+
+    /// The value after `flag`, `None` when the flag isn't given.
+    pub fn get<T: FromStr>(&self, flag: &str) -> Result<Option<T>, String> {
+        self.value(flag)?
+            .map(|value| parse_value(flag, value))
+            .transpose()
+    }
+
+    /// The value after `flag`, `default` when the flag isn't given.
+    pub fn parse<T: FromStr>(&self, flag: &str, default: T) -> Result<T, String> {
+        Ok(self.get(flag)?.unwrap_or(default))
+    }
+
+    /// The value after a flag whose value may be left out: `None` when the
+    /// flag isn't given, `default` when it is given alone.
+    pub fn parse_optional<T: FromStr>(&self, flag: &str, default: T) -> Result<Option<T>, String> {
+        if !self.has(flag) {
+            return Ok(None);
+        }
+        match self.value(flag) {
+            Ok(Some(value)) => parse_value(flag, value).map(Some),
+            _ => Ok(Some(default)),
+        }
+    }
+
+    /// Fails on the first flag given together with one it conflicts with.
+    pub fn check_conflicts(&self, conflicts: &Conflicts) -> Result<(), String> {
+        for (flag, others) in conflicts {
+            if !self.has(flag) {
+                continue;
+            }
+            if let Some(other) = others.iter().find(|other| self.has(other)) {
+                return Err(format!("{flag} can't be combined with {other}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {flag}: {value}"))
+}
+
+/// Prints `message` and ends the process, for a command line or a file it
+/// names that can't be used.
+pub fn exit(message: &str) -> ! {
+    eprintln!("{message}");
+    std::process::exit(2);
+}
@@ -1,136 +1,503 @@
 use bevy_ecs::prelude::*;
 use macroquad::audio;
-use macroquad::prelude::animation::{AnimatedSprite, Animation};
+use macroquad::file::load_string;
+use macroquad::logging::warn;
+use macroquad::prelude::{BLACK, Image, MAGENTA, Rect, Vec2};
 use macroquad::texture::{FilterMode, Texture2D, load_texture};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Mutex;
 
-#[derive(Resource, Default)]
+use crate::animation::{AnimationClip, ClipFrame};
+use crate::atlas::{Atlas, parse_atlas};
+use crate::error::{Error, Result};
+use crate::manifest::{MANIFEST_PATH, Manifest, ManifestError, RequiredAssets};
+use crate::text::{BitmapFont, FontDescription};
+
+/// The typed name of an asset listed in the manifest, so a sound can't be
+/// looked up where a texture is expected.
+pub struct Handle<T> {
+    name: &'static str,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            marker: PhantomData,
+        }
+    }
+
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Handle({})", self.name)
+    }
+}
+
+pub type TextureHandle = Handle<Texture2D>;
+pub type SoundHandle = Handle<audio::Sound>;
+pub type FontHandle = Handle<BitmapFont>;
+pub type ClipHandle = Handle<AnimationClip>;
+
+//the assets the game code draws and plays, all listed in REQUIRED_ASSETS
+pub const PIPE_TEXTURE: TextureHandle = Handle::new("pipe");
+pub const FLOOR_TEXTURE: TextureHandle = Handle::new("floor");
+pub const GAME_OVER_TEXTURE: TextureHandle = Handle::new("game_over");
+pub const PRESS_SPACE_TEXTURE: TextureHandle = Handle::new("press_space");
+pub const SPRITE_SHEET_TEXTURE: TextureHandle = Handle::new("sprite_sheet");
+pub const HIT_SOUND: SoundHandle = Handle::new("hit_sound");
+pub const SCORE_SOUND: SoundHandle = Handle::new("score_sound");
+pub const FLAP_SOUND: SoundHandle = Handle::new("flap_sound");
+pub const SCORE_FONT: FontHandle = Handle::new("score_font");
+pub const PIXEL_FONT: FontHandle = Handle::new("pixel_font");
+pub const BIRD_YELLOW_CLIP: ClipHandle = Handle::new("bird_yellow");
+pub const BIRD_BLUE_CLIP: ClipHandle = Handle::new("bird_blue");
+pub const BIRD_RED_CLIP: ClipHandle = Handle::new("bird_red");
+pub const BIRD_CLIPS: [ClipHandle; 3] = [BIRD_YELLOW_CLIP, BIRD_BLUE_CLIP, BIRD_RED_CLIP];
+
+/// Every handle the game code uses; the manifest has to define all of them.
+pub const REQUIRED_ASSETS: RequiredAssets = RequiredAssets {
+    textures: &[
+        PIPE_TEXTURE.name(),
+        FLOOR_TEXTURE.name(),
+        GAME_OVER_TEXTURE.name(),
+        PRESS_SPACE_TEXTURE.name(),
+        SPRITE_SHEET_TEXTURE.name(),
+    ],
+    sounds: &[HIT_SOUND.name(), SCORE_SOUND.name(), FLAP_SOUND.name()],
+    fonts: &[SCORE_FONT.name(), PIXEL_FONT.name()],
+    clips: &[
+        BIRD_YELLOW_CLIP.name(),
+        BIRD_BLUE_CLIP.name(),
+        BIRD_RED_CLIP.name(),
+    ],
+};
+
+/// Size of the checkerboard drawn for a missing texture.
+const FALLBACK_TEXTURE_SIZE: u16 = 16;
+/// Size of a square of that checkerboard.
+const FALLBACK_CHECKER_SIZE: u16 = 4;
+
+/// Every loaded asset by name, with stand-ins for those that are missing.
+#[derive(Resource)]
 pub struct AssetLoader {
-    pub texture_assets: HashMap<&'static str, Texture2D>,
-    pub sound_assets: HashMap<&'static str, audio::Sound>,
-    pub animation_assets: HashMap<&'static str, AnimatedSprite>,
+    pub texture_assets: HashMap<String, Texture2D>,
+    pub sound_assets: HashMap<String, audio::Sound>,
+    pub font_assets: HashMap<String, BitmapFont>,
+    pub atlas_assets: HashMap<String, Atlas>,
+    pub clip_assets: HashMap<String, AnimationClip>,
+    fallback_texture: Option<Texture2D>,
+    fallback_sound: Option<audio::Sound>,
+    fallback_font: BitmapFont,
+    fallback_clip: AnimationClip,
+    warned: Mutex<HashSet<String>>,
 }
 
-pub enum AssetType {
-    Texture,
-    Sound,
-    Animation,
+impl Default for AssetLoader {
+    fn default() -> Self {
+        Self {
+            texture_assets: HashMap::new(),
+            sound_assets: HashMap::new(),
+            font_assets: HashMap::new(),
+            atlas_assets: HashMap::new(),
+            clip_assets: HashMap::new(),
+            fallback_texture: None,
+            fallback_sound: None,
+            fallback_font: BitmapFont::default(),
+            fallback_clip: AnimationClip {
+                name: "missing".to_string(),
+                texture: "missing".to_string(),
+                frames: vec![ClipFrame {
+                    source: Rect::new(
+                        0.0,
+                        0.0,
+                        FALLBACK_TEXTURE_SIZE as f32,
+                        FALLBACK_TEXTURE_SIZE as f32,
+                    ),
+                    offset: Vec2::ZERO,
+                    size: Vec2::splat(FALLBACK_TEXTURE_SIZE as f32),
+                    duration: 1.0,
+                }],
+            },
+            warned: Mutex::new(HashSet::new()),
+        }
+    }
 }
 
 impl AssetLoader {
-    pub async fn load_texture(&mut self, name: &'static str, path: &'static str) -> &mut Self {
-        let texture: Texture2D = load_texture(path).await.expect("Cannot load texture");
+    /// Creates the magenta checkerboard and the silent sound that stand in
+    /// for assets that failed to load. Needs a window.
+    pub async fn load_fallbacks(&mut self) -> Result<&mut Self> {
+        let mut image =
+            Image::gen_image_color(FALLBACK_TEXTURE_SIZE, FALLBACK_TEXTURE_SIZE, MAGENTA);
+        for y in 0..FALLBACK_TEXTURE_SIZE {
+            for x in 0..FALLBACK_TEXTURE_SIZE {
+                if (x / FALLBACK_CHECKER_SIZE + y / FALLBACK_CHECKER_SIZE) % 2 == 1 {
+                    image.set_pixel(x as u32, y as u32, BLACK);
+                }
+            }
+        }
+        let texture = Texture2D::from_image(&image);
         texture.set_filter(FilterMode::Nearest);
+        self.fallback_texture = Some(texture);
 
-        self.texture_assets.insert(name, texture);
+        let sound = audio::load_sound_from_bytes(&silent_wav())
+            .await
+            .map_err(|error| Error::asset("<silent sound>", error))?;
+        self.fallback_sound = Some(sound);
 
-        self
+        Ok(self)
     }
 
-    pub async fn load_sound(&mut self, name: &'static str, path: &'static str) -> &mut Self {
-        let sound: audio::Sound = audio::load_sound(path).await.unwrap();
-        self.sound_assets.insert(name, sound);
+    pub async fn load_texture(&mut self, name: &str, path: &str) -> Result<&mut Self> {
+        let texture: Texture2D = load_texture(path)
+            .await
+            .map_err(|error| Error::asset(path, error))?;
+        texture.set_filter(FilterMode::Nearest);
+
+        self.texture_assets.insert(name.to_string(), texture);
 
-        self
+        Ok(self)
     }
 
-    pub fn load_animation(&mut self, name: &'static str, animation: AnimatedSprite) -> &mut Self {
-        self.animation_assets.insert(name, animation);
-        self
+    pub async fn load_sound(&mut self, name: &str, path: &str) -> Result<&mut Self> {
+        let sound: audio::Sound = audio::load_sound(path)
+            .await
+            .map_err(|error| Error::asset(path, error))?;
+        self.sound_assets.insert(name.to_string(), sound);
+
+        Ok(self)
     }
 
-    pub fn get_animation(&self, name: &str) -> &AnimatedSprite {
-        self.animation_assets.get(name).unwrap()
+    /// Loads glyph metrics for a font drawn from an already loaded texture.
+    pub async fn load_font(&mut self, name: &str, texture: &str, path: &str) -> Result<&mut Self> {
+        let description = load_string(path)
+            .await
+            .map_err(|error| Error::asset(path, error))?;
+        self.insert_font(name, texture, path, &description)
     }
 
-    pub fn get_texture(&self, name: &str) -> &Texture2D {
-        self.texture_assets.get(name).unwrap()
+    /// Parses a font description that was read by other means, e.g. by a
+    /// headless tool without a window. `path` is only used for errors.
+    pub fn insert_font(
+        &mut self,
+        name: &str,
+        texture: &str,
+        path: &str,
+        description: &str,
+    ) -> Result<&mut Self> {
+        let description: FontDescription =
+            serde_json::from_str(description).map_err(|error| Error::asset(path, error))?;
+        let font =
+            BitmapFont::new(texture, description).map_err(|error| Error::asset(path, error))?;
+        self.font_assets.insert(name.to_string(), font);
+
+        Ok(self)
     }
 
-    pub fn get_sound(&self, name: &str) -> &audio::Sound {
-        self.sound_assets.get(name).unwrap()
+    /// Loads the frames and clips of an Aseprite or TexturePacker export.
+    pub async fn load_atlas(
+        &mut self,
+        name: &str,
+        texture: &str,
+        path: &str,
+        frame_duration: f32,
+    ) -> Result<&mut Self> {
+        let json = load_string(path)
+            .await
+            .map_err(|error| Error::asset(path, error))?;
+        self.insert_atlas(name, texture, path, &json, frame_duration)
     }
 
-    pub fn update_animation(&mut self, name: &str) {
-        let anim = self.animation_assets.get_mut(name).unwrap();
-        anim.update();
+    /// Parses an atlas export that was read by other means. Its clips become
+    /// available by name, replacing clips of the same name.
+    pub fn insert_atlas(
+        &mut self,
+        name: &str,
+        texture: &str,
+        path: &str,
+        json: &str,
+        frame_duration: f32,
+    ) -> Result<&mut Self> {
+        let atlas = parse_atlas(json, texture, frame_duration)
+            .map_err(|error| Error::asset(path, error))?;
+        for clip in &atlas.clips {
+            self.clip_assets.insert(clip.name.clone(), clip.clone());
+        }
+        self.atlas_assets.insert(name.to_string(), atlas);
+
+        Ok(self)
     }
 
-    pub fn set_animation(&mut self, name: &str, number: usize) {
-        let anim = self.animation_assets.get_mut(name).unwrap();
-        anim.set_animation(number);
+    /// Loads every asset listed in the manifest at `path`.
+    ///
+    /// Problems don't stop loading: everything that can be loaded is, and
+    /// the rest falls back to placeholders. The error lists every problem.
+    pub async fn load_manifest(&mut self, path: &str) -> Result<&mut Self> {
+        let json = load_string(path)
+            .await
+            .map_err(|error| Error::asset(path, error))?;
+        let manifest = Manifest::parse(&json).map_err(|error| Error::Manifest(vec![error]))?;
+
+        let mut manifest_errors = manifest.validate(&REQUIRED_ASSETS);
+        let mut errors = Vec::new();
+
+        for texture in manifest.textures.iter().filter(|entry| exists(&entry.path)) {
+            if let Err(error) = self.load_texture(&texture.name, &texture.path).await {
+                errors.push(error);
+            }
+        }
+        for sound in manifest.sounds.iter().filter(|entry| exists(&entry.path)) {
+            if let Err(error) = self.load_sound(&sound.name, &sound.path).await {
+                errors.push(error);
+            }
+        }
+        for font in manifest.fonts.iter().filter(|entry| exists(&entry.path)) {
+            if let Err(error) = self.load_font(&font.name, &font.texture, &font.path).await {
+                errors.push(error);
+            }
+        }
+        for atlas in manifest.atlases.iter().filter(|entry| exists(&entry.path)) {
+            let loaded = self
+                .load_atlas(
+                    &atlas.name,
+                    &atlas.texture,
+                    &atlas.path,
+                    atlas.frame_duration,
+                )
+                .await;
+            if let Err(error) = loaded {
+                errors.push(error);
+            }
+        }
+        for error in self.load_manifest_clips(&manifest) {
+            //an unknown sprite sheet was already found by validate
+            if !manifest_errors.contains(&error) {
+                manifest_errors.push(error);
+            }
+        }
+
+        manifest_errors.extend(self.missing_clips());
+        collect_errors(manifest_errors, errors)?;
+        Ok(self)
+    }
+
+    /// Loads the parts of a manifest that need no window, for tools that run
+    /// headless. Textures and sounds are left out.
+    pub fn load_manifest_headless(&mut self, manifest: &Manifest) -> Result<&mut Self> {
+        let mut errors = Vec::new();
+
+        for font in &manifest.fonts {
+            let loaded = std::fs::read_to_string(&font.path)
+                .map_err(|error| Error::asset(&font.path, error))
+                .and_then(|description| {
+                    self.insert_font(&font.name, &font.texture, &font.path, &description)
+                        .map(|_| ())
+                });
+            if let Err(error) = loaded {
+                errors.push(error);
+            }
+        }
+        for atlas in &manifest.atlases {
+            let loaded = std::fs::read_to_string(&atlas.path)
+                .map_err(|error| Error::asset(&atlas.path, error))
+                .and_then(|json| {
+                    self.insert_atlas(
+                        &atlas.name,
+                        &atlas.texture,
+                        &atlas.path,
+                        &json,
+                        atlas.frame_duration,
+                    )
+                    .map(|_| ())
+                });
+            if let Err(error) = loaded {
+                errors.push(error);
+            }
+        }
+        let mut manifest_errors = self.load_manifest_clips(manifest);
+
+        manifest_errors.extend(self.missing_clips());
+        collect_errors(manifest_errors, errors)?;
+        Ok(self)
+    }
+
+    /// Makes the clips of the manifest's atlases and animations available by
+    /// name, the last one winning when names collide. Collisions and
+    /// animations that can't be built are returned.
+    fn load_manifest_clips(&mut self, manifest: &Manifest) -> Vec<ManifestError> {
+        let mut errors = Vec::new();
+        let mut clips: Vec<AnimationClip> = manifest
+            .atlases
+            .iter()
+            .filter_map(|entry| self.atlas_assets.get(&entry.name))
+            .flat_map(|atlas| atlas.clips.iter().cloned())
+            .collect();
+        for animation in &manifest.animations {
+            match manifest.build_clips(animation) {
+                Ok(built) => clips.extend(built),
+                Err(error) => errors.push(error),
+            }
+        }
+
+        let mut names = HashSet::new();
+        for clip in clips {
+            if !names.insert(clip.name.clone()) {
+                errors.push(ManifestError::Duplicate {
+                    section: "clip",
+                    name: clip.name.clone(),
+                });
+            }
+            self.clip_assets.insert(clip.name.clone(), clip);
+        }
+        errors
+    }
+
+    /// Required clips that no atlas or sprite sheet defined. Clips are only
+    /// known once their atlases are parsed, so the manifest can't check them.
+    fn missing_clips(&self) -> Vec<ManifestError> {
+        REQUIRED_ASSETS
+            .clips
+            .iter()
+            .filter(|name| !self.clip_assets.contains_key(**name))
+            .map(|name| ManifestError::MissingKey {
+                section: "clip",
+                name: name.to_string(),
+            })
+            .collect()
+    }
+
+    /// Logs a missing asset the first time it is looked up.
+    fn warn_missing(&self, kind: &str, name: &str) {
+        let mut warned = self.warned.lock().unwrap();
+        if warned.insert(format!("{kind}/{name}")) {
+            warn!("{} \"{}\" is not loaded, using a placeholder", kind, name);
+        }
+    }
+
+    /// The texture, or the placeholder when it isn't loaded. `None` when
+    /// the placeholder isn't loaded either.
+    pub fn get_texture(&self, handle: TextureHandle) -> Option<&Texture2D> {
+        self.get_texture_named(handle.name())
+    }
+
+    /// Looks up a texture named in data, such as a font description.
+    pub fn get_texture_named(&self, name: &str) -> Option<&Texture2D> {
+        self.texture_assets.get(name).or_else(|| {
+            self.warn_missing("texture", name);
+            self.fallback_texture.as_ref()
+        })
+    }
+
+    /// The sound, or silence when it isn't loaded. `None` when the silence
+    /// isn't loaded either.
+    pub fn get_sound(&self, handle: SoundHandle) -> Option<&audio::Sound> {
+        self.sound_assets.get(handle.name()).or_else(|| {
+            self.warn_missing("sound", handle.name());
+            self.fallback_sound.as_ref()
+        })
+    }
+
+    pub fn get_font(&self, handle: FontHandle) -> &BitmapFont {
+        self.font_assets.get(handle.name()).unwrap_or_else(|| {
+            self.warn_missing("font", handle.name());
+            &self.fallback_font
+        })
+    }
+
+    pub fn get_clip(&self, handle: ClipHandle) -> &AnimationClip {
+        self.get_clip_named(handle.name())
+    }
+
+    /// Looks up a clip named in data, such as a tag in an atlas export.
+    pub fn get_clip_named(&self, name: &str) -> &AnimationClip {
+        self.clip_assets.get(name).unwrap_or_else(|| {
+            self.warn_missing("clip", name);
+            &self.fallback_clip
+        })
+    }
+
+    /// The source rectangle of a named frame in an atlas.
+    pub fn get_frame(&self, atlas: &str, frame: &str) -> Option<Rect> {
+        self.atlas_assets.get(atlas)?.frames.get(frame).copied()
     }
 }
 
+fn exists(path: &str) -> bool {
+    Path::new(path).exists()
+}
+
+fn collect_errors(manifest_errors: Vec<ManifestError>, mut errors: Vec<Error>) -> Result<()> {
+    if !manifest_errors.is_empty() {
+        errors.insert(0, Error::Manifest(manifest_errors));
+    }
+
+    match errors.len() {
+        0 => Ok(()),
+        1 => Err(errors.remove(0)),
+        _ => Err(Error::Many(errors)),
+    }
+}
+
+/// A few milliseconds of 16 bit mono silence as a WAV file.
+fn silent_wav() -> Vec<u8> {
+    const SAMPLE_RATE: u32 = 44100;
+    const SAMPLES: u32 = 64;
+    let data_size = SAMPLES * 2;
+
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    wav.resize(wav.len() + data_size as usize, 0);
+    wav
+}
+
 pub async fn initialize_assets(world: &mut World) {
     let mut assets = AssetLoader::default();
-    assets
-        .load_texture("pipe", "assets/textures/pipe.png")
-        .await;
-    assets
-        .load_texture("floor", "assets/textures/base.png")
-        .await;
-    assets
-        .load_texture("game_over", "assets/textures/gameover.png")
-        .await;
-    assets
-        .load_texture("press_space", "assets/textures/press_space.png")
-        .await;
-    assets
-        .load_texture("sprite_sheet", "assets/textures/sprite_sheet.png")
-        .await;
-    assets
-        .load_sound("hit_sound", "assets/audio/audio_hit.wav")
-        .await;
-    assets
-        .load_sound("score_sound", "assets/audio/point.wav")
-        .await;
-    assets
-        .load_sound("flap_sound", "assets/audio/wing.wav")
-        .await;
-    assets.load_animation(
-        "player_animations",
-        AnimatedSprite::new(
-            36,
-            36,
-            &[
-                Animation {
-                    name: "yellow".to_string(),
-                    row: 1,
-                    frames: 3,
-                    fps: 6,
-                },
-                Animation {
-                    name: "blue".to_string(),
-                    row: 2,
-                    frames: 3,
-                    fps: 6,
-                },
-                Animation {
-                    name: "red".to_string(),
-                    row: 3,
-                    frames: 3,
-                    fps: 6,
-                },
-            ],
-            true,
-        ),
-    );
-    assets.load_animation(
-        "score_animations",
-        AnimatedSprite::new(
-            36,
-            36,
-            &[Animation {
-                name: "numbers".to_string(),
-                row: 0,
-                frames: 10,
-                fps: 1,
-            }],
-            true,
-        ),
-    );
+    if let Err(error) = assets.load_fallbacks().await {
+        warn!("{}", error);
+    }
+    if let Err(error) = assets.load_manifest(MANIFEST_PATH).await {
+        for problem in error.problems() {
+            warn!("{}", problem);
+        }
+    }
 
     world.init_resource::<crate::game::GameState>();
+    world.init_resource::<crate::game::MatchSettings>();
     world.insert_resource(assets);
 }
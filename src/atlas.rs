@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+
+use macroquad::prelude::*;
+use serde::Deserialize;
+
+use crate::animation::{AnimationClip, ClipFrame};
+
+/// Frame duration for atlases that don't store one, like TexturePacker's.
+pub const DEFAULT_FRAME_DURATION: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AtlasFormat {
+    Aseprite,
+    TexturePacker,
+}
+
+/// Named frames and animation clips imported from an atlas export.
+#[derive(Debug, Clone, Default)]
+pub struct Atlas {
+    pub frames: HashMap<String, Rect>,
+    pub clips: Vec<AnimationClip>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+struct JsonRect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+struct JsonSize {
+    w: f32,
+    h: f32,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct JsonFrame {
+    #[serde(default)]
+    filename: Option<String>,
+    frame: JsonRect,
+    #[serde(default)]
+    rotated: bool,
+    #[serde(default)]
+    trimmed: bool,
+    #[serde(rename = "spriteSourceSize")]
+    sprite_source_size: Option<JsonRect>,
+    #[serde(rename = "sourceSize")]
+    source_size: Option<JsonSize>,
+    /// Milliseconds, only written by Aseprite.
+    duration: Option<f32>,
+}
+
+/// Both tools write frames either as an array or as a map keyed by name.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum JsonFrames {
+    Array(Vec<JsonFrame>),
+    Hash(ordered_frames::OrderedFrames),
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct JsonTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+struct JsonMeta {
+    #[serde(default)]
+    app: String,
+    #[serde(default, rename = "frameTags")]
+    frame_tags: Vec<JsonTag>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct JsonAtlas {
+    frames: JsonFrames,
+    #[serde(default)]
+    meta: JsonMeta,
+    /// TexturePacker's animation lists, as written by its Phaser and PixiJS
+    /// exporters.
+    #[serde(default)]
+    animations: HashMap<String, Vec<String>>,
+}
+
+/// Keeps the frames of a hash export in file order, which serde's maps
+/// don't, because frame order decides clip order.
+mod ordered_frames {
+    use std::fmt;
+
+    use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+
+    use super::JsonFrame;
+
+    #[derive(Debug, Clone)]
+    pub struct OrderedFrames(pub Vec<(String, JsonFrame)>);
+
+    impl<'de> Deserialize<'de> for OrderedFrames {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct FramesVisitor;
+
+            impl<'de> Visitor<'de> for FramesVisitor {
+                type Value = OrderedFrames;
+
+                fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                    formatter.write_str("a map of frame names to frames")
+                }
+
+                fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                    let mut frames = Vec::new();
+                    while let Some((name, frame)) = map.next_entry::<String, JsonFrame>()? {
+                        frames.push((name, frame));
+                    }
+                    Ok(OrderedFrames(frames))
+                }
+            }
+
+            deserializer.deserialize_map(FramesVisitor)
+        }
+    }
+}
+
+/// Parses an Aseprite or TexturePacker JSON export. Clips come from
+/// Aseprite's tags, in any of their directions, from TexturePacker's
+/// animation lists, or else from frame names that only differ in a trailing
+/// number (`bird_0.png`, `bird_1.png`).
+pub fn parse_atlas(json: &str, texture: &str, frame_duration: f32) -> Result<Atlas, String> {
+    let atlas: JsonAtlas = serde_json::from_str(json).map_err(|error| error.to_string())?;
+    let format = if atlas.meta.app.to_lowercase().contains("aseprite") {
+        AtlasFormat::Aseprite
+    } else {
+        AtlasFormat::TexturePacker
+    };
+
+    let named_frames: Vec<(String, JsonFrame)> = match atlas.frames {
+        JsonFrames::Array(frames) => frames
+            .into_iter()
+            .enumerate()
+            .map(|(index, frame)| {
+                let name = frame.filename.clone().unwrap_or_else(|| index.to_string());
+                (name, frame)
+            })
+            .collect(),
+        JsonFrames::Hash(frames) => frames.0,
+    };
+
+    let mut frames = Vec::with_capacity(named_frames.len());
+    for (name, frame) in &named_frames {
+        if frame.rotated {
+            return Err(format!(
+                "frame \"{name}\" is rotated, which is not supported"
+            ));
+        }
+        frames.push(clip_frame(frame, frame_duration));
+    }
+
+    let mut result = Atlas {
+        frames: named_frames
+            .iter()
+            .zip(&frames)
+            .map(|((name, _frame), clip_frame)| (name.clone(), clip_frame.source))
+            .collect(),
+        clips: Vec::new(),
+    };
+
+    if format == AtlasFormat::Aseprite && !atlas.meta.frame_tags.is_empty() {
+        for tag in &atlas.meta.frame_tags {
+            if tag.from > tag.to || tag.to >= frames.len() {
+                return Err(format!("tag \"{}\" points outside the frames", tag.name));
+            }
+            let forward = &frames[tag.from..=tag.to];
+            let backward: Vec<ClipFrame> = forward.iter().rev().copied().collect();
+            let ordered: Vec<ClipFrame> = match tag.direction.as_str() {
+                "" | "forward" => forward.to_vec(),
+                "reverse" => backward,
+                "pingpong" => ping_pong(forward),
+                "pingpong_reverse" => ping_pong(&backward),
+                direction => {
+                    return Err(format!(
+                        "tag \"{}\" has unknown direction \"{direction}\"",
+                        tag.name
+                    ));
+                }
+            };
+            result.clips.push(clip(&tag.name, texture, ordered));
+        }
+    } else if !atlas.animations.is_empty() {
+        let index: HashMap<&str, usize> = named_frames
+            .iter()
+            .enumerate()
+            .map(|(index, (name, _frame))| (name.as_str(), index))
+            .collect();
+
+        let mut names: Vec<&String> = atlas.animations.keys().collect();
+        names.sort();
+        for name in names {
+            let mut clip_frames = Vec::new();
+            for frame_name in &atlas.animations[name] {
+                let Some(&frame) = index.get(frame_name.as_str()) else {
+                    return Err(format!(
+                        "animation \"{name}\" refers to unknown frame \"{frame_name}\""
+                    ));
+                };
+                clip_frames.push(frames[frame]);
+            }
+            result.clips.push(clip(name, texture, clip_frames));
+        }
+    } else {
+        let mut groups: Vec<(String, Vec<ClipFrame>)> = Vec::new();
+        for ((name, _frame), clip_frame) in named_frames.iter().zip(&frames) {
+            let base = sequence_name(name);
+            match groups.iter_mut().find(|(group, _frames)| *group == base) {
+                Some((_group, group_frames)) => group_frames.push(*clip_frame),
+                None => groups.push((base, vec![*clip_frame])),
+            }
+        }
+        for (name, clip_frames) in groups {
+            result.clips.push(clip(&name, texture, clip_frames));
+        }
+    }
+
+    Ok(result)
+}
+
+/// There and back again, without repeating the turning frames.
+fn ping_pong(frames: &[ClipFrame]) -> Vec<ClipFrame> {
+    frames
+        .iter()
+        .chain(
+            frames
+                .iter()
+                .rev()
+                .skip(1)
+                .take(frames.len().saturating_sub(2)),
+        )
+        .copied()
+        .collect()
+}
+
+fn clip_frame(frame: &JsonFrame, frame_duration: f32) -> ClipFrame {
+    let source = Rect::new(frame.frame.x, frame.frame.y, frame.frame.w, frame.frame.h);
+    let (offset, size) = match (frame.trimmed, frame.sprite_source_size, frame.source_size) {
+        (true, Some(trim), Some(size)) => (
+            Vec2 {
+                x: trim.x,
+                y: trim.y,
+            },
+            Vec2 {
+                x: size.w,
+                y: size.h,
+            },
+        ),
+        _ => (Vec2::ZERO, source.size()),
+    };
+
+    ClipFrame {
+        source,
+        offset,
+        size,
+        duration: frame
+            .duration
+            .map(|milliseconds| milliseconds / 1000.0)
+            .unwrap_or(frame_duration),
+    }
+}
+
+fn clip(name: &str, texture: &str, frames: Vec<ClipFrame>) -> AnimationClip {
+    AnimationClip {
+        name: name.to_string(),
+        texture: texture.to_string(),
+        frames,
+    }
+}
+
+/// `bird_yellow_02.png` -> `bird_yellow`
+fn sequence_name(frame_name: &str) -> String {
+    let stem = frame_name
+        .rsplit_once('.')
+        .map_or(frame_name, |(stem, _extension)| stem);
+    stem.trim_end_matches(|c: char| c.is_ascii_digit())
+        .trim_end_matches(['_', '-', ' '])
+        .to_string()
+}
@@ -1,15 +1,21 @@
 use bevy_ecs::prelude::*;
-use macroquad::time::get_frame_time;
 
 use crate::{
-    asset_loader::AssetLoader,
-    game::{GameState, GameStates},
+    asset_loader::FLOOR_TEXTURE,
+    game::GameState,
+    simulation::TICK,
+    sprite::{LAYER_FLOOR, Sprite},
     transform::{Position, Velocity},
 };
 
+/// Floor tiles in a row, enough to cover the widest view.
 const BACKGROUND_TILES_AMOUNT: u32 = 5;
+/// Width of a floor tile.
+pub const FLOOR_WIDTH: f32 = 336.0;
+/// How fast the floor scrolls, as fast as the pipes.
 const BACKGROUND_SPEED: Velocity = Velocity { x: -120.0, y: 0.0 };
 
+/// A floor tile, scrolled by [`update_background`].
 #[derive(Component)]
 pub struct Floor;
 
@@ -17,6 +23,7 @@ pub struct Floor;
 pub struct FloorBundle {
     position: Position,
     velocity: Velocity,
+    sprite: Sprite,
     is_type: Floor,
 }
 
@@ -25,31 +32,31 @@ impl FloorBundle {
         FloorBundle {
             position: Position { x, y: -30.0 },
             velocity: BACKGROUND_SPEED,
+            sprite: Sprite::new(FLOOR_TEXTURE, LAYER_FLOOR),
             is_type: Floor {},
         }
     }
 }
 
-pub fn spawn_background(mut commands: Commands, assets: Res<AssetLoader>) {
-    let floor_sprite = assets.get_texture("floor");
+/// Lays the floor tiles out side by side.
+pub fn spawn_background(mut commands: Commands) {
     for i in 0..BACKGROUND_TILES_AMOUNT {
-        commands.spawn(FloorBundle::new((i as f32) * floor_sprite.width()));
+        commands.spawn(FloorBundle::new((i as f32) * FLOOR_WIDTH));
     }
 }
 
+/// Scrolls the floor until the round is over, moving tiles that left on the
+/// left to the right end of the row.
 pub fn update_background(
     mut floor_query: Query<(&mut Position, &Velocity), With<Floor>>,
-    assets: Res<AssetLoader>,
     game_state: Res<GameState>,
 ) {
-    let floor_sprite = assets.get_texture("floor");
-
-    if game_state.game_state != GameStates::GameOver {
+    if !game_state.is_over() {
         for (mut position, velocity) in &mut floor_query {
-            position.x += velocity.x * get_frame_time();
+            position.x += velocity.x * TICK;
 
-            if position.x < -floor_sprite.width() {
-                position.x += (BACKGROUND_TILES_AMOUNT as f32) * floor_sprite.width();
+            if position.x < -FLOOR_WIDTH {
+                position.x += (BACKGROUND_TILES_AMOUNT as f32) * FLOOR_WIDTH;
             }
         }
     }
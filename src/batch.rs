@@ -0,0 +1,346 @@
+use std::thread;
+
+use crate::env::{Gap, OBSERVED_GAPS, Observation, RewardConfig};
+use crate::game::SCREEN_SIZE;
+use crate::pipe::{
+    OBSTACLE_AMOUNT, PIPE_SPEED, PIPE_WIDTH, PIPES_H_SEPARATION, PIPES_V_SEPARATION, random_gap_y,
+};
+use crate::player::{
+    FLOOR_COLLISION_Y, GRAVITY, JUMP_SPEED, PLAYER_COLLISION, PLAYER_SPRITE_SIZES, start_position,
+};
+use crate::simulation::{GameRng, TICK};
+
+const PIPES: usize = OBSTACLE_AMOUNT as usize;
+
+/// Many single-bird games stepped together, one array per field instead of
+/// one `World` per game. Every step does the same floating point operations
+/// in the same order as [`simulation_schedule`](crate::simulation::simulation_schedule),
+/// so a game here gives bit for bit the same results as a
+/// [`FlappyEnv`](crate::env::FlappyEnv) with the same seed and actions.
+pub struct BatchSim {
+    pub rewards: RewardConfig,
+    rng: Vec<GameRng>,
+    bird_x: Vec<f32>,
+    bird_y: Vec<f32>,
+    bird_velocity: Vec<f32>,
+    done: Vec<bool>,
+    score: Vec<u32>,
+    reward: Vec<f32>,
+    pipe_x: Vec<[f32; PIPES]>,
+    pipe_y: Vec<[f32; PIPES]>,
+    can_score: Vec<[bool; PIPES]>,
+}
+
+/// Mutable views of a range of games, so that ranges can be stepped on
+/// different threads.
+struct Lanes<'a> {
+    rewards: RewardConfig,
+    rng: &'a mut [GameRng],
+    bird_x: &'a mut [f32],
+    bird_y: &'a mut [f32],
+    bird_velocity: &'a mut [f32],
+    done: &'a mut [bool],
+    score: &'a mut [u32],
+    reward: &'a mut [f32],
+    pipe_x: &'a mut [[f32; PIPES]],
+    pipe_y: &'a mut [[f32; PIPES]],
+    can_score: &'a mut [[bool; PIPES]],
+}
+
+impl BatchSim {
+    /// One game per seed, each with the bird already flying.
+    pub fn new(seeds: impl IntoIterator<Item = u64>, rewards: RewardConfig) -> Self {
+        let mut batch = Self {
+            rewards,
+            rng: Vec::new(),
+            bird_x: Vec::new(),
+            bird_y: Vec::new(),
+            bird_velocity: Vec::new(),
+            done: Vec::new(),
+            score: Vec::new(),
+            reward: Vec::new(),
+            pipe_x: Vec::new(),
+            pipe_y: Vec::new(),
+            can_score: Vec::new(),
+        };
+        for seed in seeds {
+            batch.rng.push(GameRng::new(seed));
+            batch.bird_x.push(0.0);
+            batch.bird_y.push(0.0);
+            batch.bird_velocity.push(0.0);
+            batch.done.push(false);
+            batch.score.push(0);
+            batch.reward.push(0.0);
+            batch.pipe_x.push([0.0; PIPES]);
+            batch.pipe_y.push([0.0; PIPES]);
+            batch.can_score.push([true; PIPES]);
+            batch.reset(batch.len() - 1, seed);
+        }
+        batch
+    }
+
+    pub fn len(&self) -> usize {
+        self.rng.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rng.is_empty()
+    }
+
+    /// Starts game `index` again on the pipe layout of `seed`, the way
+    /// [`headless_world`](crate::simulation::headless_world) sets one up.
+    pub fn reset(&mut self, index: usize, seed: u64) {
+        let mut rng = GameRng::new(seed);
+        for pipe in 0..PIPES {
+            self.pipe_x[index][pipe] = SCREEN_SIZE + (pipe as f32) * PIPES_H_SEPARATION;
+            self.pipe_y[index][pipe] = random_gap_y(&mut rng);
+        }
+        self.can_score[index] = [true; PIPES];
+        self.rng[index] = rng;
+
+        let start = start_position(0, 1);
+        self.bird_x[index] = start.x;
+        self.bird_y[index] = start.y;
+        self.bird_velocity[index] = 0.0;
+        self.done[index] = false;
+        self.score[index] = 0;
+        self.reward[index] = 0.0;
+    }
+
+    /// Advances every game that is not over by one tick, flapping the games
+    /// whose entry in `flaps` is set.
+    pub fn step(&mut self, flaps: &[bool]) {
+        assert_eq!(flaps.len(), self.len(), "one flap per game");
+        self.lanes().step(flaps);
+    }
+
+    /// Same as [`step`](Self::step), with the games split across `threads`.
+    pub fn step_parallel(&mut self, flaps: &[bool], threads: usize) {
+        assert_eq!(flaps.len(), self.len(), "one flap per game");
+        let chunk = self.len().div_ceil(threads.max(1)).max(1);
+        let mut lanes = self.lanes();
+        let mut flaps = flaps;
+        thread::scope(|scope| {
+            while lanes.len() > chunk {
+                let (head, tail) = lanes.split_at(chunk);
+                let (head_flaps, tail_flaps) = flaps.split_at(chunk);
+                scope.spawn(move || head.step(head_flaps));
+                lanes = tail;
+                flaps = tail_flaps;
+            }
+            lanes.step(flaps);
+        });
+    }
+
+    /// Whether game `index` has crashed.
+    pub fn done(&self, index: usize) -> bool {
+        self.done[index]
+    }
+
+    pub fn score(&self, index: usize) -> u32 {
+        self.score[index]
+    }
+
+    /// What game `index` earned on the last step.
+    pub fn reward(&self, index: usize) -> f32 {
+        self.reward[index]
+    }
+
+    /// Height of the middle of the bird in game `index`.
+    pub fn bird_y(&self, index: usize) -> f32 {
+        self.bird_y[index] + PLAYER_SPRITE_SIZES.y / 2.0
+    }
+
+    pub fn bird_velocity(&self, index: usize) -> f32 {
+        self.bird_velocity[index]
+    }
+
+    /// Game `index` as [`FlappyEnv`](crate::env::FlappyEnv) reports it.
+    pub fn observation(&self, index: usize) -> Observation {
+        observe(
+            self.bird_x[index],
+            self.bird_y[index],
+            self.bird_velocity[index],
+            &self.pipe_x[index],
+            &self.pipe_y[index],
+        )
+    }
+
+    fn lanes(&mut self) -> Lanes<'_> {
+        Lanes {
+            rewards: self.rewards,
+            rng: &mut self.rng,
+            bird_x: &mut self.bird_x,
+            bird_y: &mut self.bird_y,
+            bird_velocity: &mut self.bird_velocity,
+            done: &mut self.done,
+            score: &mut self.score,
+            reward: &mut self.reward,
+            pipe_x: &mut self.pipe_x,
+            pipe_y: &mut self.pipe_y,
+            can_score: &mut self.can_score,
+        }
+    }
+}
+
+impl<'a> Lanes<'a> {
+    fn len(&self) -> usize {
+        self.rng.len()
+    }
+
+    fn split_at(self, mid: usize) -> (Lanes<'a>, Lanes<'a>) {
+        let (rng, rng_tail) = self.rng.split_at_mut(mid);
+        let (bird_x, bird_x_tail) = self.bird_x.split_at_mut(mid);
+        let (bird_y, bird_y_tail) = self.bird_y.split_at_mut(mid);
+        let (bird_velocity, bird_velocity_tail) = self.bird_velocity.split_at_mut(mid);
+        let (done, done_tail) = self.done.split_at_mut(mid);
+        let (score, score_tail) = self.score.split_at_mut(mid);
+        let (reward, reward_tail) = self.reward.split_at_mut(mid);
+        let (pipe_x, pipe_x_tail) = self.pipe_x.split_at_mut(mid);
+        let (pipe_y, pipe_y_tail) = self.pipe_y.split_at_mut(mid);
+        let (can_score, can_score_tail) = self.can_score.split_at_mut(mid);
+        (
+            Lanes {
+                rewards: self.rewards,
+                rng,
+                bird_x,
+                bird_y,
+                bird_velocity,
+                done,
+                score,
+                reward,
+                pipe_x,
+                pipe_y,
+                can_score,
+            },
+            Lanes {
+                rewards: self.rewards,
+                rng: rng_tail,
+                bird_x: bird_x_tail,
+                bird_y: bird_y_tail,
+                bird_velocity: bird_velocity_tail,
+                done: done_tail,
+                score: score_tail,
+                reward: reward_tail,
+                pipe_x: pipe_x_tail,
+                pipe_y: pipe_y_tail,
+                can_score: can_score_tail,
+            },
+        )
+    }
+
+    fn step(self, flaps: &[bool]) {
+        for (game, &flap) in flaps.iter().enumerate() {
+            if self.done[game] {
+                self.reward[game] = 0.0;
+                continue;
+            }
+
+            //add_gravity
+            self.bird_velocity[game] += GRAVITY * TICK;
+
+            //move_player, the bird only moves sideways once it has crashed
+            let bird_y = &mut self.bird_y[game];
+            *bird_y += self.bird_velocity[game] * TICK;
+            *bird_y = bird_y.min(FLOOR_COLLISION_Y - PLAYER_SPRITE_SIZES.y / 2.0);
+            if flap {
+                self.bird_velocity[game] = -JUMP_SPEED;
+            }
+
+            //move_pipe
+            let (pipe_x, pipe_y) = (&mut self.pipe_x[game], &mut self.pipe_y[game]);
+            for pipe in 0..PIPES {
+                pipe_x[pipe] += -PIPE_SPEED * TICK;
+
+                if pipe_x[pipe] < -PIPE_WIDTH {
+                    pipe_x[pipe] += (OBSTACLE_AMOUNT as f32) * PIPES_H_SEPARATION;
+                    pipe_y[pipe] = random_gap_y(&mut self.rng[game]);
+                    self.can_score[game][pipe] = true;
+                }
+            }
+
+            //collide_player
+            let center_x = self.bird_x[game] + PLAYER_SPRITE_SIZES.x / 2.0;
+            let center_y = self.bird_y[game] + PLAYER_SPRITE_SIZES.y / 2.0;
+            let mut reward = 0.0;
+            //the floor first, as in collide_player
+            let mut crashed = center_y + PLAYER_COLLISION.y / 2.0 > FLOOR_COLLISION_Y;
+            if !crashed {
+                for pipe in 0..PIPES {
+                    let (x_min, x_max) = (pipe_x[pipe], pipe_x[pipe] + PIPE_WIDTH);
+                    let (y_min, y_max) = (pipe_y[pipe], pipe_y[pipe] + PIPES_V_SEPARATION);
+
+                    if center_x - PLAYER_COLLISION.x / 2.0 < x_max
+                        && center_x + PLAYER_COLLISION.x / 2.0 > x_min
+                        && !(center_y - PLAYER_COLLISION.y / 2.0 > y_min
+                            && center_y + PLAYER_COLLISION.y / 2.0 < y_max - 10.0)
+                    {
+                        crashed = true;
+                        break;
+                    }
+
+                    if center_x > x_max && self.can_score[game][pipe] {
+                        self.can_score[game][pipe] = false;
+                        self.score[game] += 1;
+                        reward += self.rewards.pipe;
+                    }
+                }
+            }
+
+            //the same reward as FlappyEnv::step
+            if crashed {
+                self.done[game] = true;
+                reward += self.rewards.crash;
+            } else {
+                reward += self.rewards.alive;
+                if let Some(pipe) = pipes_ahead(self.bird_x[game], pipe_x).next() {
+                    let (top, bottom) = (pipe_y[pipe], pipe_y[pipe] + PIPES_V_SEPARATION);
+                    let middle = (top + bottom) / 2.0;
+                    reward -= self.rewards.off_center * (center_y - middle).abs() / (bottom - top);
+                }
+            }
+            self.reward[game] = reward;
+        }
+    }
+}
+
+fn observe(
+    bird_x: f32,
+    bird_y: f32,
+    bird_velocity: f32,
+    pipe_x: &[f32; PIPES],
+    pipe_y: &[f32; PIPES],
+) -> Observation {
+    let left = bird_left(bird_x);
+    Observation {
+        bird_y: bird_y + PLAYER_SPRITE_SIZES.y / 2.0,
+        bird_velocity,
+        gaps: pipes_ahead(bird_x, pipe_x)
+            .take(OBSERVED_GAPS)
+            .map(|pipe| Gap {
+                distance: pipe_x[pipe] + PIPE_WIDTH - left,
+                top: pipe_y[pipe],
+                bottom: pipe_y[pipe] + PIPES_V_SEPARATION,
+            })
+            .collect(),
+    }
+}
+
+fn bird_left(bird_x: f32) -> f32 {
+    bird_x + PLAYER_SPRITE_SIZES.x / 2.0 - PLAYER_COLLISION.x / 2.0
+}
+
+/// The pipes the bird still has to pass, nearest first.
+fn pipes_ahead(bird_x: f32, pipe_x: &[f32; PIPES]) -> impl Iterator<Item = usize> + '_ {
+    let left = bird_left(bird_x);
+    let mut ahead = [0; PIPES];
+    let mut count = 0;
+    for (pipe, x) in pipe_x.iter().enumerate() {
+        if x + PIPE_WIDTH > left {
+            ahead[count] = pipe;
+            count += 1;
+        }
+    }
+    ahead[..count].sort_by(|&a, &b| pipe_x[a].total_cmp(&pipe_x[b]));
+    ahead.into_iter().take(count)
+}
@@ -0,0 +1,78 @@
+//! Lets the autopilot play rounds without a window, at every difficulty, to
+//! check how hard the game is after changing it:
+//!
+//! ```text
+//! cargo run --bin autopilot -- [--rounds N] [--seed N] [--seconds N]
+//! ```
+//!
+//! Every difficulty flies the same `--rounds` layouts (20 by default),
+//! seeded from `--seed` on; a round stops after `--seconds` (120 by default)
+//! of game time. Exits with an error when a harder bot scores less on
+//! average than an easier one.
+
+use bevy_ecs::prelude::*;
+use flappy::args::{Args, exit};
+use flappy::bot::{Bot, Difficulty};
+use flappy::game::{GameState, GameStates, MatchSettings};
+use flappy::player::{Controller, Player};
+use flappy::simulation::{TICKS_PER_SECOND, headless_world, simulation_schedule};
+
+fn main() {
+    let args = Args::from_env();
+    let value = |flag: &str, default: u64| {
+        args.parse(flag, default)
+            .unwrap_or_else(|error| exit(&error))
+    };
+    let rounds = value("--rounds", 20);
+    let seed = value("--seed", 0);
+    let max_ticks = value("--seconds", 120) as u32 * TICKS_PER_SECOND;
+
+    let mut means = Vec::new();
+    for difficulty in Difficulty::ALL {
+        let (scores, crashes): (Vec<u32>, Vec<bool>) = (seed..seed + rounds)
+            .map(|round_seed| play_round(difficulty, round_seed, max_ticks))
+            .unzip();
+        let crashed = crashes.iter().filter(|crashed| **crashed).count();
+        let mean = scores.iter().sum::<u32>() as f32 / scores.len().max(1) as f32;
+
+        println!(
+            "{:6}  mean {:6.1}  min {:4}  max {:4}  crashed {}/{}",
+            difficulty.name(),
+            mean,
+            scores.iter().min().unwrap_or(&0),
+            scores.iter().max().unwrap_or(&0),
+            crashed,
+            rounds
+        );
+        means.push(mean);
+    }
+
+    if means.windows(2).any(|pair| pair[0] > pair[1]) {
+        eprintln!("a harder bot scored less than an easier one");
+        std::process::exit(1);
+    }
+}
+
+/// The score of one bot on the layout of `seed`, and whether it crashed
+/// before `max_ticks`.
+fn play_round(difficulty: Difficulty, seed: u64, max_ticks: u32) -> (u32, bool) {
+    let mut world = headless_world(MatchSettings::default(), seed);
+    let mut query = world.query_filtered::<Entity, With<Player>>();
+    let birds: Vec<Entity> = query.iter(&world).collect();
+    for bird in birds {
+        world
+            .entity_mut(bird)
+            .insert((Controller::Bot, Bot::new(difficulty, seed)));
+    }
+    world.resource_mut::<GameState>().game_state = GameStates::Play;
+
+    let mut simulation = simulation_schedule();
+    for _ in 0..max_ticks {
+        simulation.run(&mut world);
+        let game_state = world.resource::<GameState>();
+        if game_state.game_state == GameStates::GameOver {
+            return (game_state.score, true);
+        }
+    }
+    (world.resource::<GameState>().score, false)
+}
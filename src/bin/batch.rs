@@ -0,0 +1,131 @@
+//! Checks the batch simulator against the game:
+//!
+//! ```text
+//! cargo run --release --bin batch -- [--verify N] [--threads N]
+//! ```
+//!
+//! Plays `--verify` games (64 by default) both in a
+//! [`FlappyEnv`](flappy::env::FlappyEnv) and in a [`BatchSim`] stepped on
+//! `--threads` (all cores by default), with the same seeds and actions, and
+//! exits with an error at the first step where any observation, reward or
+//! score differs by a single bit. `cargo bench --bench batch` measures how
+//! fast both step.
+
+use flappy::args::{Args, exit};
+use flappy::batch::BatchSim;
+use flappy::bot::{AIM, should_flap};
+use flappy::env::{Action, FlappyEnv, Observation, RewardConfig};
+use flappy::simulation::GameRng;
+
+/// Steps after which a verified game stops even if the bird still flies.
+const VERIFY_STEPS: u32 = 20_000;
+
+/// Neighbouring seeds start with nearly the same gaps, so the verified games
+/// use seeds far apart.
+fn spread(game: u64) -> u64 {
+    game.wrapping_mul(0x9e37_79b9_7f4a_7c15)
+}
+
+fn main() {
+    let args = Args::from_env();
+    let value = |flag: &str, default: u64| {
+        args.parse(flag, default)
+            .unwrap_or_else(|error| exit(&error))
+    };
+    let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    let verify_games = value("--verify", 64);
+    let threads = value("--threads", threads as u64) as usize;
+
+    if let Err(message) = verify(verify_games, threads) {
+        eprintln!("{message}");
+        std::process::exit(1);
+    }
+    println!("{verify_games} games match the game bit for bit");
+}
+
+/// Flaps when the bird sinks below the middle of the next gap, now and then
+/// at random, so the games crash at different times.
+fn policy(observation: &Observation, rng: &mut GameRng) -> bool {
+    let gap = observation
+        .gaps
+        .first()
+        .map(|gap| (gap.top, gap.bottom - gap.top));
+    should_flap(observation.bird_y, observation.bird_velocity, gap, AIM)
+        || rng.gen_range(0.0, 1.0) < 0.001
+}
+
+fn verify(games: u64, threads: usize) -> Result<(), String> {
+    let rewards = RewardConfig {
+        off_center: 0.5,
+        ..Default::default()
+    };
+    let mut envs: Vec<FlappyEnv> = (0..games).map(|_| FlappyEnv::new(rewards, None)).collect();
+    let mut observations: Vec<Observation> = envs
+        .iter_mut()
+        .enumerate()
+        .map(|(game, env)| env.reset(spread(game as u64)))
+        .collect();
+    let mut done = vec![false; envs.len()];
+    let mut rngs: Vec<GameRng> = (0..games).map(|seed| GameRng::new(!spread(seed))).collect();
+    let mut batch = BatchSim::new((0..games).map(spread), rewards);
+
+    for step in 0..VERIFY_STEPS {
+        if done.iter().all(|done| *done) {
+            break;
+        }
+        let flaps: Vec<bool> = observations
+            .iter()
+            .zip(&mut rngs)
+            .map(|(observation, rng)| policy(observation, rng))
+            .collect();
+        batch.step_parallel(&flaps, threads);
+
+        for (game, env) in envs.iter_mut().enumerate() {
+            if done[game] {
+                continue;
+            }
+            let action = if flaps[game] {
+                Action::Flap
+            } else {
+                Action::Idle
+            };
+            let (observation, reward, env_done, info) = env.step(action);
+            let batch_observation = batch.observation(game);
+            if !same_bits(&observation, &batch_observation)
+                || reward.to_bits() != batch.reward(game).to_bits()
+                || env_done != batch.done(game)
+                || info.score != batch.score(game)
+            {
+                return Err(format!(
+                    "game {game} differs on step {}:\n  game:  {observation:?} reward {reward} done {env_done} score {}\n  batch: {batch_observation:?} reward {} done {} score {}",
+                    step + 1,
+                    info.score,
+                    batch.reward(game),
+                    batch.done(game),
+                    batch.score(game),
+                ));
+            }
+            observations[game] = observation;
+            done[game] = env_done;
+        }
+    }
+    Ok(())
+}
+
+fn same_bits(a: &Observation, b: &Observation) -> bool {
+    let bits = |observation: &Observation| {
+        let mut bits = vec![
+            observation.bird_y.to_bits(),
+            observation.bird_velocity.to_bits(),
+        ];
+        for gap in &observation.gaps {
+            bits.extend([
+                gap.distance.to_bits(),
+                gap.top.to_bits(),
+                gap.bottom.to_bits(),
+            ]);
+        }
+        bits
+    };
+    bits(a) == bits(b)
+}
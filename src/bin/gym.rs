@@ -0,0 +1,105 @@
+//! Serves [`FlappyEnv`] over stdin and stdout, one JSON object per line, so
+//! trainers in any language can drive it:
+//!
+//! ```text
+//! {"cmd": "configure", "rewards": {"alive": 0.1, "pipe": 1.0, "crash": -1.0, "off_center": 0.0}, "max_steps": 10000}
+//! {"ok": true}
+//! {"cmd": "reset", "seed": 42}
+//! {"observation": {"bird_y": -142.0, "bird_velocity": 0.0, "gaps": [...]}}
+//! {"cmd": "step", "action": 1}
+//! {"observation": {...}, "reward": 0.1, "done": false, "info": {"score": 0, "steps": 1, "truncated": false}}
+//! {"cmd": "close"}
+//! ```
+//!
+//! A request that can't be read gets `{"error": "..."}` back.
+
+use std::io::{BufRead, Write};
+
+use flappy::env::{Action, FlappyEnv, Observation, RewardConfig, StepInfo};
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum Request {
+    Configure {
+        #[serde(default)]
+        rewards: RewardConfig,
+        #[serde(default)]
+        max_steps: Option<u32>,
+    },
+    Reset {
+        #[serde(default)]
+        seed: u64,
+    },
+    Step {
+        action: Action,
+    },
+    Close,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Response {
+    Configured {
+        ok: bool,
+    },
+    Reset {
+        observation: Observation,
+    },
+    Step {
+        observation: Observation,
+        reward: f32,
+        done: bool,
+        info: StepInfo,
+    },
+    Error {
+        error: String,
+    },
+}
+
+fn main() {
+    let mut env = FlappyEnv::default();
+    let stdout = std::io::stdout();
+    let mut output = stdout.lock();
+
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::Configure { rewards, max_steps }) => {
+                env.rewards = rewards;
+                env.max_steps = max_steps;
+                Response::Configured { ok: true }
+            }
+            Ok(Request::Reset { seed }) => Response::Reset {
+                observation: env.reset(seed),
+            },
+            Ok(Request::Step { action }) => {
+                let (observation, reward, done, info) = env.step(action);
+                Response::Step {
+                    observation,
+                    reward,
+                    done,
+                    info,
+                }
+            }
+            Ok(Request::Close) => break,
+            Err(error) => Response::Error {
+                error: error.to_string(),
+            },
+        };
+
+        let json = serde_json::to_string(&response).expect("responses are always valid JSON");
+        if writeln!(output, "{json}")
+            .and_then(|_| output.flush())
+            .is_err()
+        {
+            break;
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Plays a network game without a window, for trying the rollback netcode
+//! with two processes on one machine:
+//!
+//! ```text
+//! cargo run --bin netplay -- --local 127.0.0.1:7000 --peer 127.0.0.1:7001 --player 0 \
+//!     --seed 7 --latency 80 --jitter 20 --loss 0.1
+//! cargo run --bin netplay -- --local 127.0.0.1:7001 --peer 127.0.0.1:7000 --player 1 \
+//!     --seed 7 --latency 80 --jitter 20 --loss 0.1
+//! ```
+//!
+//! Each bird flaps on its own simple rule. Both processes print the checksum
+//! of the confirmed game every second of game time and exit once `--frames`
+//! frames (1800 by default) are confirmed; the lines must be identical.
+//! With `--save-state PATH` each process also writes its final game to a
+//! file, so two games that went apart can be compared with `diff`.
+
+use std::time::{Duration, Instant};
+
+use bevy_ecs::prelude::*;
+use flappy::args::{Args, exit};
+use flappy::bot::{AIM, next_gap, should_flap};
+use flappy::game::{GameState, GameStates};
+use flappy::net::{NetplayConfig, RollbackSession};
+use flappy::pipe::Pipe;
+use flappy::player::{PLAYER_SPRITE_SIZES, Player};
+use flappy::save::SaveState;
+use flappy::simulation::{GameEvents, PlayerInput, TICK, headless_world, simulation_schedule};
+use flappy::transform::{Position, Velocity};
+
+const DEFAULT_FRAMES: u32 = 1800;
+/// Time to keep answering the peer after finishing, so it can finish too.
+const LINGER: Duration = Duration::from_secs(2);
+
+fn main() {
+    let args = Args::from_env();
+    let config = match NetplayConfig::from_args(&args) {
+        Ok(Some(config)) => config,
+        Ok(None) => exit(
+            "usage: netplay --local ADDR --peer ADDR [--player N] [--seed N] [--latency MS] [--jitter MS] [--loss FRACTION] [--frames N] [--save-state PATH]",
+        ),
+        Err(error) => exit(&error),
+    };
+    let frames: u32 = args
+        .parse("--frames", DEFAULT_FRAMES)
+        .unwrap_or_else(|error| exit(&error));
+    let save_path = args
+        .value("--save-state")
+        .unwrap_or_else(|error| exit(&error));
+
+    let mut world = headless_world(config.match_settings(), config.seed);
+    config.assign_controllers(&mut world);
+
+    let mut simulation = simulation_schedule();
+    let mut session = RollbackSession::new(&config)
+        .unwrap_or_else(|error| exit(&format!("cannot open {}: {error}", config.local)));
+
+    println!(
+        "bird {} on {}, waiting for {}",
+        config.player + 1,
+        config.local,
+        config.peer
+    );
+
+    let mut finished: Option<Instant> = None;
+    let mut last_update = Instant::now();
+    loop {
+        if session.connected() {
+            decide_flap(&mut world, config.player);
+        }
+
+        let now = Instant::now();
+        let frame_time = (now - last_update).as_secs_f32();
+        last_update = now;
+        session.update(&mut world, &mut simulation, frame_time, frames);
+        world.resource_mut::<GameEvents>().0.clear();
+
+        for (frame, checksum) in session.take_checksums() {
+            println!("frame {frame:5} checksum {checksum:016x}");
+        }
+
+        let done = session.confirmed_frame() >= frames && session.peer_has_inputs_until(frames);
+        match finished {
+            None if done => finished = Some(now),
+            Some(time) if now - time >= LINGER => break,
+            _ => {}
+        }
+
+        std::thread::sleep(Duration::from_secs_f32(TICK / 4.0));
+    }
+
+    let game_state = world.resource::<GameState>();
+    let result = match (game_state.game_state, game_state.winner) {
+        (GameStates::GameOver, Some(winner)) => format!("P{} won", winner + 1),
+        (GameStates::GameOver, None) => "draw".to_string(),
+        _ => "still flying".to_string(),
+    };
+    println!(
+        "done after {} frames, {}, {} rollbacks, {} frames resimulated, {} ticks stalled",
+        frames,
+        result,
+        session.stats.rollbacks,
+        session.stats.resimulated_frames,
+        session.stats.stalled_ticks
+    );
+
+    if let Some(path) = save_path
+        && let Err(error) = SaveState::take(&mut world).save(path)
+    {
+        exit(&error.to_string());
+    }
+}
+
+/// Flaps when the bird falls below the middle of the next gap, a little
+/// differently per bird so the two don't mirror each other.
+///
+/// Not a [`flappy::bot::Bot`]: bots flap from inside the simulation, so their
+/// flaps would never reach the peer. This presses the flap key instead.
+fn decide_flap(world: &mut World, index: usize) {
+    let gaps: Vec<(Position, f32)> = world
+        .query::<(&Position, &Pipe)>()
+        .iter(world)
+        .map(|(position, pipe)| (*position, pipe.gap_height))
+        .collect();
+
+    let mut query = world.query::<(&Player, &Position, &Velocity, &mut PlayerInput)>();
+    for (player, position, velocity, mut input) in query.iter_mut(world) {
+        if player.index != index || !player.alive {
+            continue;
+        }
+        let center = position.y + PLAYER_SPRITE_SIZES.y / 2.0;
+        let gaps = gaps.iter().map(|(pipe, gap_height)| (pipe, *gap_height));
+        let gap = next_gap(gaps, position.x).map(|(pipe, gap_height)| (pipe.y, gap_height));
+        if should_flap(center, velocity.y, gap, AIM - index as f32 * 0.04) {
+            input.flap = true;
+        }
+    }
+}
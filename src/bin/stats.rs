@@ -0,0 +1,29 @@
+//! Prints the lifetime stats for analysis, as CSV or as JSON:
+//!
+//! ```text
+//! cargo run --bin stats -- [--json] [--stats PATH] > stats.csv
+//! ```
+//!
+//! The stats are read from `stats.json` unless `--stats` names another
+//! file. The CSV has one `stat,value` row per stat and one per score.
+
+use flappy::args::{Args, exit};
+use flappy::stats::{STATS_PATH, Stats};
+
+fn main() {
+    let args = Args::from_env();
+    let path = args
+        .value("--stats")
+        .unwrap_or_else(|error| exit(&error))
+        .unwrap_or(STATS_PATH);
+    let stats = Stats::load(path).unwrap_or_else(|error| exit(&error.to_string()));
+
+    if args.has("--json") {
+        match serde_json::to_string_pretty(&stats) {
+            Ok(json) => println!("{json}"),
+            Err(error) => exit(&error.to_string()),
+        }
+    } else {
+        print!("{}", stats.to_csv());
+    }
+}
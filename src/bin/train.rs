@@ -0,0 +1,56 @@
+//! Breeds neural networks that play the game, without a window and as fast
+//! as the machine allows:
+//!
+//! ```text
+//! cargo run --release --bin train -- [--generations N] [--save champion.json]
+//!     [--load genome.json] [--population N] [--elite N] [--tournament N]
+//!     [--crossover RATE] [--mutation-rate RATE] [--mutation-strength N]
+//!     [--seed N] [--max-seconds N]
+//! ```
+//!
+//! Prints the fitness of every generation and saves the fittest genome seen
+//! to `--save` (`champion.json` by default) after each one, so training can
+//! be stopped at any time. `--load` starts from a saved genome instead of
+//! random ones.
+
+use flappy::args::{Args, exit};
+use flappy::game::MatchSettings;
+use flappy::neuro::{EvolutionConfig, Genome, Trainer, training_schedule};
+use flappy::simulation::{GameEvents, headless_world};
+
+fn main() {
+    let args = Args::from_env();
+    let config = EvolutionConfig::from_args(&args).unwrap_or_else(|error| exit(&error));
+    let generations: u32 = args
+        .parse("--generations", 100)
+        .unwrap_or_else(|error| exit(&error));
+    let save_path = args
+        .value("--save")
+        .unwrap_or_else(|error| exit(&error))
+        .unwrap_or("champion.json");
+    let load_path = args.value("--load").unwrap_or_else(|error| exit(&error));
+    let ancestors = match load_path {
+        Some(path) => vec![Genome::load(path).unwrap_or_else(|error| exit(&error.to_string()))],
+        None => Vec::new(),
+    };
+
+    let mut world = headless_world(MatchSettings::default(), config.seed);
+    let mut trainer = Trainer::new(config, ancestors);
+    let mut simulation = training_schedule();
+    trainer.start(&mut world);
+
+    while trainer.generation() < generations {
+        simulation.run(&mut world);
+        world.resource_mut::<GameEvents>().0.clear();
+
+        if let Some(stats) = trainer.update(&mut world) {
+            println!("{stats}");
+            if let Some(champion) = trainer.champion() {
+                champion
+                    .save(save_path)
+                    .unwrap_or_else(|error| exit(&error.to_string()));
+            }
+        }
+    }
+    println!("saved the champion to {save_path}");
+}
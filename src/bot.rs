@@ -0,0 +1,213 @@
+use bevy_ecs::prelude::*;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game::{GameState, GameStates, MatchSettings, SCREEN_SIZE};
+use crate::pipe::{PIPE_WIDTH, Pipe};
+use crate::player::{MAX_PLAYERS, PLAYER_COLLISION, PLAYER_SPRITE_SIZES, Player};
+use crate::simulation::{GameRng, PlayerInput};
+use crate::transform::{Position, Velocity};
+
+/// How far down into the gap a bot lets its bird fall before flapping, as a
+/// share of the gap height.
+pub const AIM: f32 = 0.55;
+/// Seconds on the start screen without a key press before the demo starts.
+pub const ATTRACT_DELAY: f32 = 10.0;
+/// Seconds the game over screen stays up during the demo.
+const ATTRACT_GAME_OVER: f32 = 2.0;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "EASY",
+            Difficulty::Normal => "NORM",
+            Difficulty::Hard => "HARD",
+        }
+    }
+
+    /// Ticks between deciding to flap and flapping.
+    pub fn reaction_ticks(&self) -> u32 {
+        match self {
+            Difficulty::Easy => 2,
+            Difficulty::Normal => 1,
+            Difficulty::Hard => 0,
+        }
+    }
+
+    /// Largest distance in pixels between where a bot aims and the best spot.
+    pub fn aim_noise(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 8.0,
+            Difficulty::Normal => 6.0,
+            Difficulty::Hard => 0.0,
+        }
+    }
+}
+
+/// An autopilot flying one bird, for birds whose [`Controller`] is
+/// `Controller::Bot`.
+///
+/// [`Controller`]: crate::player::Controller
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Bot {
+    pub difficulty: Difficulty,
+    rng: GameRng,
+    /// Offset of the current aim from the best spot.
+    aim: f32,
+    /// Ticks left until a decided flap happens.
+    reaction: Option<u32>,
+}
+
+impl Bot {
+    /// Bots with the same `seed` make the same mistakes.
+    pub fn new(difficulty: Difficulty, seed: u64) -> Self {
+        let mut bot = Self {
+            difficulty,
+            rng: GameRng::new(seed),
+            aim: 0.0,
+            reaction: None,
+        };
+        bot.pick_aim();
+        bot
+    }
+
+    fn pick_aim(&mut self) {
+        let noise = self.difficulty.aim_noise();
+        self.aim = self.rng.gen_range(-noise, noise);
+    }
+}
+
+/// The pipe the bird at `x` has to fly through next, of pipes given with the
+/// height of their gap; its `y` is the top of the gap.
+pub fn next_gap<'a>(
+    pipes: impl IntoIterator<Item = (&'a Position, f32)>,
+    x: f32,
+) -> Option<(Position, f32)> {
+    let left = x + PLAYER_SPRITE_SIZES.x / 2.0 - PLAYER_COLLISION.x / 2.0;
+    pipes
+        .into_iter()
+        .filter(|(pipe, _)| pipe.x + PIPE_WIDTH > left)
+        .min_by(|(a, _), (b, _)| a.x.total_cmp(&b.x))
+        .map(|(pipe, gap_height)| (*pipe, gap_height))
+}
+
+/// Whether a bird with its middle at `y`, falling at `velocity`, flaps to
+/// stay in the next gap, given by its top and height: once it sinks below
+/// `aim` of the way down the gap, or below the middle of the screen between
+/// pipes. For tools that play without the reaction time and noise of a
+/// [`Bot`].
+pub fn should_flap(y: f32, velocity: f32, gap: Option<(f32, f32)>, aim: f32) -> bool {
+    let target = gap.map_or(-SCREEN_SIZE / 2.0, |(top, height)| top + height * aim);
+    velocity > 0.0 && y > target
+}
+
+/// Flaps the bots' birds whenever they fall below their aim in the next gap.
+pub fn drive_bots(
+    game_state: Res<GameState>,
+    pipe_query: Query<(&Position, &Pipe)>,
+    mut bot_query: Query<(&Player, &Position, &Velocity, &mut PlayerInput, &mut Bot)>,
+) {
+    if game_state.game_state != GameStates::Play {
+        return;
+    }
+
+    for (player, position, velocity, mut input, mut bot) in &mut bot_query {
+        if !player.alive {
+            continue;
+        }
+
+        let center = position.y + PLAYER_SPRITE_SIZES.y / 2.0;
+        //between the pipes the bot aims for the middle of the screen
+        let pipes = pipe_query
+            .iter()
+            .map(|(pipe, Pipe { gap_height, .. })| (pipe, *gap_height));
+        let target =
+            next_gap(pipes, position.x).map_or(-SCREEN_SIZE / 2.0, |(pipe, gap_height)| {
+                pipe.y + gap_height * AIM
+            }) + bot.aim;
+        if bot.reaction.is_none() && velocity.y > 0.0 && center > target {
+            bot.reaction = Some(bot.difficulty.reaction_ticks());
+        }
+
+        match bot.reaction {
+            Some(0) => {
+                input.flap = true;
+                bot.reaction = None;
+                bot.pick_aim();
+            }
+            Some(ticks) => bot.reaction = Some(ticks - 1),
+            None => {}
+        }
+    }
+}
+
+/// Lets bots play on their own after a while on the start screen, until a
+/// key is pressed.
+#[derive(Resource, Debug, Default)]
+pub struct AttractMode {
+    idle: f32,
+    /// The settings picked on the start screen, while the demo runs.
+    saved: Option<MatchSettings>,
+}
+
+impl AttractMode {
+    pub fn active(&self) -> bool {
+        self.saved.is_some()
+    }
+}
+
+pub fn attract_mode(
+    mut attract: ResMut<AttractMode>,
+    mut game_state: ResMut<GameState>,
+    mut settings: ResMut<MatchSettings>,
+) {
+    let pressed = get_last_key_pressed().is_some();
+
+    if let Some(saved) = attract.saved {
+        if pressed {
+            *settings = saved;
+            attract.saved = None;
+            attract.idle = 0.0;
+            game_state.game_state = GameStates::Restart;
+            return;
+        }
+
+        attract.idle += get_frame_time();
+        match game_state.game_state {
+            GameStates::PressStart => {
+                game_state.game_state = GameStates::Play;
+                attract.idle = 0.0;
+            }
+            GameStates::GameOver | GameStates::LevelComplete
+                if attract.idle >= ATTRACT_GAME_OVER =>
+            {
+                game_state.game_state = GameStates::Restart;
+            }
+            GameStates::Play => attract.idle = 0.0,
+            _ => {}
+        }
+        return;
+    }
+
+    if pressed || game_state.game_state != GameStates::PressStart {
+        attract.idle = 0.0;
+        return;
+    }
+    attract.idle += get_frame_time();
+    if attract.idle >= ATTRACT_DELAY {
+        attract.saved = Some(*settings);
+        attract.idle = 0.0;
+        settings.bots = [Some(Difficulty::Hard); MAX_PLAYERS];
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use bevy_ecs::prelude::*;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::game::{GameState, GameStates};
+use crate::level::{Level, Pickup, despawn_level, spawn_level};
+use crate::player::Player;
+use crate::simulation::{GameEvent, GameEvents};
+use crate::sprite::Sprite;
+
+/// Most stars a level gives.
+pub const MAX_STARS: usize = 3;
+
+/// A campaign file: levels played in order, each unlocked by clearing the
+/// one before. Paths are relative to the campaign file:
+///
+/// ```json
+/// {
+///   "name": "FLAPPY ISLAND",
+///   "levels": [
+///     { "path": "first_flight.json" },
+///     { "path": "bounce_house.json",
+///       "stars": [{ "type": "score", "at_least": 13 }, { "type": "no_damage" }] }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CampaignFile {
+    pub name: String,
+    pub levels: Vec<CampaignEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CampaignEntry {
+    pub path: String,
+    /// One to three goals, each worth a star. Left out, they are reaching
+    /// par, collecting every pickup and clearing without a crash.
+    #[serde(default)]
+    pub stars: Vec<Goal>,
+}
+
+/// What a clear has to achieve for a star.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Goal {
+    Score {
+        at_least: u32,
+    },
+    /// Collecting `at_least` pickups, or all of them when left out.
+    Pickups {
+        #[serde(default)]
+        at_least: Option<u32>,
+    },
+    /// No bird crashed since the level was chosen: a game over and a retry
+    /// count as damage, and so does one of several birds crashing before the
+    /// others reach the finish line.
+    NoDamage,
+}
+
+impl Goal {
+    pub fn name(&self) -> String {
+        match self {
+            Goal::Score { at_least } => format!("SCORE {at_least}"),
+            Goal::Pickups {
+                at_least: Some(at_least),
+            } => format!("{at_least} PICKUPS"),
+            Goal::Pickups { at_least: None } => "ALL PICKUPS".to_string(),
+            Goal::NoDamage => "NO CRASHES".to_string(),
+        }
+    }
+
+    pub fn met(&self, result: &LevelResult) -> bool {
+        match self {
+            Goal::Score { at_least } => result.score >= *at_least,
+            Goal::Pickups { at_least } => {
+                result.pickups >= at_least.unwrap_or(result.total_pickups)
+            }
+            Goal::NoDamage => result.no_damage,
+        }
+    }
+}
+
+/// The best clear of each level, by its path in the campaign file. A level
+/// is in here once it was cleared.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Progress {
+    pub levels: BTreeMap<String, LevelProgress>,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LevelProgress {
+    pub stars: usize,
+    pub best_score: u32,
+}
+
+impl Progress {
+    /// Reads the progress saved at `path`, or starts anew when there is none.
+    pub fn load(path: &str) -> Result<Self> {
+        if !Path::new(path).exists() {
+            return Ok(Self::default());
+        }
+        let json = std::fs::read_to_string(path).map_err(|error| Error::asset(path, error))?;
+        serde_json::from_str(&json).map_err(|error| Error::asset(path, error))
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(|error| Error::write(path, error))?;
+        std::fs::write(path, json).map_err(|error| Error::write(path, error))
+    }
+}
+
+/// How a clear went.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelResult {
+    pub score: u32,
+    pub pickups: u32,
+    pub total_pickups: u32,
+    pub no_damage: bool,
+    /// Whether each goal of the level was met, in order.
+    pub goals: Vec<bool>,
+    pub new_best: bool,
+}
+
+impl LevelResult {
+    pub fn stars(&self) -> usize {
+        self.goals.iter().filter(|met| **met).count()
+    }
+}
+
+/// The level select screen, a level being played or the results of a clear.
+/// The game itself stays `GameStates::Paused` outside of levels.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum CampaignStates {
+    #[default]
+    LevelSelect,
+    Playing,
+    Results,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CampaignLevel {
+    /// The path in the campaign file, which progress is saved under.
+    pub key: String,
+    pub level: Level,
+    pub goals: Vec<Goal>,
+}
+
+/// A campaign being played, with the progress saved next to its file.
+#[derive(Resource, Debug, Clone)]
+pub struct Campaign {
+    pub state: CampaignStates,
+    pub name: String,
+    pub levels: Vec<CampaignLevel>,
+    pub progress: Progress,
+    pub progress_path: String,
+    /// The level chosen on the level select screen, or being played.
+    pub selected: usize,
+    pub result: Option<LevelResult>,
+    /// Whether a bird crashed since the level was chosen.
+    pub crashed: bool,
+    /// Whether the level entities have to be spawned again.
+    dirty: bool,
+}
+
+impl Campaign {
+    /// Loads the campaign at `path` with all its levels, and the progress
+    /// saved for it in `<path without .json>.progress.json`.
+    pub fn load(path: &str) -> Result<Self> {
+        let json = std::fs::read_to_string(path).map_err(|error| Error::asset(path, error))?;
+        let file: CampaignFile =
+            serde_json::from_str(&json).map_err(|error| Error::asset(path, error))?;
+        if file.levels.is_empty() {
+            return Err(Error::asset(path, "a campaign needs at least one level"));
+        }
+
+        let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+        let mut levels = Vec::new();
+        let mut errors = Vec::new();
+        for entry in &file.levels {
+            if entry.stars.len() > MAX_STARS {
+                errors.push(Error::asset(
+                    path,
+                    format!("{}: at most {MAX_STARS} stars", entry.path),
+                ));
+            }
+            let level_path = directory.join(&entry.path);
+            match Level::load(&level_path.to_string_lossy()) {
+                Ok(level) => levels.push(CampaignLevel {
+                    key: entry.path.clone(),
+                    goals: if entry.stars.is_empty() {
+                        vec![
+                            Goal::Score {
+                                at_least: level.par,
+                            },
+                            Goal::Pickups { at_least: None },
+                            Goal::NoDamage,
+                        ]
+                    } else {
+                        entry.stars.clone()
+                    },
+                    level,
+                }),
+                Err(error) => errors.push(error),
+            }
+        }
+        match errors.len() {
+            0 => {}
+            1 => return Err(errors.remove(0)),
+            _ => return Err(Error::Many(errors)),
+        }
+
+        let progress_path = Path::new(path)
+            .with_extension("progress.json")
+            .to_string_lossy()
+            .to_string();
+        let progress = Progress::load(&progress_path)?;
+        //starts on the furthest level unlocked
+        let selected = (0..levels.len())
+            .rev()
+            .find(|index| *index == 0 || progress.levels.contains_key(&levels[index - 1].key))
+            .unwrap_or(0);
+
+        Ok(Self {
+            state: CampaignStates::LevelSelect,
+            name: file.name,
+            levels,
+            progress,
+            progress_path,
+            selected,
+            result: None,
+            crashed: false,
+            dirty: true,
+        })
+    }
+
+    pub fn progress(&self, index: usize) -> Option<&LevelProgress> {
+        self.progress.levels.get(&self.levels[index].key)
+    }
+
+    /// The first level is open from the start, every other one once the one
+    /// before it was cleared.
+    pub fn unlocked(&self, index: usize) -> bool {
+        index == 0 || self.progress(index - 1).is_some()
+    }
+
+    pub fn level(&self) -> &CampaignLevel {
+        &self.levels[self.selected]
+    }
+
+    pub fn total_stars(&self) -> usize {
+        self.progress.levels.values().map(|level| level.stars).sum()
+    }
+
+    /// Moves the selection on the level select screen by `steps`, skipping
+    /// locked levels.
+    pub fn select(&mut self, steps: isize) {
+        let index = self.selected.saturating_add_signed(steps);
+        if index < self.levels.len() && self.unlocked(index) {
+            self.selected = index;
+        }
+    }
+
+    /// Plays level `index` and returns it, to become the [`Level`] resource.
+    pub fn play(&mut self, index: usize) -> Level {
+        self.selected = index;
+        self.state = CampaignStates::Playing;
+        self.result = None;
+        self.crashed = false;
+        self.dirty = true;
+        self.levels[index].level.clone()
+    }
+
+    pub fn back_to_select(&mut self) {
+        self.state = CampaignStates::LevelSelect;
+        self.result = None;
+        self.dirty = true;
+    }
+
+    /// Rates a clear of the selected level, keeps it if it beat the saved
+    /// one and shows the results.
+    pub fn finish(&mut self, score: u32, pickups: u32, total_pickups: u32) {
+        let mut result = LevelResult {
+            score,
+            pickups,
+            total_pickups,
+            no_damage: !self.crashed,
+            goals: Vec::new(),
+            new_best: false,
+        };
+        result.goals = self
+            .level()
+            .goals
+            .iter()
+            .map(|goal| goal.met(&result))
+            .collect();
+
+        let key = self.level().key.clone();
+        let best = self.progress.levels.get(&key).copied();
+        result.new_best = best.is_none_or(|best| score > best.best_score);
+        let best = best.unwrap_or_default();
+        self.progress.levels.insert(
+            key,
+            LevelProgress {
+                stars: best.stars.max(result.stars()),
+                best_score: best.best_score.max(score),
+            },
+        );
+        if let Err(error) = self.progress.save(&self.progress_path) {
+            eprintln!("{error}");
+        }
+
+        self.result = Some(result);
+        self.state = CampaignStates::Results;
+    }
+}
+
+/// Moves through the campaign: choosing a level, playing it, and the results
+/// once the birds reached the finish line. The birds only show during a
+/// level. Runs after [`restart_game`](crate::game::restart_game).
+pub fn campaign_menu(
+    mut commands: Commands,
+    mut campaign: ResMut<Campaign>,
+    mut game_state: ResMut<GameState>,
+    pickup_query: Query<&Pickup>,
+    mut player_query: Query<(&Player, &mut Sprite)>,
+) {
+    for (_player, mut sprite) in &mut player_query {
+        sprite.visible = campaign.state == CampaignStates::Playing;
+    }
+
+    let confirm = is_key_pressed(KeyCode::Enter) || is_key_pressed(KeyCode::Space);
+    match campaign.state {
+        CampaignStates::LevelSelect => {
+            //the birds wait at the start while a level is chosen
+            if game_state.game_state == GameStates::PressStart {
+                game_state.game_state = GameStates::Paused;
+            }
+            if is_key_pressed(KeyCode::Up) {
+                campaign.select(-1);
+            }
+            if is_key_pressed(KeyCode::Down) {
+                campaign.select(1);
+            }
+            if confirm && game_state.game_state == GameStates::Paused {
+                let index = campaign.selected;
+                commands.insert_resource(campaign.play(index));
+                game_state.game_state = GameStates::Restart;
+            }
+        }
+        CampaignStates::Playing => {
+            if game_state.game_state == GameStates::LevelComplete {
+                let collected = pickup_query
+                    .iter()
+                    .filter(|pickup| pickup.collected)
+                    .count();
+                campaign.finish(
+                    game_state.score,
+                    collected as u32,
+                    pickup_query.iter().count() as u32,
+                );
+                game_state.game_state = GameStates::Paused;
+            } else if is_key_pressed(KeyCode::Escape) {
+                campaign.back_to_select();
+                game_state.game_state = GameStates::Restart;
+            }
+        }
+        CampaignStates::Results => {
+            let next = campaign.selected + 1;
+            if is_key_pressed(KeyCode::R) {
+                let index = campaign.selected;
+                commands.insert_resource(campaign.play(index));
+                game_state.game_state = GameStates::Restart;
+            } else if confirm && next < campaign.levels.len() {
+                commands.insert_resource(campaign.play(next));
+                game_state.game_state = GameStates::Restart;
+            } else if confirm || is_key_pressed(KeyCode::Escape) {
+                campaign.back_to_select();
+                game_state.game_state = GameStates::Restart;
+            }
+        }
+    }
+}
+
+/// Notes the crashes of the level being played, for
+/// [`Goal::NoDamage`]. Runs before the events are drained.
+pub fn track_campaign_crashes(events: Res<GameEvents>, mut campaign: ResMut<Campaign>) {
+    if campaign.state == CampaignStates::Playing
+        && events
+            .0
+            .iter()
+            .any(|event| matches!(event, GameEvent::Crash { .. }))
+    {
+        campaign.crashed = true;
+    }
+}
+
+/// Spawns the level that is played, or takes it away on the level select
+/// screen.
+pub fn show_campaign_level(world: &mut World) {
+    let campaign = world.resource::<Campaign>();
+    if !campaign.dirty {
+        return;
+    }
+    let level = (campaign.state == CampaignStates::Playing).then(|| campaign.level().level.clone());
+    world.resource_mut::<Campaign>().dirty = false;
+
+    despawn_level(world);
+    if let Some(level) = level {
+        spawn_level(&mut world.commands(), &level, 0.0);
+        world.flush();
+    }
+}
@@ -0,0 +1,163 @@
+use bevy_ecs::prelude::*;
+use macroquad::prelude::*;
+
+use crate::game::SCREEN_SIZE;
+use crate::transform::Position;
+
+pub const LETTERBOX_COLOR: Color = BLACK;
+/// The widest aspect ratio the view grows to when extending horizontally.
+pub const MAX_ASPECT_RATIO: f32 = 16.0 / 9.0;
+
+/// Chosen with `--scale integer` or `--scale fit`.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum ScaleMode {
+    /// Largest whole multiple of the logical resolution, for crisp pixels.
+    #[default]
+    Integer,
+    /// Fill as much of the window as possible, at any scale.
+    Fit,
+}
+
+impl ScaleMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "integer" => Some(ScaleMode::Integer),
+            "fit" => Some(ScaleMode::Fit),
+            _ => None,
+        }
+    }
+}
+
+/// Maps the logical `SCREEN_SIZE` view onto the window, keeping its aspect
+/// ratio and filling the rest with bars. With `--wide` the view grows
+/// sideways in wide windows instead, up to [`MAX_ASPECT_RATIO`].
+///
+/// `viewport` is in physical window pixels with the origin at the top left,
+/// `view` is the part of the game world that is shown.
+#[derive(Resource, Debug, Clone)]
+pub struct Display {
+    pub scale_mode: ScaleMode,
+    pub extend_horizontally: bool,
+    pub fullscreen: bool,
+    pub window_size: Vec2,
+    pub viewport: Rect,
+    pub view: Rect,
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new(ScaleMode::default(), false)
+    }
+}
+
+impl Display {
+    pub fn new(scale_mode: ScaleMode, extend_horizontally: bool) -> Self {
+        let mut display = Self {
+            scale_mode,
+            extend_horizontally,
+            fullscreen: false,
+            window_size: Vec2::ZERO,
+            viewport: Rect::default(),
+            view: Rect::default(),
+        };
+        display.resize(Vec2::splat(SCREEN_SIZE));
+        display
+    }
+
+    pub fn resize(&mut self, window_size: Vec2) {
+        self.window_size = window_size;
+
+        let aspect = if self.extend_horizontally {
+            (window_size.x / window_size.y).clamp(1.0, MAX_ASPECT_RATIO)
+        } else {
+            1.0
+        };
+        self.view = Rect::new(0.0, -SCREEN_SIZE, SCREEN_SIZE * aspect, SCREEN_SIZE);
+
+        let fit = (window_size.x / self.view.w).min(window_size.y / self.view.h);
+        let scale = match self.scale_mode {
+            //a window smaller than the logical size still has to show everything
+            ScaleMode::Integer if fit >= 1.0 => fit.floor(),
+            _ => fit,
+        };
+
+        let size = self.view.size() * scale;
+        self.viewport = Rect::new(
+            ((window_size.x - size.x) / 2.0).floor(),
+            ((window_size.y - size.y) / 2.0).floor(),
+            size.x.floor(),
+            size.y.floor(),
+        );
+    }
+
+    pub fn camera(&self) -> Camera2D {
+        Camera2D {
+            //the GL viewport counts from the bottom of the window
+            viewport: Some((
+                self.viewport.x as i32,
+                (self.window_size.y - self.viewport.bottom()) as i32,
+                self.viewport.w as i32,
+                self.viewport.h as i32,
+            )),
+            ..Camera2D::from_display_rect(Rect::new(
+                self.view.x,
+                self.view.bottom(),
+                self.view.w,
+                -self.view.h,
+            ))
+        }
+    }
+
+    /// Converts a point in physical window pixels to game coordinates, or
+    /// `None` when it falls on the bars around the game.
+    pub fn screen_to_world(&self, point: Vec2) -> Option<Vec2> {
+        if !self.viewport.contains(point) {
+            return None;
+        }
+
+        let relative = (point - self.viewport.point()) / self.viewport.size();
+        Some(self.view.point() + relative * self.view.size())
+    }
+
+    pub fn mouse_world_position(&self) -> Option<Vec2> {
+        self.screen_to_world(Vec2::from(mouse_position()) * screen_dpi_scale())
+    }
+
+    pub fn touch_world_positions(&self) -> Vec<Vec2> {
+        touches()
+            .iter()
+            .filter_map(|touch| self.screen_to_world(touch.position * screen_dpi_scale()))
+            .collect()
+    }
+}
+
+/// Keeps an entity horizontally centred in the view when it grows wider.
+#[derive(Component)]
+pub struct CenterOnView;
+
+pub fn window_size() -> Vec2 {
+    Vec2 {
+        x: screen_width(),
+        y: screen_height(),
+    } * screen_dpi_scale()
+}
+
+pub fn update_display(mut display: ResMut<Display>) {
+    if is_key_pressed(KeyCode::F11) {
+        display.fullscreen = !display.fullscreen;
+        set_fullscreen(display.fullscreen);
+    }
+
+    let window_size = window_size();
+    if window_size != display.window_size {
+        display.resize(window_size);
+    }
+}
+
+pub fn center_on_view(display: Res<Display>, mut query: Query<&mut Position, With<CenterOnView>>) {
+    if display.is_changed() {
+        for mut position in &mut query {
+            position.x = display.view.center().x;
+        }
+    }
+}
@@ -1,206 +1,209 @@
-use std::f32::consts::PI;
-
 use bevy_ecs::prelude::*;
 use macroquad::prelude::*;
 
 use crate::asset_loader::AssetLoader;
-use crate::background::Floor;
-use crate::game::{GameState, GameStates, SCREEN_SIZE};
-use crate::pipe::{Pipe, PIPES_V_SEPARATION};
-use crate::player::Player;
-use crate::transform::{Position, Velocity};
-use crate::ui::{Score, UI_SPRITE_SEP, UI_SPRITE_SIZE};
+use crate::display::{Display, LETTERBOX_COLOR};
+use crate::sprite::{CompositeSprite, Sprite};
+use crate::text::Text;
+use crate::transform::Position;
+
+/// A single texture draw, resolved to screen coordinates.
+#[derive(Debug, Clone)]
+pub struct DrawCommand<'a> {
+    pub texture: &'a str,
+    pub dest: Rect,
+    pub source: Option<Rect>,
+    pub rotation: f32,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub pivot: Option<Vec2>,
+    pub tint: Color,
+    pub layer: i32,
+}
 
-pub fn draw_call(
-    floor_query: Query<(Entity, &Position), With<Floor>>,
-    pipe_query: Query<(Entity, &Position), With<Pipe>>,
-    player_query: Single<(&Position, &Velocity), With<Player>>,
-    score_query: Single<(Entity, &Position), With<Score>>,
-    game_state: Res<GameState>,
-    assets: ResMut<AssetLoader>,
-) {
-    let pipe_texture = assets.get_texture("pipe");
-    let sprite_sheet = assets.get_texture("sprite_sheet");
-    let player_animations = assets.get_animation("player_animations");
-    let mut score_animations = assets.get_animation("score_animations").clone();
-    let press_space: &Texture2D = assets.get_texture("press_space");
-    let game_over = assets.get_texture("game_over");
-
-    //draw pipes
-    {
-        for (_entity, position) in &pipe_query {
-            //above
-            draw_texture_ex(
-                pipe_texture,
-                position.x,
-                position.y - pipe_texture.height(),
-                WHITE,
-                DrawTextureParams {
-                    source: None,
-                    rotation: 0.0,
-                    flip_x: false,
-                    flip_y: true,
-                    pivot: None,
-                    dest_size: Some(Vec2 {
-                        x: pipe_texture.width(),
-                        y: pipe_texture.height(),
-                    }),
-                },
-            );
-
-            draw_texture_ex(
-                pipe_texture,
-                position.x,
-                position.y + PIPES_V_SEPARATION,
-                WHITE,
-                DrawTextureParams {
-                    source: None,
-                    rotation: 0.0,
-                    flip_x: false,
-                    flip_y: false,
-                    pivot: None,
-                    dest_size: Some(Vec2 {
-                        x: pipe_texture.width(),
-                        y: pipe_texture.height(),
-                    }),
-                },
-            );
+impl<'a> DrawCommand<'a> {
+    fn from_sprite(sprite: &'a Sprite, position: &Position, texture_size: Vec2) -> Self {
+        let size = sprite
+            .dest_size
+            .or(sprite.source.map(|source| source.size()))
+            .unwrap_or(texture_size);
+        let frame_size = sprite.frame_size.unwrap_or(size);
+        let frame_x = (position.x + sprite.offset.x - sprite.anchor.x * frame_size.x).floor();
+        let frame_y = (position.y + sprite.offset.y - sprite.anchor.y * frame_size.y).floor();
+        let x = frame_x + sprite.trim_offset.x;
+        let y = frame_y + sprite.trim_offset.y;
+
+        Self {
+            texture: sprite.texture.name(),
+            dest: Rect::new(x, y, size.x, size.y),
+            source: sprite.source,
+            rotation: sprite.rotation,
+            flip_x: sprite.flip_x,
+            flip_y: sprite.flip_y,
+            pivot: sprite.pivot.map(|pivot| Vec2 {
+                x: frame_x + pivot.x,
+                y: frame_y + pivot.y,
+            }),
+            tint: sprite.tint,
+            layer: sprite.layer,
         }
     }
-    {
-        //draw floors
-        for (_entity, floor_position) in &floor_query {
-            draw_texture(
-                assets.get_texture("floor"),
-                floor_position.x,
-                floor_position.y,
-                WHITE,
-            )
-        }
+}
+
+/// The sky behind everything.
+pub const CLEAR_COLOR: Color = SKYBLUE;
+
+/// Something that can draw a list of [`DrawCommand`]s, e.g. the GPU through
+/// macroquad or an in-memory image.
+pub trait RenderBackend {
+    fn texture_size(&self, texture: &str) -> Vec2;
+    fn clear(&mut self, color: Color);
+    fn draw(&mut self, command: &DrawCommand);
+}
+
+/// Draws to the window, scaled and placed by the [`Display`].
+pub struct MacroquadBackend<'a> {
+    pub assets: &'a AssetLoader,
+    pub display: &'a Display,
+}
+
+impl RenderBackend for MacroquadBackend<'_> {
+    fn texture_size(&self, texture: &str) -> Vec2 {
+        self.assets
+            .get_texture_named(texture)
+            .map_or(Vec2::ZERO, Texture2D::size)
     }
 
-    //draw player
-    let (position, velocity) = player_query.into_inner();
-    {
-        match game_state.game_state {
-            GameStates::Play => {
-                draw_texture_ex(
-                    sprite_sheet,
-                    (position.x).floor(),
-                    (position.y).floor(),
-                    WHITE,
-                    DrawTextureParams {
-                        rotation: clamp(
-                            PI / 20.0 * (velocity.y * get_frame_time()),
-                            -PI / 4.0,
-                            PI / 2.0,
-                        ),
-                        flip_x: false,
-                        flip_y: false,
-                        pivot: None,
-                        source: Some(player_animations.frame().source_rect),
-                        dest_size: Some(player_animations.frame().dest_size),
-                    },
-                );
-            }
-
-            _ => {
-                draw_texture_ex(
-                    sprite_sheet,
-                    (position.x).floor(),
-                    (position.y).floor(),
-                    WHITE,
-                    DrawTextureParams {
-                        rotation: 0.0,
-                        flip_x: false,
-                        flip_y: false,
-                        pivot: None,
-                        source: Some(player_animations.frame().source_rect),
-                        dest_size: Some(player_animations.frame().dest_size),
-                    },
-                );
-            }
-        }
+    fn clear(&mut self, color: Color) {
+        clear_background(LETTERBOX_COLOR);
+        set_camera(&self.display.camera());
+
+        let view = self.display.view;
+        draw_rectangle(view.x, view.y, view.w, view.h, color);
     }
 
-    //draw UI
-    {
-        match game_state.game_state {
-            GameStates::Play => {
-                let (_entity, position) = score_query.into_inner();
-
-                let score_str: String = game_state.score.to_string();
-                let length = score_str.len();
-                let mut count: u32 = 0;
-                let total_length =
-                    (length as i32 - 1) * UI_SPRITE_SEP + ((length as i32) * UI_SPRITE_SIZE);
-
-                for c in score_str.chars() {
-                    score_animations.set_frame(c.to_digit(10).unwrap());
-
-                    draw_texture_ex(
-                        sprite_sheet,
-                        (position.x).floor() - total_length as f32 / 2.0
-                            + count as f32 * (UI_SPRITE_SEP + UI_SPRITE_SIZE) as f32,
-                        (position.y).floor() - UI_SPRITE_SIZE as f32 / 2.0,
-                        WHITE,
-                        DrawTextureParams {
-                            rotation: 0.0,
-                            flip_x: false,
-                            flip_y: false,
-                            pivot: None,
-                            source: Some(score_animations.frame().source_rect),
-                            dest_size: Some(score_animations.frame().dest_size),
-                        },
-                    );
-                    count += 1;
-                }
-            }
-            GameStates::GameOver => {
-                let (_entity, position) = score_query.into_inner();
-
-                let score_str: String = game_state.score.to_string();
-                let length = score_str.len();
-                let mut count: u32 = 0;
-                let total_length =
-                    (length as i32 - 1) * UI_SPRITE_SEP + ((length as i32) * UI_SPRITE_SIZE);
-
-                for c in score_str.chars() {
-                    score_animations.set_frame(c.to_digit(10).unwrap());
-
-                    draw_texture_ex(
-                        sprite_sheet,
-                        (position.x).floor() - total_length as f32 / 2.0
-                            + count as f32 * (UI_SPRITE_SEP + UI_SPRITE_SIZE) as f32,
-                        (position.y).floor() - UI_SPRITE_SIZE as f32 / 2.0,
-                        WHITE,
-                        DrawTextureParams {
-                            rotation: 0.0,
-                            flip_x: false,
-                            flip_y: false,
-                            pivot: None,
-                            source: Some(score_animations.frame().source_rect),
-                            dest_size: Some(score_animations.frame().dest_size),
-                        },
-                    );
-                    count += 1;
-                }
-
-                draw_texture(
-                    game_over,
-                    SCREEN_SIZE / 2.0 - game_over.width() / 2.0,
-                    -SCREEN_SIZE / 2.0 - game_over.height() / 2.0,
-                    WHITE,
-                )
-            }
-
-            GameStates::PressStart => draw_texture(
-                press_space,
-                SCREEN_SIZE / 2.0 - press_space.width() / 2.0,
-                -SCREEN_SIZE / 2.0 - press_space.height() / 2.0 - 10.0,
-                WHITE,
-            ),
-            _ => {}
+    fn draw(&mut self, command: &DrawCommand) {
+        let Some(texture) = self.assets.get_texture_named(command.texture) else {
+            return;
+        };
+        draw_texture_ex(
+            texture,
+            command.dest.x,
+            command.dest.y,
+            command.tint,
+            DrawTextureParams {
+                dest_size: Some(command.dest.size()),
+                source: command.source,
+                rotation: command.rotation,
+                flip_x: command.flip_x,
+                flip_y: command.flip_y,
+                pivot: command.pivot,
+            },
+        );
+    }
+}
+
+/// Collects every visible sprite and text entity into a list sorted by layer.
+pub fn collect_draw_commands<'a>(
+    sprites: impl Iterator<Item = (&'a Position, &'a Sprite)>,
+    texts: impl Iterator<Item = (&'a Position, &'a Text)>,
+    backend: &impl RenderBackend,
+    assets: &'a AssetLoader,
+) -> Vec<DrawCommand<'a>> {
+    let mut commands: Vec<DrawCommand> = sprites
+        .filter(|(_position, sprite)| sprite.visible)
+        .map(|(position, sprite)| {
+            DrawCommand::from_sprite(
+                sprite,
+                position,
+                backend.texture_size(sprite.texture.name()),
+            )
+        })
+        .collect();
+    for (position, text) in texts.filter(|(_position, text)| text.visible) {
+        let font = assets.get_font(text.font);
+        for glyph in font.layout(&text.content, text.align, text.letter_spacing, text.scale) {
+            commands.push(DrawCommand {
+                texture: &font.texture,
+                dest: Rect::new(
+                    (position.x + glyph.dest.x).floor(),
+                    (position.y + glyph.dest.y).floor(),
+                    glyph.dest.w,
+                    glyph.dest.h,
+                ),
+                source: Some(glyph.source),
+                rotation: 0.0,
+                flip_x: false,
+                flip_y: false,
+                pivot: None,
+                tint: text.color,
+                layer: text.layer,
+            });
         }
     }
+
+    //stable, so entities on the same layer keep their query order
+    commands.sort_by_key(|command| command.layer);
+    commands
+}
+
+/// Clears the backend and draws sprites and texts in layer order.
+pub fn render<'a>(
+    sprites: impl Iterator<Item = (&'a Position, &'a Sprite)>,
+    texts: impl Iterator<Item = (&'a Position, &'a Text)>,
+    backend: &mut impl RenderBackend,
+    assets: &'a AssetLoader,
+) {
+    let commands = collect_draw_commands(sprites, texts, backend, assets);
+
+    backend.clear(CLEAR_COLOR);
+    for command in &commands {
+        backend.draw(command);
+    }
+}
+
+/// Renders a world outside of the schedule, for tools that run without a
+/// window.
+pub fn render_world(world: &mut World, backend: &mut impl RenderBackend) {
+    let mut sprite_query = world.query::<(&Position, &Sprite)>();
+    let mut composite_query = world.query::<(&Position, &CompositeSprite)>();
+    let mut text_query = world.query::<(&Position, &Text)>();
+    let assets = world.resource::<AssetLoader>();
+
+    let sprites = sprite_query
+        .iter(world)
+        .chain(
+            composite_query
+                .iter(world)
+                .flat_map(|(position, composite)| {
+                    composite.0.iter().map(move |sprite| (position, sprite))
+                }),
+        );
+    render(sprites, text_query.iter(world), backend, assets);
+}
+
+/// Draws the world to the window.
+pub fn draw_call(
+    sprite_query: Query<(&Position, &Sprite)>,
+    composite_query: Query<(&Position, &CompositeSprite)>,
+    text_query: Query<(&Position, &Text)>,
+    assets: Res<AssetLoader>,
+    display: Res<Display>,
+) {
+    let sprites =
+        sprite_query
+            .iter()
+            .chain(composite_query.iter().flat_map(|(position, composite)| {
+                composite.0.iter().map(move |sprite| (position, sprite))
+            }));
+
+    render(
+        sprites,
+        text_query.iter(),
+        &mut MacroquadBackend {
+            assets: &assets,
+            display: &display,
+        },
+        &assets,
+    );
 }
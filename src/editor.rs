@@ -0,0 +1,653 @@
+use std::path::Path;
+
+use bevy_ecs::prelude::*;
+use macroquad::prelude::*;
+
+use crate::display::Display;
+use crate::error::Result;
+use crate::game::{GameState, GameStates, SCREEN_SIZE};
+use crate::level::{
+    FinishLine, Level, LevelPickup, LevelPipe, LevelStart, MIN_GAP_HEIGHT, Movement, Pickup,
+    despawn_level, spawn_level,
+};
+use crate::pipe::{PIPE_WIDTH, PIPES_H_SEPARATION, PIPES_V_SEPARATION, Pipe};
+use crate::player::PLAYER_START_X;
+use crate::sprite::{CompositeSprite, Sprite};
+use crate::text::Text;
+use crate::transform::Position;
+
+/// The grid positions snap to.
+pub const SNAP: f32 = 10.0;
+/// How close the mouse has to be to the edge of a gap or to the finish line
+/// to grab it.
+const GRAB_DISTANCE: f32 = 5.0;
+/// Half the size of the box around a pickup that grabs it.
+const PICKUP_GRAB: f32 = 10.0;
+/// Pixels per second the arrow keys scroll, three times as many with shift.
+const SCROLL_SPEED: f32 = 240.0;
+/// Pixels per notch of the mouse wheel.
+const WHEEL_SCROLL: f32 = 30.0;
+/// Speed of a pipe that starts bouncing in the editor.
+const BOUNCE_SPEED: f32 = 30.0;
+/// Edits that can be undone.
+const UNDO_LIMIT: usize = 100;
+/// Top of the gap of the pipe a new level starts with.
+const NEW_GAP_Y: f32 = -210.0;
+const SELECTED_TINT: Color = Color::new(1.0, 0.6, 0.6, 1.0);
+
+/// Whether the level is being edited or test-played. The game itself stays
+/// `GameStates::Paused` while editing.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum EditorStates {
+    #[default]
+    Editing,
+    Testing,
+}
+
+/// What the editor has selected, by index into [`Level::obstacles`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Selection {
+    Pipe(usize),
+    Pickup { pipe: usize, index: usize },
+    Finish,
+}
+
+/// The part of a pipe that is dragged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Handle {
+    /// Moves the whole pipe.
+    Body,
+    GapTop,
+    GapBottom,
+}
+
+/// A drag in progress. Every frame the level is rebuilt from `before`, so
+/// snapping never drifts.
+#[derive(Debug, Clone)]
+struct Drag {
+    selection: Selection,
+    handle: Handle,
+    /// Where the mouse grabbed, in level coordinates.
+    from: Vec2,
+    before: Level,
+}
+
+/// A level being edited. Level coordinates are those of
+/// [`Level::pipe_positions`]; the view shows them `scroll` pixels to the
+/// left, which is also where test-play starts.
+#[derive(Resource, Debug, Clone)]
+pub struct Editor {
+    pub state: EditorStates,
+    pub path: String,
+    pub level: Level,
+    pub scroll: f32,
+    pub snap: bool,
+    pub selection: Option<Selection>,
+    /// What the last action did or why it failed, until the next click.
+    pub message: String,
+    drag: Option<Drag>,
+    undo: Vec<Level>,
+    redo: Vec<Level>,
+    /// Whether the level entities have to be spawned again.
+    dirty: bool,
+}
+
+impl Editor {
+    /// Opens the level at `path`, or a new one with a single pipe when there
+    /// is no such file yet.
+    pub fn open(path: &str) -> Result<Self> {
+        let level = if Path::new(path).exists() {
+            Level::load(path)?
+        } else {
+            let name = Path::new(path)
+                .file_stem()
+                .map_or(String::new(), |stem| stem.to_string_lossy().to_string());
+            Level {
+                name: name.replace('_', " ").to_uppercase(),
+                par: 1,
+                obstacles: vec![new_pipe(PIPES_H_SEPARATION, NEW_GAP_Y)],
+                finish: PIPES_H_SEPARATION,
+            }
+        };
+
+        Ok(Self {
+            state: EditorStates::Editing,
+            path: path.to_string(),
+            level,
+            scroll: 0.0,
+            snap: true,
+            selection: None,
+            message: String::new(),
+            drag: None,
+            undo: Vec::new(),
+            redo: Vec::new(),
+            dirty: true,
+        })
+    }
+
+    fn snap(&self, value: f32) -> f32 {
+        if self.snap {
+            (value / SNAP).round() * SNAP
+        } else {
+            value.round()
+        }
+    }
+
+    /// Keeps `before` for undo, once the level changed.
+    fn record(&mut self, before: Level) {
+        if before == self.level {
+            return;
+        }
+        self.undo.push(before);
+        if self.undo.len() > UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+        self.dirty = true;
+    }
+
+    pub fn undo(&mut self) {
+        if self.drag.is_none()
+            && let Some(level) = self.undo.pop()
+        {
+            self.redo.push(std::mem::replace(&mut self.level, level));
+            self.selection = None;
+            self.dirty = true;
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if self.drag.is_none()
+            && let Some(level) = self.redo.pop()
+        {
+            self.undo.push(std::mem::replace(&mut self.level, level));
+            self.selection = None;
+            self.dirty = true;
+        }
+    }
+
+    pub fn scroll_by(&mut self, distance: f32) {
+        let scroll = (self.scroll + distance).clamp(0.0, self.level.finish_x());
+        if scroll != self.scroll {
+            self.scroll = scroll;
+            self.dirty = true;
+        }
+    }
+
+    /// What is under `point`, in level coordinates. Pickups come first, as
+    /// they float over the pipes.
+    fn pick(&self, point: Vec2) -> Option<(Selection, Handle)> {
+        let positions = self.level.pipe_positions();
+        for (pipe, (obstacle, position)) in self.level.obstacles.iter().zip(&positions).enumerate()
+        {
+            for (index, pickup) in obstacle.pickups.iter().enumerate() {
+                if (point.x - position.x - pickup.x).abs() < PICKUP_GRAB
+                    && (point.y - pickup.y).abs() < PICKUP_GRAB
+                {
+                    return Some((Selection::Pickup { pipe, index }, Handle::Body));
+                }
+            }
+        }
+
+        if (point.x - self.level.finish_x()).abs() < GRAB_DISTANCE {
+            return Some((Selection::Finish, Handle::Body));
+        }
+
+        for (index, (pipe, position)) in self.level.obstacles.iter().zip(&positions).enumerate() {
+            if point.x < position.x || point.x > position.x + PIPE_WIDTH {
+                continue;
+            }
+            let handle = if (point.y - pipe.gap_y).abs() < GRAB_DISTANCE {
+                Handle::GapTop
+            } else if (point.y - pipe.gap_y - pipe.gap_height).abs() < GRAB_DISTANCE {
+                Handle::GapBottom
+            } else {
+                Handle::Body
+            };
+            return Some((Selection::Pipe(index), handle));
+        }
+        None
+    }
+
+    /// Grabs what is under `point`, or places a pipe there when there is
+    /// nothing.
+    pub fn click(&mut self, point: Vec2) {
+        self.message.clear();
+        match self.pick(point) {
+            Some((selection, handle)) => self.grab(selection, handle, point),
+            None => self.add_pipe(point),
+        }
+    }
+
+    fn grab(&mut self, selection: Selection, handle: Handle, point: Vec2) {
+        self.selection = Some(selection);
+        self.drag = Some(Drag {
+            selection,
+            handle,
+            from: point,
+            before: self.level.clone(),
+        });
+        self.dirty = true;
+    }
+
+    /// Moves what was grabbed by as much as the mouse moved since.
+    pub fn drag_to(&mut self, point: Vec2) {
+        let Some(drag) = &self.drag else {
+            return;
+        };
+        let delta = point - drag.from;
+        let positions = drag.before.pipe_positions();
+        let mut level = drag.before.clone();
+
+        match (drag.selection, drag.handle) {
+            (Selection::Pipe(index), Handle::Body) => {
+                let pipe = &mut level.obstacles[index];
+                pipe.gap_y = self.snap(pipe.gap_y + delta.y);
+                //the first pipe always starts at the right edge of the screen
+                if index > 0 {
+                    let previous = positions[index - 1].x;
+                    let next = positions.get(index + 1).map(|position| position.x);
+                    let x = self
+                        .snap(positions[index].x + delta.x)
+                        .max(previous + PIPE_WIDTH)
+                        .min(next.map_or(f32::MAX, |next| next - PIPE_WIDTH));
+                    pipe.spacing = x - previous;
+                    //the pipes after it and the finish line stay where they are
+                    match next {
+                        Some(next) => level.obstacles[index + 1].spacing = next - x,
+                        None => level.finish = (drag.before.finish_x() - x).max(PIPE_WIDTH),
+                    }
+                }
+            }
+            (Selection::Pipe(index), Handle::GapTop) => {
+                let pipe = &mut level.obstacles[index];
+                let bottom = pipe.gap_y + pipe.gap_height;
+                pipe.gap_y = self.snap(pipe.gap_y + delta.y).min(bottom - MIN_GAP_HEIGHT);
+                pipe.gap_height = bottom - pipe.gap_y;
+            }
+            (Selection::Pipe(index), Handle::GapBottom) => {
+                let pipe = &mut level.obstacles[index];
+                let bottom = self.snap(pipe.gap_y + pipe.gap_height + delta.y);
+                pipe.gap_height = (bottom - pipe.gap_y).max(MIN_GAP_HEIGHT);
+            }
+            (Selection::Pickup { pipe, index }, _) => {
+                let pipe_x = positions[pipe].x;
+                let pickup = &mut level.obstacles[pipe].pickups[index];
+                pickup.x = self.snap(pipe_x + pickup.x + delta.x) - pipe_x;
+                pickup.y = self.snap(pickup.y + delta.y);
+            }
+            (Selection::Finish, _) => {
+                let last = positions.last().map_or(SCREEN_SIZE, |position| position.x);
+                level.finish = (self.snap(drag.before.finish_x() + delta.x) - last).max(PIPE_WIDTH);
+            }
+        }
+
+        if level != self.level {
+            self.level = level;
+            self.dirty = true;
+        }
+    }
+
+    /// Lets go of what was grabbed; undo takes the whole drag back.
+    pub fn drop_drag(&mut self) {
+        if let Some(drag) = self.drag.take() {
+            self.record(drag.before);
+        }
+    }
+
+    /// Places a pipe centred on `point`, between the pipes around it.
+    pub fn add_pipe(&mut self, point: Vec2) {
+        let positions = self.level.pipe_positions();
+        let mut pipe = new_pipe(
+            PIPES_H_SEPARATION,
+            self.snap(point.y - PIPES_V_SEPARATION / 2.0),
+        );
+        let before = self.level.clone();
+
+        let x = self.snap(point.x - PIPE_WIDTH / 2.0);
+        let index = positions.iter().filter(|position| position.x < x).count();
+        if index == 0 && !positions.is_empty() {
+            self.message = "the level starts at the first pipe".to_string();
+            return;
+        }
+        if let Some(previous) = index.checked_sub(1).map(|previous| positions[previous].x) {
+            let next = positions.get(index).map(|position| position.x);
+            if x < previous + PIPE_WIDTH || next.is_some_and(|next| x + PIPE_WIDTH > next) {
+                self.message = "no room for a pipe here".to_string();
+                return;
+            }
+            pipe.spacing = x - previous;
+            match next {
+                Some(next) => self.level.obstacles[index].spacing = next - x,
+                None => {
+                    let finish = before.finish_x() - x;
+                    self.level.finish = if finish < PIPE_WIDTH {
+                        PIPES_H_SEPARATION
+                    } else {
+                        finish
+                    };
+                }
+            }
+        }
+
+        self.level.obstacles.insert(index, pipe);
+        self.selection = Some(Selection::Pipe(index));
+        self.record(before);
+    }
+
+    /// Places a pickup worth a point at `point`, belonging to the last pipe
+    /// on its left.
+    pub fn add_pickup(&mut self, point: Vec2) {
+        let positions = self.level.pipe_positions();
+        if positions.is_empty() {
+            self.message = "place a pipe first".to_string();
+            return;
+        }
+        let before = self.level.clone();
+
+        let pipe = positions
+            .iter()
+            .rposition(|position| position.x <= point.x)
+            .unwrap_or(0);
+        let pickup = LevelPickup {
+            x: self.snap(point.x) - positions[pipe].x,
+            y: self.snap(point.y),
+            points: 1,
+        };
+        let pickups = &mut self.level.obstacles[pipe].pickups;
+        pickups.push(pickup);
+        self.selection = Some(Selection::Pickup {
+            pipe,
+            index: pickups.len() - 1,
+        });
+        self.record(before);
+    }
+
+    /// Removes the selected pipe, with its pickups, or the selected pickup.
+    /// The pipes after a removed pipe stay where they are, so the first pipe
+    /// only goes once it is the last one.
+    pub fn delete(&mut self) {
+        let before = self.level.clone();
+        match self.selection {
+            Some(Selection::Pipe(0)) if self.level.obstacles.len() > 1 => {
+                self.message = "the level starts at the first pipe".to_string();
+                return;
+            }
+            Some(Selection::Pipe(index)) => {
+                let removed = self.level.obstacles.remove(index);
+                match self.level.obstacles.get_mut(index) {
+                    Some(next) => next.spacing += removed.spacing,
+                    None if index > 0 => self.level.finish += removed.spacing,
+                    None => {}
+                }
+            }
+            Some(Selection::Pickup { pipe, index }) => {
+                self.level.obstacles[pipe].pickups.remove(index);
+            }
+            Some(Selection::Finish) => {
+                self.message = "a level needs its finish line".to_string();
+                return;
+            }
+            None => return,
+        }
+        self.selection = None;
+        self.record(before);
+    }
+
+    /// Changes the points of the selected pickup, or how far the selected
+    /// pipe bounces, by `steps`.
+    pub fn change_amount(&mut self, steps: i32) {
+        let before = self.level.clone();
+        match self.selection {
+            Some(Selection::Pipe(index)) => {
+                let pipe = &mut self.level.obstacles[index];
+                let (speed, range) = match pipe.movement {
+                    Movement::Still => (BOUNCE_SPEED, 0.0),
+                    Movement::Bounce { speed, range } => (speed, range),
+                };
+                let range = (range + steps as f32 * SNAP).max(0.0);
+                pipe.movement = if range > 0.0 {
+                    Movement::Bounce { speed, range }
+                } else {
+                    Movement::Still
+                };
+            }
+            Some(Selection::Pickup { pipe, index }) => {
+                let pickup = &mut self.level.obstacles[pipe].pickups[index];
+                pickup.points = pickup.points.saturating_add_signed(steps).max(1);
+            }
+            Some(Selection::Finish) | None => return,
+        }
+        self.record(before);
+    }
+
+    /// Writes the level to its file, unless it is unplayable.
+    pub fn save(&mut self) {
+        if let Some(problem) = first_problem(&self.level) {
+            self.message = format!("cannot save: {problem}");
+            return;
+        }
+        self.message = match self.level.save(&self.path) {
+            Ok(()) => format!("saved {}", self.path),
+            Err(error) => {
+                eprintln!("{error}");
+                "cannot save, see the log".to_string()
+            }
+        };
+    }
+
+    /// Switches to test-playing from the current scroll, unless the level is
+    /// unplayable.
+    pub fn start_test(&mut self) -> bool {
+        if let Some(problem) = first_problem(&self.level) {
+            self.message = format!("cannot test: {problem}");
+            return false;
+        }
+        self.drop_drag();
+        self.state = EditorStates::Testing;
+        self.dirty = true;
+        true
+    }
+
+    pub fn stop_test(&mut self) {
+        self.state = EditorStates::Editing;
+        self.dirty = true;
+    }
+}
+
+fn new_pipe(spacing: f32, gap_y: f32) -> LevelPipe {
+    LevelPipe {
+        spacing,
+        gap_y,
+        gap_height: PIPES_V_SEPARATION,
+        movement: Movement::Still,
+        pickups: Vec::new(),
+    }
+}
+
+fn first_problem(level: &Level) -> Option<String> {
+    let problems = level.problems();
+    let first = problems.first()?;
+    Some(match problems.len() {
+        1 => first.clone(),
+        count => format!("{first} and {} more", count - 1),
+    })
+}
+
+/// Edits the level with the mouse and the keyboard, and switches between
+/// editing and test-playing it. Runs after
+/// [`restart_game`](crate::game::restart_game).
+pub fn edit_level(
+    mut commands: Commands,
+    mut editor: ResMut<Editor>,
+    mut game_state: ResMut<GameState>,
+    display: Res<Display>,
+) {
+    if editor.state == EditorStates::Testing {
+        if is_key_pressed(KeyCode::Escape) {
+            editor.stop_test();
+            game_state.game_state = GameStates::Restart;
+        }
+        return;
+    }
+
+    //the birds wait at the start while the level is edited
+    if game_state.game_state == GameStates::PressStart {
+        game_state.game_state = GameStates::Paused;
+    }
+
+    if is_key_pressed(KeyCode::T) && editor.start_test() {
+        commands.insert_resource(editor.level.clone());
+        game_state.game_state = GameStates::Restart;
+        return;
+    }
+
+    let control = is_key_down(KeyCode::LeftControl)
+        || is_key_down(KeyCode::RightControl)
+        || is_key_down(KeyCode::LeftSuper)
+        || is_key_down(KeyCode::RightSuper);
+    let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+    if control && is_key_pressed(KeyCode::Z) {
+        if shift {
+            editor.redo();
+        } else {
+            editor.undo();
+        }
+    }
+    if control && is_key_pressed(KeyCode::Y) {
+        editor.redo();
+    }
+    if control && is_key_pressed(KeyCode::S) {
+        editor.save();
+    }
+    if is_key_pressed(KeyCode::G) {
+        editor.snap = !editor.snap;
+    }
+    if is_key_pressed(KeyCode::Delete) || is_key_pressed(KeyCode::Backspace) {
+        editor.delete();
+    }
+    if is_key_pressed(KeyCode::Equal) || is_key_pressed(KeyCode::KpAdd) {
+        editor.change_amount(1);
+    }
+    if is_key_pressed(KeyCode::Minus) || is_key_pressed(KeyCode::KpSubtract) {
+        editor.change_amount(-1);
+    }
+
+    let mut scroll = 0.0;
+    if is_key_down(KeyCode::Left) || is_key_down(KeyCode::A) {
+        scroll -= SCROLL_SPEED * get_frame_time();
+    }
+    if is_key_down(KeyCode::Right) || is_key_down(KeyCode::D) {
+        scroll += SCROLL_SPEED * get_frame_time();
+    }
+    if shift {
+        scroll *= 3.0;
+    }
+    let (wheel_x, wheel_y) = mouse_wheel();
+    if wheel_x != 0.0 {
+        scroll += wheel_x.signum() * WHEEL_SCROLL;
+    }
+    if wheel_y != 0.0 {
+        scroll -= wheel_y.signum() * WHEEL_SCROLL;
+    }
+    if is_key_pressed(KeyCode::Home) {
+        scroll = -editor.scroll;
+    }
+    if is_key_pressed(KeyCode::End) {
+        scroll = editor.level.finish_x();
+    }
+    editor.scroll_by(scroll);
+
+    //picking happens in level coordinates, the view scrolled by `scroll`
+    let scroll = editor.scroll;
+    if let Some(mouse) = display.mouse_world_position() {
+        let point = vec2(mouse.x + scroll, mouse.y);
+        if is_mouse_button_pressed(MouseButton::Left) {
+            editor.click(point);
+        } else if is_mouse_button_down(MouseButton::Left) {
+            editor.drag_to(point);
+        }
+        if is_mouse_button_pressed(MouseButton::Right) {
+            editor.message.clear();
+            editor.add_pickup(point);
+        }
+    }
+    if is_mouse_button_released(MouseButton::Left) {
+        editor.drop_drag();
+    }
+}
+
+/// Spawns the level again where the editor scrolled to, whenever it
+/// changed. Test-play starts there, without the pipes the birds already
+/// passed.
+pub fn show_level(world: &mut World) {
+    let editor = world.resource::<Editor>();
+    if !editor.dirty {
+        return;
+    }
+    let (level, scroll, state, selection) = (
+        editor.level.clone(),
+        editor.scroll,
+        editor.state,
+        editor.selection,
+    );
+    world.resource_mut::<Editor>().dirty = false;
+
+    despawn_level(world);
+    spawn_level(&mut world.commands(), &level, scroll);
+    world.flush();
+
+    match state {
+        EditorStates::Editing => highlight(world, &level, scroll, selection),
+        EditorStates::Testing => {
+            let mut pipe_query = world.query_filtered::<(Entity, &Position), With<Pipe>>();
+            let passed: Vec<Entity> = pipe_query
+                .iter(world)
+                .filter(|(_, position)| position.x + PIPE_WIDTH < PLAYER_START_X)
+                .map(|(entity, _)| entity)
+                .collect();
+            for entity in passed {
+                world.despawn(entity);
+            }
+        }
+    }
+}
+
+/// Tints the entity of the selection, found by where it starts.
+fn highlight(world: &mut World, level: &Level, scroll: f32, selection: Option<Selection>) {
+    let positions = level.pipe_positions();
+    match selection {
+        Some(Selection::Pipe(index)) => {
+            let x = positions[index].x - scroll;
+            let mut pipe_query =
+                world.query_filtered::<(&LevelStart, &mut CompositeSprite), With<Pipe>>();
+            for (start, mut sprite) in pipe_query.iter_mut(world) {
+                if start.position.x == x {
+                    for sprite in &mut sprite.0 {
+                        sprite.tint = SELECTED_TINT;
+                    }
+                }
+            }
+        }
+        Some(Selection::Pickup { pipe, index }) => {
+            let pickup = &level.obstacles[pipe].pickups[index];
+            let position = Position {
+                x: positions[pipe].x - scroll + pickup.x,
+                y: pickup.y,
+            };
+            let mut pickup_query = world.query_filtered::<(&LevelStart, &mut Text), With<Pickup>>();
+            for (start, mut text) in pickup_query.iter_mut(world) {
+                if start.position == position {
+                    text.color = SELECTED_TINT;
+                }
+            }
+        }
+        Some(Selection::Finish) => {
+            let mut finish_query = world.query_filtered::<&mut Sprite, With<FinishLine>>();
+            for mut sprite in finish_query.iter_mut(world) {
+                sprite.tint = SELECTED_TINT;
+            }
+        }
+        None => {}
+    }
+}
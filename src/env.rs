@@ -0,0 +1,221 @@
+use bevy_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game::{GameState, GameStates, MatchSettings};
+use crate::pipe::{PIPE_WIDTH, Pipe};
+use crate::player::{PLAYER_COLLISION, PLAYER_SPRITE_SIZES, Player};
+use crate::simulation::{GameEvent, GameEvents, PlayerInput, headless_world, simulation_schedule};
+use crate::transform::{Position, Velocity};
+
+/// Gaps ahead of the bird included in an [`Observation`].
+pub const OBSERVED_GAPS: usize = 2;
+
+/// What the agent does on a step. Written as `0` or `1` in JSON.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "u8")]
+pub enum Action {
+    #[default]
+    Idle,
+    Flap,
+}
+
+impl TryFrom<u8> for Action {
+    type Error = String;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Action::Idle),
+            1 => Ok(Action::Flap),
+            _ => Err(format!(
+                "unknown action {value}, expected 0 (idle) or 1 (flap)"
+            )),
+        }
+    }
+}
+
+/// A gap the bird still has to pass, in pixels. `distance` is from the
+/// bird's left edge to the pipe's right edge.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Gap {
+    pub distance: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+/// The state of the game as the agent sees it, in pixels and pixels per
+/// second. `y` grows downwards, from -320 at the top of the screen.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Observation {
+    /// Height of the middle of the bird.
+    pub bird_y: f32,
+    pub bird_velocity: f32,
+    /// The next gaps, nearest first.
+    pub gaps: Vec<Gap>,
+}
+
+/// Rewards given on every step, all configurable.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RewardConfig {
+    /// For every step the bird survives.
+    pub alive: f32,
+    /// For every pipe passed.
+    pub pipe: f32,
+    /// When the bird crashes.
+    pub crash: f32,
+    /// Taken away per gap height between the bird and the middle of the
+    /// next gap, every step.
+    pub off_center: f32,
+}
+
+impl Default for RewardConfig {
+    fn default() -> Self {
+        Self {
+            alive: 0.1,
+            pipe: 1.0,
+            crash: -1.0,
+            off_center: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct StepInfo {
+    pub score: u32,
+    pub steps: u32,
+    /// Whether the episode ended because it reached `max_steps` rather
+    /// than by a crash.
+    pub truncated: bool,
+}
+
+/// The game as a reinforcement learning environment, one simulation tick
+/// per step, with the same API as a Gym environment.
+pub struct FlappyEnv {
+    pub rewards: RewardConfig,
+    /// Steps after which an episode ends even if the bird still flies.
+    pub max_steps: Option<u32>,
+    world: World,
+    simulation: Schedule,
+    steps: u32,
+}
+
+impl FlappyEnv {
+    pub fn new(rewards: RewardConfig, max_steps: Option<u32>) -> Self {
+        let mut env = Self {
+            rewards,
+            max_steps,
+            world: World::default(),
+            simulation: simulation_schedule(),
+            steps: 0,
+        };
+        env.reset(0);
+        env
+    }
+
+    /// Starts a new episode on the pipe layout of `seed`, with the bird
+    /// already flying.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        //a schedule only runs on the world it first ran on
+        self.world = headless_world(MatchSettings::default(), seed);
+        self.simulation = simulation_schedule();
+        self.world.resource_mut::<GameState>().game_state = GameStates::Play;
+        self.steps = 0;
+        self.observe()
+    }
+
+    pub fn step(&mut self, action: Action) -> (Observation, f32, bool, StepInfo) {
+        if self.done() {
+            return (self.observe(), 0.0, true, self.info());
+        }
+
+        let mut input = self
+            .world
+            .query_filtered::<&mut PlayerInput, With<Player>>();
+        for mut input in input.iter_mut(&mut self.world) {
+            input.flap = action == Action::Flap;
+        }
+        self.simulation.run(&mut self.world);
+        self.steps += 1;
+
+        let mut reward = 0.0;
+        let mut crashed = false;
+        for event in self.world.resource_mut::<GameEvents>().0.drain(..) {
+            match event {
+                GameEvent::Score { .. } => reward += self.rewards.pipe,
+                GameEvent::Crash { .. } => crashed = true,
+                GameEvent::Flap { .. } | GameEvent::PipeSpawned { .. } => {}
+            }
+        }
+
+        let observation = self.observe();
+        if crashed {
+            reward += self.rewards.crash;
+        } else {
+            reward += self.rewards.alive;
+            if let Some(gap) = observation.gaps.first() {
+                let middle = (gap.top + gap.bottom) / 2.0;
+                reward -= self.rewards.off_center * (observation.bird_y - middle).abs()
+                    / (gap.bottom - gap.top);
+            }
+        }
+
+        (observation, reward, self.done(), self.info())
+    }
+
+    fn done(&self) -> bool {
+        self.world.resource::<GameState>().game_state == GameStates::GameOver || self.truncated()
+    }
+
+    fn truncated(&self) -> bool {
+        self.max_steps
+            .is_some_and(|max_steps| self.steps >= max_steps)
+    }
+
+    fn info(&self) -> StepInfo {
+        StepInfo {
+            score: self.world.resource::<GameState>().score,
+            steps: self.steps,
+            truncated: self.truncated()
+                && self.world.resource::<GameState>().game_state != GameStates::GameOver,
+        }
+    }
+
+    fn observe(&mut self) -> Observation {
+        let mut bird = self
+            .world
+            .query_filtered::<(&Position, &Velocity), With<Player>>();
+        let (position, velocity) = bird
+            .iter(&self.world)
+            .next()
+            .map(|(position, velocity)| (*position, *velocity))
+            .expect("the environment always has a bird");
+
+        let left = position.x + PLAYER_SPRITE_SIZES.x / 2.0 - PLAYER_COLLISION.x / 2.0;
+        let mut pipes = self.world.query::<(&Position, &Pipe)>();
+        let mut ahead: Vec<(&Position, &Pipe)> = pipes
+            .iter(&self.world)
+            .filter(|(position, _pipe)| position.x + PIPE_WIDTH > left)
+            .collect();
+        ahead.sort_by(|(a, _), (b, _)| a.x.total_cmp(&b.x));
+
+        Observation {
+            bird_y: position.y + PLAYER_SPRITE_SIZES.y / 2.0,
+            bird_velocity: velocity.y,
+            gaps: ahead
+                .iter()
+                .take(OBSERVED_GAPS)
+                .map(|(position, pipe)| Gap {
+                    distance: position.x + PIPE_WIDTH - left,
+                    top: position.y,
+                    bottom: position.y + pipe.gap_height,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Default for FlappyEnv {
+    fn default() -> Self {
+        Self::new(RewardConfig::default(), None)
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt;
+
+use crate::manifest::ManifestError;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// An asset file could not be read or decoded.
+    Asset { path: String, message: String },
+    /// A file could not be written.
+    Write { path: String, message: String },
+    /// A saved game doesn't fit the world it is loaded into.
+    State(String),
+    /// The asset manifest is malformed or inconsistent.
+    Manifest(Vec<ManifestError>),
+    /// Several independent problems, reported together.
+    Many(Vec<Error>),
+}
+
+impl Error {
+    pub fn asset(path: &str, message: impl ToString) -> Self {
+        Error::Asset {
+            path: path.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn write(path: &str, message: impl ToString) -> Self {
+        Error::Write {
+            path: path.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Every single problem contained in this error, flattened.
+    pub fn problems(&self) -> Vec<String> {
+        match self {
+            Error::Asset { .. } | Error::Write { .. } | Error::State(_) => {
+                vec![self.to_string()]
+            }
+            Error::Manifest(errors) => errors.iter().map(ToString::to_string).collect(),
+            Error::Many(errors) => errors.iter().flat_map(Error::problems).collect(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Asset { path, message } => write!(f, "cannot load {path}: {message}"),
+            Error::Write { path, message } => write!(f, "cannot write {path}: {message}"),
+            Error::State(message) => write!(f, "cannot restore the saved game: {message}"),
+            Error::Manifest(_) | Error::Many(_) => write!(f, "{}", self.problems().join("\n")),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<Vec<ManifestError>> for Error {
+    fn from(errors: Vec<ManifestError>) -> Self {
+        Error::Manifest(errors)
+    }
+}
@@ -1,74 +1,258 @@
-use crate::asset_loader::AssetLoader;
-use crate::pipe::{
-    Pipe, PIPES_H_SEPARATION, PIPES_V_SEPARATION, PIPE_CEILING_PADDING, PIPE_FLOOR_PADDING,
-};
-use crate::player::Player;
+use crate::animation::Animator;
+use crate::asset_loader::BIRD_CLIPS;
+use crate::bot::{Bot, Difficulty};
+use crate::level::Level;
+use crate::mode::GameMode;
+use crate::pipe::{Pipe, PipeSpawn, reset_pipes};
+use crate::player::{Controller, MAX_PLAYERS, Player, PlayerBundle, start_position};
+use crate::simulation::{Distance, GameRng, PlayerInput, Tick};
 use crate::transform::{Position, Velocity};
 use bevy_ecs::prelude::*;
 use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
 
+/// Width and height of the view, in game units.
 pub const SCREEN_SIZE: f32 = 320.0;
 
-#[derive(Default, Component, Debug, PartialEq)]
+/// The screen the game is on; `Restart` lasts a single frame.
+#[derive(Default, Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum GameStates {
     Play,
     Paused,
     GameOver,
+    /// The birds reached the finish line of a level.
+    LevelComplete,
     #[default]
     PressStart,
     Restart,
 }
 
-#[derive(Default, Resource, Debug)]
+/// `score` is the best score of the round; every bird keeps its own in
+/// [`Player`]. `winner` is the index of the winning bird once a round with
+/// more than one bird is over, `None` for a draw.
+#[derive(Default, Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GameState {
     pub game_state: GameStates,
     pub score: u32,
+    pub winner: Option<usize>,
 }
 
-pub fn check_inputs(mut game_state: ResMut<GameState>) {
-    if is_key_pressed(KeyCode::Space) && game_state.game_state == GameStates::PressStart {
+impl GameState {
+    /// Whether the round ended, by crashing or by finishing a level.
+    pub fn is_over(&self) -> bool {
+        matches!(
+            self.game_state,
+            GameStates::GameOver | GameStates::LevelComplete
+        )
+    }
+}
+
+/// How the winner of a round with several birds is decided.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WinRule {
+    /// The round ends when one bird is left, which wins.
+    #[default]
+    LastBirdStanding,
+    /// The round goes on until every bird crashed; the best score wins.
+    HighestScore,
+}
+
+impl WinRule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WinRule::LastBirdStanding => "LAST BIRD STANDING",
+            WinRule::HighestScore => "HIGHEST SCORE",
+        }
+    }
+}
+
+/// How many birds play, which of them the autopilot flies, how the winner
+/// is decided and what for. Changed on the start screen with the number
+/// keys, the function keys, M and Tab.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct MatchSettings {
+    pub players: usize,
+    pub win_rule: WinRule,
+    pub mode: GameMode,
+    /// The difficulty of each bot, by player index; `None` for a human.
+    pub bots: [Option<Difficulty>; MAX_PLAYERS],
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        Self {
+            players: 1,
+            win_rule: WinRule::default(),
+            mode: GameMode::default(),
+            bots: [None; MAX_PLAYERS],
+        }
+    }
+}
+
+impl MatchSettings {
+    pub fn is_multiplayer(&self) -> bool {
+        self.players > 1
+    }
+
+    pub fn has_bots(&self) -> bool {
+        self.bots[..self.players].iter().any(Option::is_some)
+    }
+
+    /// Whether the round is over with `alive` of the birds still flying.
+    pub fn round_over(&self, alive: usize) -> bool {
+        match self.win_rule {
+            WinRule::LastBirdStanding if self.is_multiplayer() => alive <= 1,
+            _ => alive == 0,
+        }
+    }
+}
+
+/// The keys that choose how many birds play on the start screen.
+const PLAYER_COUNT_KEYS: [KeyCode; MAX_PLAYERS] =
+    [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4];
+/// The keys that hand each bird to the autopilot and back.
+const BOT_KEYS: [KeyCode; MAX_PLAYERS] = [KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4];
+
+/// Changes the match settings on the start screen, starts the round on a
+/// flap and restarts it with R once it is over.
+pub fn check_inputs(
+    mut game_state: ResMut<GameState>,
+    mut settings: ResMut<MatchSettings>,
+    level: Option<Res<Level>>,
+    player_query: Query<(&Player, &Controller)>,
+) {
+    if game_state.game_state == GameStates::PressStart {
+        for (index, key) in PLAYER_COUNT_KEYS.into_iter().enumerate() {
+            if is_key_pressed(key) && settings.players != index + 1 {
+                settings.players = index + 1;
+            }
+        }
+        //cycles a bird from human through the bot difficulties and back
+        for (index, key) in BOT_KEYS.into_iter().enumerate() {
+            if is_key_pressed(key) {
+                settings.bots[index] = match settings.bots[index] {
+                    None => Some(Difficulty::Easy),
+                    Some(Difficulty::Easy) => Some(Difficulty::Normal),
+                    Some(Difficulty::Normal) => Some(Difficulty::Hard),
+                    Some(Difficulty::Hard) => None,
+                };
+            }
+        }
+        if is_key_pressed(KeyCode::M) {
+            settings.win_rule = match settings.win_rule {
+                WinRule::LastBirdStanding => WinRule::HighestScore,
+                WinRule::HighestScore => WinRule::LastBirdStanding,
+            };
+        }
+        //a level has a finish line of its own
+        if is_key_pressed(KeyCode::Tab) && level.is_none() {
+            settings.mode = settings.mode.next();
+        }
+    }
+
+    let flapped = player_query.iter().any(|(player, controller)| {
+        *controller != Controller::Remote && is_key_pressed(player.flap_key)
+    });
+    if flapped && game_state.game_state == GameStates::PressStart {
         game_state.score = 0;
         game_state.game_state = GameStates::Play;
-    } else if is_key_pressed(KeyCode::R) && game_state.game_state == GameStates::GameOver {
+    } else if is_key_pressed(KeyCode::R) && game_state.is_over() {
         game_state.score = 0;
         game_state.game_state = GameStates::Restart;
     }
 }
 
+/// Spawns one bird per player, replacing the old ones when the number of
+/// players changed on the start screen, and hands the birds to the bots
+/// picked there.
+pub fn sync_players(
+    mut commands: Commands,
+    settings: Res<MatchSettings>,
+    player_query: Query<(Entity, &Player)>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    if player_query.iter().count() != settings.players {
+        for (entity, _player) in &player_query {
+            commands.entity(entity).despawn();
+        }
+        for index in 0..settings.players {
+            let entity = commands
+                .spawn(PlayerBundle::new(index, settings.players))
+                .id();
+            set_controller(&mut commands, entity, index, &settings);
+        }
+    } else {
+        for (entity, player) in &player_query {
+            set_controller(&mut commands, entity, player.index, &settings);
+        }
+    }
+}
+
+fn set_controller(commands: &mut Commands, entity: Entity, index: usize, settings: &MatchSettings) {
+    let mut bird = commands.entity(entity);
+    match settings.bots[index] {
+        Some(difficulty) => {
+            bird.insert((Controller::Bot, Bot::new(difficulty, index as u64)));
+        }
+        None => {
+            bird.insert(Controller::Keyboard).remove::<Bot>();
+        }
+    }
+}
+
+/// Once a round is restarted, lays out new pipes, puts the birds back and
+/// returns to the start screen.
+#[allow(clippy::type_complexity)]
 pub fn restart_game(
     mut game_state: ResMut<GameState>,
-    mut player_query: Single<(&mut Position, &mut Velocity), (With<Player>, Without<Pipe>)>,
-    mut pipe_query: Query<(Entity, &mut Position, &mut Pipe), (With<Pipe>, Without<Player>)>,
-    mut assets: ResMut<AssetLoader>,
+    settings: Res<MatchSettings>,
+    level: Option<Res<Level>>,
+    (mut rng, spawn): (ResMut<GameRng>, Res<PipeSpawn>),
+    (mut tick, mut distance): (ResMut<Tick>, ResMut<Distance>),
+    mut player_query: Query<
+        (
+            &mut Position,
+            &mut Velocity,
+            &mut Animator,
+            &mut PlayerInput,
+            &mut Player,
+        ),
+        Without<Pipe>,
+    >,
+    mut pipe_query: Query<(&mut Position, &mut Pipe), Without<Player>>,
 ) {
     if game_state.is_changed() && game_state.game_state == GameStates::Restart {
-        //reset pipes
-        {
-            let mut current_pipe = 0.0;
-            for (_entity, mut position, mut pipe) in &mut pipe_query {
-                let random: f32 = macroquad::rand::gen_range(0.0, 1.0);
-                position.x = SCREEN_SIZE + current_pipe * PIPES_H_SEPARATION;
-                position.y = clamp(
-                    random * (-SCREEN_SIZE),
-                    -SCREEN_SIZE + PIPE_CEILING_PADDING,
-                    -PIPES_V_SEPARATION - PIPE_FLOOR_PADDING,
-                );
-                current_pipe += 1.0;
-                pipe.can_score = true;
-            }
+        //a level puts its pipes back itself, see restart_level
+        if level.is_none() {
+            reset_pipes(pipe_query.iter_mut(), &spawn, &mut rng);
         }
 
-        {
-            let (mut position, mut velocity) = player_query.into_inner();
-
-            position.x = 50.0;
-            position.y = -SCREEN_SIZE / 2.0;
+        for (mut position, mut velocity, mut animator, mut input, mut player) in &mut player_query {
+            *position = start_position(player.index, settings.players);
 
             velocity.x = 0.0;
             velocity.y = 0.0;
+
+            //a lone bird gets a random colour, in versus every bird keeps its own
+            if settings.is_multiplayer() {
+                animator.play(player.clip());
+            } else {
+                animator.play(BIRD_CLIPS[rand::gen_range(0, BIRD_CLIPS.len())]);
+            }
+            *input = PlayerInput::default();
+
+            player.score = 0;
+            player.alive = true;
         }
 
+        tick.0 = 0;
+        distance.0 = 0.0;
+        game_state.winner = None;
         game_state.game_state = GameStates::PressStart;
-        assets.set_animation("player_animations", rand::RandomRange::gen_range(0, 3));
     }
 }
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use bevy_ecs::prelude::*;
+use macroquad::file::load_string;
+use macroquad::logging::{info, warn};
+use macroquad::time::get_time;
+
+use crate::asset_loader::AssetLoader;
+use crate::manifest::Manifest;
+
+/// Seconds between two scans of the assets directory.
+pub const POLL_INTERVAL: f64 = 0.5;
+
+/// Watches the assets directory while the game runs and reloads files that
+/// changed on disk. Only compiled with the `hot-reload` feature.
+///
+/// A file that fails to load keeps the previously loaded version, so a
+/// half-saved texture never takes the game down.
+pub struct HotReload {
+    root: PathBuf,
+    manifest_path: PathBuf,
+    manifest: Option<Manifest>,
+    modified: HashMap<PathBuf, SystemTime>,
+    last_poll: f64,
+}
+
+impl HotReload {
+    pub async fn new(root: &str, manifest_path: &str) -> Self {
+        let mut hot_reload = Self {
+            root: PathBuf::from(root),
+            manifest_path: PathBuf::from(manifest_path),
+            manifest: None,
+            modified: HashMap::new(),
+            last_poll: 0.0,
+        };
+        hot_reload.modified = hot_reload.scan();
+        hot_reload.manifest = hot_reload.read_manifest().await;
+        hot_reload
+    }
+
+    /// Reloads everything that changed since the last poll into the world's
+    /// `AssetLoader`, leaving the rest of the game state alone.
+    pub async fn update(&mut self, world: &mut World) {
+        if get_time() - self.last_poll < POLL_INTERVAL {
+            return;
+        }
+        self.last_poll = get_time();
+
+        let modified = self.scan();
+        let changed: Vec<PathBuf> = modified
+            .iter()
+            .filter(|(path, time)| self.modified.get(*path) != Some(time))
+            .map(|(path, _time)| path.clone())
+            .collect();
+        self.modified = modified;
+
+        if changed.is_empty() {
+            return;
+        }
+
+        //a changed manifest can rename or retime anything, so reload it all
+        if changed.contains(&self.manifest_path) {
+            self.reload_manifest(world).await;
+            return;
+        }
+
+        for path in changed {
+            self.reload_file(world, &path).await;
+        }
+    }
+
+    async fn reload_manifest(&mut self, world: &mut World) {
+        let Some(manifest) = self.read_manifest().await else {
+            return;
+        };
+        self.manifest = Some(manifest);
+
+        let mut assets = world.resource_mut::<AssetLoader>();
+        let path = self.manifest_path.to_string_lossy();
+        match assets.load_manifest(&path).await {
+            Ok(_) => info!("reloaded {}", path),
+            Err(error) => {
+                for problem in error.problems() {
+                    warn!("{}", problem);
+                }
+            }
+        }
+    }
+
+    async fn reload_file(&self, world: &mut World, path: &Path) {
+        let Some(manifest) = &self.manifest else {
+            return;
+        };
+        let mut assets = world.resource_mut::<AssetLoader>();
+        let mut results = Vec::new();
+
+        for texture in manifest
+            .textures
+            .iter()
+            .filter(|entry| same_file(&entry.path, path))
+        {
+            let result = assets.load_texture(&texture.name, &texture.path).await;
+            results.push(result.map(|_| ()));
+        }
+        for sound in manifest
+            .sounds
+            .iter()
+            .filter(|entry| same_file(&entry.path, path))
+        {
+            let result = assets.load_sound(&sound.name, &sound.path).await;
+            results.push(result.map(|_| ()));
+        }
+        for font in manifest
+            .fonts
+            .iter()
+            .filter(|entry| same_file(&entry.path, path))
+        {
+            let result = assets
+                .load_font(&font.name, &font.texture, &font.path)
+                .await;
+            results.push(result.map(|_| ()));
+        }
+        for atlas in manifest
+            .atlases
+            .iter()
+            .filter(|entry| same_file(&entry.path, path))
+        {
+            let result = assets
+                .load_atlas(
+                    &atlas.name,
+                    &atlas.texture,
+                    &atlas.path,
+                    atlas.frame_duration,
+                )
+                .await;
+            results.push(result.map(|_| ()));
+        }
+
+        for result in results {
+            match result {
+                Ok(()) => info!("reloaded {}", path.display()),
+                Err(error) => warn!("{}, keeping the previous version", error),
+            }
+        }
+    }
+
+    async fn read_manifest(&self) -> Option<Manifest> {
+        let path = self.manifest_path.to_string_lossy();
+        let parsed = load_string(&path)
+            .await
+            .map_err(|error| error.to_string())
+            .and_then(|json| Manifest::parse(&json).map_err(|error| error.to_string()));
+
+        match parsed {
+            Ok(manifest) => Some(manifest),
+            Err(error) => {
+                warn!("cannot reload {}: {}", path, error);
+                None
+            }
+        }
+    }
+
+    /// Modification times of every file below the root.
+    fn scan(&self) -> HashMap<PathBuf, SystemTime> {
+        let mut modified = HashMap::new();
+        let mut directories = vec![self.root.clone()];
+
+        while let Some(directory) = directories.pop() {
+            let Ok(entries) = fs::read_dir(&directory) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                if metadata.is_dir() {
+                    directories.push(path);
+                } else if let Ok(time) = metadata.modified() {
+                    modified.insert(path, time);
+                }
+            }
+        }
+
+        modified
+    }
+}
+
+fn same_file(manifest_path: &str, path: &Path) -> bool {
+    Path::new(manifest_path) == path
+}
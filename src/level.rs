@@ -0,0 +1,417 @@
+use bevy_ecs::prelude::*;
+use macroquad::prelude::{Color, GOLD, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::asset_loader::{PIPE_TEXTURE, PIXEL_FONT};
+use crate::error::{Error, Result};
+use crate::game::{GameState, GameStates, SCREEN_SIZE};
+use crate::pipe::{
+    PIPE_SPEED, PIPE_WIDTH, PIPES_H_SEPARATION, PIPES_V_SEPARATION, Pipe, PipeBundle,
+};
+use crate::player::{
+    FLOOR_COLLISION_Y, PLAYER_COLLISION, PLAYER_SPRITE_SIZES, Player, best_player,
+};
+use crate::simulation::{GameEvent, GameEvents, TICK};
+use crate::sprite::{LAYER_PIPES, Sprite};
+use crate::text::Text;
+use crate::transform::{Position, Velocity};
+
+/// Half the size of the box around a pickup that a bird has to touch.
+const PICKUP_RADIUS: f32 = 8.0;
+/// The smallest gap a bird fits through.
+pub const MIN_GAP_HEIGHT: f32 = PLAYER_COLLISION.y + 10.0;
+const FINISH_LINE_WIDTH: f32 = 4.0;
+const FINISH_LINE_TINT: Color = Color::new(1.0, 1.0, 1.0, 0.6);
+
+/// A handcrafted course of pipes, played in place of the random ones.
+/// Levels are JSON files:
+///
+/// ```json
+/// {
+///   "name": "FIRST FLIGHT",
+///   "par": 4,
+///   "obstacles": [
+///     { "gap_y": -200 },
+///     { "spacing": 180, "gap_y": -150, "gap_height": 90,
+///       "movement": { "type": "bounce", "speed": 30, "range": 40 },
+///       "pickups": [{ "x": 90, "y": -120, "points": 2 }] }
+///   ],
+///   "finish": 200
+/// }
+/// ```
+#[derive(Resource, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Level {
+    pub name: String,
+    /// The score a good run gets.
+    pub par: u32,
+    pub obstacles: Vec<LevelPipe>,
+    /// Distance from the last pipe to the finish line.
+    #[serde(default = "default_finish")]
+    pub finish: f32,
+}
+
+/// One pipe of a [`Level`], in pixels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelPipe {
+    /// Distance from the previous pipe. The first pipe always starts at the
+    /// right edge of the screen, like the random ones.
+    #[serde(default = "default_spacing")]
+    pub spacing: f32,
+    /// Height of the top of the gap.
+    pub gap_y: f32,
+    #[serde(default = "default_gap_height")]
+    pub gap_height: f32,
+    #[serde(default)]
+    pub movement: Movement,
+    #[serde(default)]
+    pub pickups: Vec<LevelPickup>,
+}
+
+/// How a pipe's gap moves while it scrolls by.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Movement {
+    #[default]
+    Still,
+    /// Up and down at `speed` pixels per second, at most `range` pixels
+    /// away from `gap_y`, starting downwards.
+    Bounce { speed: f32, range: f32 },
+}
+
+/// Points floating near a pipe, for the first bird that touches them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LevelPickup {
+    /// From the left edge of the pipe.
+    pub x: f32,
+    pub y: f32,
+    #[serde(default = "default_points")]
+    pub points: u32,
+}
+
+fn default_finish() -> f32 {
+    PIPES_H_SEPARATION
+}
+
+fn default_spacing() -> f32 {
+    PIPES_H_SEPARATION
+}
+
+fn default_gap_height() -> f32 {
+    PIPES_V_SEPARATION
+}
+
+fn default_points() -> u32 {
+    1
+}
+
+impl Level {
+    pub fn load(path: &str) -> Result<Self> {
+        let json = std::fs::read_to_string(path).map_err(|error| Error::asset(path, error))?;
+        let level: Level =
+            serde_json::from_str(&json).map_err(|error| Error::asset(path, error))?;
+
+        let problems: Vec<Error> = level
+            .problems()
+            .into_iter()
+            .map(|problem| Error::asset(path, problem))
+            .collect();
+        match problems.len() {
+            0 => Ok(level),
+            1 => Err(problems.into_iter().next().unwrap()),
+            _ => Err(Error::Many(problems)),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(|error| Error::write(path, error))?;
+        std::fs::write(path, json).map_err(|error| Error::write(path, error))
+    }
+
+    /// What makes the level unplayable, one message per problem.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.obstacles.is_empty() {
+            problems.push("a level needs at least one pipe".to_string());
+        }
+        if self.finish < PIPE_WIDTH {
+            problems.push(format!("finish must be at least {PIPE_WIDTH}"));
+        }
+
+        for (index, pipe) in self.obstacles.iter().enumerate() {
+            let (top, bottom) = pipe.gap_range();
+            if index > 0 && pipe.spacing < PIPE_WIDTH {
+                problems.push(format!(
+                    "pipe {index}: spacing must be at least {PIPE_WIDTH}"
+                ));
+            }
+            if pipe.gap_height < MIN_GAP_HEIGHT {
+                problems.push(format!(
+                    "pipe {index}: gap_height must be at least {MIN_GAP_HEIGHT}"
+                ));
+            }
+            if top < -SCREEN_SIZE || bottom + pipe.gap_height > FLOOR_COLLISION_Y {
+                problems.push(format!("pipe {index}: the gap leaves the screen"));
+            }
+            if let Movement::Bounce { speed, range } = pipe.movement
+                && (speed <= 0.0 || range < 0.0)
+            {
+                problems.push(format!(
+                    "pipe {index}: bounce needs a positive speed and range"
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Where each pipe starts, in order.
+    pub fn pipe_positions(&self) -> Vec<Position> {
+        let mut x = SCREEN_SIZE;
+        self.obstacles
+            .iter()
+            .enumerate()
+            .map(|(index, pipe)| {
+                if index > 0 {
+                    x += pipe.spacing;
+                }
+                Position { x, y: pipe.gap_y }
+            })
+            .collect()
+    }
+
+    pub fn finish_x(&self) -> f32 {
+        self.pipe_positions()
+            .last()
+            .map_or(SCREEN_SIZE, |position| position.x)
+            + self.finish
+    }
+}
+
+impl LevelPipe {
+    /// The highest and lowest the top of the gap gets.
+    fn gap_range(&self) -> (f32, f32) {
+        match self.movement {
+            Movement::Still => (self.gap_y, self.gap_y),
+            Movement::Bounce { range, .. } => (self.gap_y - range, self.gap_y + range),
+        }
+    }
+}
+
+/// Where an entity of the level starts, to put it back there when the level
+/// restarts.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct LevelStart {
+    pub position: Position,
+    pub velocity: Velocity,
+}
+
+/// Keeps the top of a bouncing pipe's gap between two heights.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct GapBounds {
+    pub top: f32,
+    pub bottom: f32,
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Pickup {
+    pub points: u32,
+    pub collected: bool,
+}
+
+/// Ends the level once a bird flies past it.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct FinishLine;
+
+const SCROLL: Velocity = Velocity {
+    x: -PIPE_SPEED,
+    y: 0.0,
+};
+
+/// Spawns the pipes, pickups and finish line of `level`, in place of the
+/// random pipes, as if the first `scroll` pixels of it already went by.
+pub fn spawn_level(commands: &mut Commands, level: &Level, scroll: f32) {
+    for (pipe, mut position) in level.obstacles.iter().zip(level.pipe_positions()) {
+        position.x -= scroll;
+        let mut bundle = PipeBundle::with_gap(position.x, position.y, pipe.gap_height);
+        let mut entity = match pipe.movement {
+            Movement::Still => commands.spawn(bundle),
+            Movement::Bounce { speed, range } => {
+                bundle.velocity.y = speed;
+                let (top, bottom) = (position.y - range, position.y + range);
+                commands.spawn((bundle, GapBounds { top, bottom }))
+            }
+        };
+        entity.insert(LevelStart {
+            position,
+            velocity: Velocity {
+                x: SCROLL.x,
+                y: match pipe.movement {
+                    Movement::Still => 0.0,
+                    Movement::Bounce { speed, .. } => speed,
+                },
+            },
+        });
+
+        for pickup in &pipe.pickups {
+            let start = Position {
+                x: position.x + pickup.x,
+                y: pickup.y,
+            };
+            commands.spawn((
+                start,
+                SCROLL,
+                LevelStart {
+                    position: start,
+                    velocity: SCROLL,
+                },
+                Pickup {
+                    points: pickup.points,
+                    collected: false,
+                },
+                Text::new(format!("+{}", pickup.points), PIXEL_FONT).with_color(GOLD),
+            ));
+        }
+    }
+
+    let finish = Position {
+        x: level.finish_x() - scroll,
+        y: -SCREEN_SIZE,
+    };
+    let mut sprite = Sprite::new(PIPE_TEXTURE, LAYER_PIPES).with_tint(FINISH_LINE_TINT);
+    sprite.dest_size = Some(Vec2::new(
+        FINISH_LINE_WIDTH,
+        SCREEN_SIZE + FLOOR_COLLISION_Y,
+    ));
+    commands.spawn((
+        finish,
+        SCROLL,
+        LevelStart {
+            position: finish,
+            velocity: SCROLL,
+        },
+        FinishLine,
+        sprite,
+    ));
+}
+
+/// Removes the pipes, pickups and finish line of the level that is played,
+/// to spawn another one.
+pub fn despawn_level(world: &mut World) {
+    let mut level_query = world.query_filtered::<Entity, With<LevelStart>>();
+    let entities: Vec<Entity> = level_query.iter(world).collect();
+    for entity in entities {
+        world.despawn(entity);
+    }
+}
+
+/// Scrolls the pickups and the finish line along with the pipes.
+#[allow(clippy::type_complexity)]
+pub fn move_level(
+    game_state: Res<GameState>,
+    mut query: Query<(&mut Position, &Velocity), (With<LevelStart>, Without<Pipe>)>,
+) {
+    if game_state.game_state == GameStates::Play {
+        for (mut position, velocity) in &mut query {
+            position.x += velocity.x * TICK;
+        }
+    }
+}
+
+/// Gives the points of a pickup to the first bird that touches it.
+pub fn collect_pickups(
+    game_state: Res<GameState>,
+    mut events: ResMut<GameEvents>,
+    mut pickup_query: Query<(&Position, &mut Pickup, &mut Text)>,
+    mut player_query: Query<(&Position, &mut Player), Without<Pickup>>,
+) {
+    if game_state.game_state != GameStates::Play {
+        return;
+    }
+
+    for (position, mut pickup, mut text) in &mut pickup_query {
+        if pickup.collected {
+            continue;
+        }
+        for (player_position, mut player) in &mut player_query {
+            let center_x = player_position.x + PLAYER_SPRITE_SIZES.x / 2.0;
+            let center_y = player_position.y + PLAYER_SPRITE_SIZES.y / 2.0;
+            if player.alive
+                && (center_x - position.x).abs() < PLAYER_COLLISION.x / 2.0 + PICKUP_RADIUS
+                && (center_y - position.y).abs() < PLAYER_COLLISION.y / 2.0 + PICKUP_RADIUS
+            {
+                pickup.collected = true;
+                text.visible = false;
+                player.score += pickup.points;
+                events.0.push(GameEvent::Score {
+                    player: player.index,
+                    score: player.score,
+                });
+                break;
+            }
+        }
+    }
+}
+
+/// Ends the level as complete when the birds still flying reach the finish
+/// line. The best of them wins.
+pub fn finish_level(
+    mut game_state: ResMut<GameState>,
+    finish_query: Query<&Position, With<FinishLine>>,
+    player_query: Query<(&Position, &Player)>,
+) {
+    if game_state.game_state != GameStates::Play {
+        return;
+    }
+
+    for finish in &finish_query {
+        let reached = player_query.iter().any(|(position, player)| {
+            player.alive && position.x + PLAYER_SPRITE_SIZES.x / 2.0 > finish.x
+        });
+        if reached {
+            let alive: Vec<&Player> = player_query
+                .iter()
+                .map(|(_, player)| player)
+                .filter(|player| player.alive)
+                .collect();
+            game_state.score = player_query
+                .iter()
+                .map(|(_, player)| player.score)
+                .max()
+                .unwrap_or(0);
+            game_state.winner = best_player(&alive);
+            game_state.game_state = GameStates::LevelComplete;
+        }
+    }
+}
+
+/// Puts the level back the way it started when the game restarts. Runs
+/// before [`restart_game`](crate::game::restart_game).
+#[allow(clippy::type_complexity)]
+pub fn restart_level(
+    game_state: Res<GameState>,
+    mut query: Query<(
+        &mut Position,
+        &mut Velocity,
+        &LevelStart,
+        Option<&mut Pipe>,
+        Option<&mut Pickup>,
+        Option<&mut Text>,
+    )>,
+) {
+    if game_state.game_state != GameStates::Restart {
+        return;
+    }
+
+    for (mut position, mut velocity, start, pipe, pickup, text) in &mut query {
+        *position = start.position;
+        *velocity = start.velocity;
+        if let Some(mut pipe) = pipe {
+            pipe.can_score = true;
+        }
+        if let Some(mut pickup) = pickup {
+            pickup.collected = false;
+        }
+        if let Some(mut text) = text {
+            text.visible = true;
+        }
+    }
+}
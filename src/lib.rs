@@ -1,8 +1,36 @@
+pub mod achievement;
+pub mod animation;
+pub mod args;
 pub mod asset_loader;
+pub mod atlas;
 pub mod background;
+pub mod batch;
+pub mod bot;
+pub mod campaign;
+pub mod display;
 pub mod draw;
+pub mod editor;
+pub mod env;
+pub mod error;
 pub mod game;
+#[cfg(feature = "hot-reload")]
+pub mod hot_reload;
+pub mod level;
+pub mod manifest;
+pub mod mode;
+pub mod net;
+pub mod neuro;
 pub mod pipe;
 pub mod player;
+pub mod practice;
+pub mod save;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod simulation;
+pub mod software;
+pub mod sprite;
+pub mod stats;
+pub mod telemetry;
+pub mod text;
 pub mod transform;
 pub mod ui;
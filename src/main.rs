@@ -1,53 +1,367 @@
 use bevy_ecs::prelude::*;
+use flappy::achievement::{
+    ACHIEVEMENT_PROGRESS_PATH, ACHIEVEMENTS_PATH, Achievements, achievement_screen,
+    track_achievements,
+};
+use flappy::animation::update_animators;
+use flappy::args::{Args, Conflicts, exit};
 use flappy::asset_loader::initialize_assets;
-use flappy::background::{spawn_background, update_background};
+use flappy::background::spawn_background;
+use flappy::bot::{AttractMode, attract_mode};
+use flappy::campaign::{Campaign, campaign_menu, show_campaign_level, track_campaign_crashes};
+use flappy::display::{Display, MAX_ASPECT_RATIO, ScaleMode, center_on_view, update_display};
 use flappy::draw::draw_call;
-use flappy::game::{SCREEN_SIZE, check_inputs, restart_game};
-use flappy::pipe::{move_pipe, spawn_pipes};
-use flappy::player::{add_gravity, collide_player, move_player, spawn_player};
-use flappy::ui::add_ui;
-use macroquad::prelude::{Camera2D, Rect, SKYBLUE, clear_background, next_frame, set_camera};
+use flappy::editor::{Editor, edit_level, show_level};
+use flappy::game::{MatchSettings, SCREEN_SIZE, check_inputs, restart_game, sync_players};
+use flappy::level::{Level, restart_level};
+#[cfg(feature = "hot-reload")]
+use flappy::manifest::MANIFEST_PATH;
+use flappy::mode::{HIGH_SCORES_PATH, HighScores, record_high_score};
+use flappy::net::{NetplayConfig, RollbackSession};
+use flappy::neuro::{EvolutionConfig, Genome, Trainer, training_schedule};
+use flappy::pipe::{PipeSpawn, spawn_pipes};
+use flappy::player::{
+    play_sounds, read_keyboard_input, rotate_player_sprite, spawn_players, tint_players,
+};
+use flappy::practice::{Practice, practice};
+use flappy::save::{SaveState, quicksave};
+use flappy::simulation::{
+    discard_events, insert_simulation_resources, run_fixed_ticks, simulation_schedule,
+};
+use flappy::sprite::animate_sprites;
+use flappy::stats::{LifetimeStats, STATS_PATH, record_stats, stats_screen};
+use flappy::telemetry::{TELEMETRY_DIR, Telemetry, add_telemetry};
+use flappy::ui::{
+    add_ui, update_achievement_text, update_campaign_text, update_demo_text, update_editor_text,
+    update_high_score_text, update_level_text, update_match_text, update_mode_text,
+    update_practice_text, update_score_text, update_stats_text, update_training_text,
+    update_ui_visibility,
+};
+use macroquad::miniquad::date;
+use macroquad::prelude::{get_frame_time, next_frame};
 use macroquad::window::request_new_screen_size;
 
 #[macroquad::main("Flappy")]
 async fn main() {
+    let args = Args::from_env();
+    args.check_conflicts(CONFLICTS)
+        .unwrap_or_else(|error| exit(&error));
+    let netplay = NetplayConfig::from_args(&args).unwrap_or_else(|error| exit(&error));
+    //watching neural networks learn, see the train binary for the options
+    let training = args.has("--train").then(|| {
+        let config = EvolutionConfig::from_args(&args).unwrap_or_else(|error| exit(&error));
+        let ancestors = value(&args, "--load")
+            .map(|path| Genome::load(path).unwrap_or_else(|error| exit(&error.to_string())))
+            .into_iter()
+            .collect();
+        Trainer::new(config, ancestors)
+    });
+
+    //a handcrafted level in place of random pipes
+    let level = value(&args, "--level")
+        .map(|path| Level::load(path).unwrap_or_else(|error| exit(&error.to_string())));
+
+    //editing a level, new when the file doesn't exist yet
+    let editor = value(&args, "--edit")
+        .map(|path| Editor::open(path).unwrap_or_else(|error| exit(&error.to_string())));
+
+    //a script changing the rules, see flappy::script
+    #[cfg(feature = "scripting")]
+    let scripts = value(&args, "--script").map(|path| {
+        flappy::script::Scripts::load(path).unwrap_or_else(|error| exit(&error.to_string()))
+    });
+    #[cfg(not(feature = "scripting"))]
+    if args.has("--script") {
+        exit("--script needs the scripting feature");
+    }
+
+    //levels unlocked one after the other, with progress saved
+    let campaign = value(&args, "--campaign")
+        .map(|path| Campaign::load(path).unwrap_or_else(|error| exit(&error.to_string())));
+
+    //crashes rewind instead of ending the run, optionally from a later pipe
+    let practice_from = args
+        .parse_optional("--practice", 0)
+        .unwrap_or_else(|error| exit(&error));
+
+    //a saved game to go on from, such as one attached to a bug report
+    let save_state = value(&args, "--state")
+        .map(|path| SaveState::load(path).unwrap_or_else(|error| exit(&error.to_string())));
+
+    //a log of every round for working out where and why birds crash
+    let telemetry_dir = args
+        .parse_optional("--telemetry", TELEMETRY_DIR.to_string())
+        .unwrap_or_else(|error| exit(&error));
+
+    //crisp whole multiples of the pixels, or as big as the window allows
+    let scale_mode = value(&args, "--scale").map_or(ScaleMode::default(), |name| {
+        ScaleMode::from_name(name).unwrap_or_else(|| exit("--scale takes integer or fit"))
+    });
+    //wide windows show more of the pipes ahead instead of bars
+    let wide = args.has("--wide");
+
     //initialize resources such as sprites
     let mut world = World::default();
     initialize_assets(&mut world).await;
+    world.insert_resource(Display::new(scale_mode, wide));
+    world.init_resource::<AttractMode>();
+
+    //a network game needs the same layout on both machines
+    let seed = match (&netplay, &training) {
+        (Some(config), _) => config.seed,
+        (None, Some(trainer)) => trainer.config.seed,
+        (None, None) => date::now() as u64,
+    };
+    insert_simulation_resources(&mut world, seed);
+    if wide {
+        world.insert_resource(PipeSpawn {
+            x: SCREEN_SIZE * MAX_ASPECT_RATIO,
+        });
+    }
+    if let Some(config) = &netplay {
+        world.insert_resource(config.match_settings());
+    }
+    let playing_level = level.is_some() || editor.is_some() || campaign.is_some();
+    let editing = editor.is_some();
+    let in_campaign = campaign.is_some();
+    let practicing = practice_from.is_some();
+    if let Some(from) = practice_from {
+        world.insert_resource(Practice::new(seed, from));
+    }
+    if let Some(level) = level {
+        world.insert_resource(level);
+    }
+    if let Some(editor) = editor {
+        world.insert_resource(editor.level.clone());
+        world.insert_resource(editor);
+    }
+    if let Some(campaign) = campaign {
+        world.insert_resource(campaign.level().level.clone());
+        world.insert_resource(campaign);
+    }
 
     //Startup systems. Run once
     let mut startup_schedule = Schedule::default();
     startup_schedule.add_systems(spawn_pipes);
-    startup_schedule.add_systems(spawn_player);
+    startup_schedule.add_systems(spawn_players);
     startup_schedule.add_systems(spawn_background);
     startup_schedule.add_systems(add_ui);
+    if let Some(state) = &save_state {
+        world.resource_mut::<MatchSettings>().players = state.birds.len();
+    }
     startup_schedule.run(&mut world);
+    if let Some(state) = save_state {
+        state
+            .restore(&mut world)
+            .unwrap_or_else(|error| exit(&error.to_string()));
+    }
 
-    //Update Systems. Run in a loop
-    let mut schedule = Schedule::default();
-    schedule.add_systems(check_inputs);
+    let mut session = netplay.as_ref().map(|config| {
+        config.assign_controllers(&mut world);
+        RollbackSession::new(config)
+            .unwrap_or_else(|error| exit(&format!("cannot open {}: {error}", config.local)))
+    });
+    if let Some(trainer) = training {
+        trainer.start(&mut world);
+        world.insert_resource(trainer);
+    }
+    let training = world.contains_resource::<Trainer>();
+
+    //Input systems. Run every frame, before the simulation
+    let mut input_schedule = Schedule::default();
+    if session.is_none() && !training {
+        //starting, restarting and changing players would desync a network game
+        input_schedule.add_systems((check_inputs, restart_game, sync_players).chain());
+        //no demo while a level is edited or chosen, or in practice
+        if !editing && !in_campaign && !practicing {
+            input_schedule.add_systems(attract_mode.before(check_inputs));
+        }
+    }
+    input_schedule.add_systems(read_keyboard_input);
+    let scripted = args.has("--script");
+    //levels, practice, scripts and network games don't make the high score tables
+    let keep_high_scores =
+        session.is_none() && !training && !playing_level && !practicing && !scripted;
+    if keep_high_scores {
+        let high_scores =
+            HighScores::load(HIGH_SCORES_PATH).unwrap_or_else(|error| exit(&error.to_string()));
+        world.insert_resource(high_scores);
+    }
+    //achievements are for people at this keyboard, not earned by rewinding or
+    //by the points a script hands out
+    let keep_achievements = session.is_none() && !training && !practicing && !scripted;
+    if keep_achievements {
+        let achievements = Achievements::load(ACHIEVEMENTS_PATH, ACHIEVEMENT_PROGRESS_PATH)
+            .unwrap_or_else(|error| exit(&error.to_string()));
+        world.insert_resource(achievements);
+        //editing and campaigns have start screens of their own
+        if !editing && !in_campaign {
+            input_schedule.add_systems(achievement_screen.after(sync_players));
+        }
+    }
+    //test flights and the rules of a script would skew the stats
+    let keep_stats = session.is_none() && !training && !practicing && !editing && !scripted;
+    if keep_stats {
+        let lifetime =
+            LifetimeStats::load(STATS_PATH).unwrap_or_else(|error| exit(&error.to_string()));
+        world.insert_resource(lifetime);
+        if !in_campaign {
+            input_schedule.add_systems(stats_screen.after(sync_players));
+        }
+    }
+    //a save state has no room for the pickups and bounds of a level, nor for
+    //the physics and obstacles of a script
+    if session.is_none() && !training && !playing_level && !scripted {
+        input_schedule.add_systems(quicksave.after(sync_players));
+    }
+    if playing_level {
+        input_schedule.add_systems(restart_level.before(restart_game));
+    }
+    if editing {
+        input_schedule.add_systems((edit_level, show_level).chain().after(restart_game));
+    }
+    if in_campaign {
+        input_schedule.add_systems(
+            (campaign_menu, show_campaign_level)
+                .chain()
+                .after(restart_game),
+        );
+    }
 
-    schedule.add_systems(add_gravity);
+    //Simulation systems. Run at a fixed rate
+    let mut simulation = if training {
+        training_schedule()
+    } else {
+        simulation_schedule()
+    };
+    #[cfg(feature = "scripting")]
+    if let Some(scripts) = scripts {
+        world.insert_resource(scripts);
+        simulation = flappy::script::scripted_schedule();
+        input_schedule.add_systems(flappy::script::restart_scripts.before(restart_game));
+    }
 
-    schedule.add_systems(collide_player);
-    schedule.add_systems(restart_game);
+    if let Some(dir) = telemetry_dir {
+        let telemetry =
+            Telemetry::open(&dir, seed).unwrap_or_else(|error| exit(&error.to_string()));
+        world.insert_resource(telemetry);
+        add_telemetry(&mut simulation);
+    }
 
-    //update
+    //Update Systems. Run in a loop
+    let mut schedule = Schedule::default();
+    //hundreds of flapping birds are better left silent
+    if training {
+        schedule.add_systems(discard_events);
+    } else {
+        schedule.add_systems(play_sounds);
+    }
+    if keep_achievements {
+        schedule.add_systems(track_achievements.before(play_sounds));
+    }
+    if keep_stats {
+        schedule.add_systems(record_stats.before(play_sounds));
+    }
+    if in_campaign {
+        schedule.add_systems(track_campaign_crashes.before(play_sounds));
+    }
+    schedule.add_systems(tint_players);
+    if keep_high_scores {
+        schedule.add_systems(record_high_score);
+    }
+    schedule.add_systems(update_score_text);
+    schedule.add_systems(update_ui_visibility);
+    schedule.add_systems(update_match_text);
+    schedule.add_systems(update_demo_text);
+    schedule.add_systems(update_training_text);
+    schedule.add_systems(update_level_text);
+    schedule.add_systems(update_editor_text);
+    schedule.add_systems(update_campaign_text);
+    schedule.add_systems(update_practice_text);
+    schedule.add_systems(update_achievement_text);
+    schedule.add_systems(update_stats_text);
+    if practicing {
+        schedule.add_systems(practice.before(update_ui_visibility));
+    }
+    schedule.add_systems(update_mode_text);
+    schedule.add_systems(update_high_score_text);
+    schedule.add_systems((update_animators, animate_sprites).chain());
+    schedule.add_systems(rotate_player_sprite);
 
-    schedule.add_systems(move_player);
-    schedule.add_systems(move_pipe);
-    schedule.add_systems(update_background);
+    schedule.add_systems(update_display);
+    schedule.add_systems(center_on_view);
 
     //draw
     schedule.add_systems(draw_call);
 
-    let camera = Camera2D::from_display_rect(Rect::new(0.0, 0.0, SCREEN_SIZE, -SCREEN_SIZE));
     request_new_screen_size(SCREEN_SIZE * 2.0, SCREEN_SIZE * 2.0);
 
+    #[cfg(feature = "hot-reload")]
+    let mut hot_reload = flappy::hot_reload::HotReload::new("assets", MANIFEST_PATH).await;
+
     loop {
-        clear_background(SKYBLUE);
-        set_camera(&camera);
+        #[cfg(feature = "hot-reload")]
+        hot_reload.update(&mut world).await;
+
+        input_schedule.run(&mut world);
+        match &mut session {
+            Some(session) => {
+                session.update(&mut world, &mut simulation, get_frame_time(), u32::MAX)
+            }
+            None => run_fixed_ticks(&mut world, &mut simulation, get_frame_time()),
+        }
+        if training {
+            world.resource_scope(|world, mut trainer: Mut<Trainer>| {
+                if let Some(stats) = trainer.update(world) {
+                    println!("{stats}");
+                }
+            });
+        }
         schedule.run(&mut world);
         next_frame().await
     }
 }
+
+/// Flags of the game that can't be combined, a network game being `--peer`.
+const CONFLICTS: &Conflicts = &[
+    (
+        "--peer",
+        &[
+            "--train",
+            "--level",
+            "--edit",
+            "--script",
+            "--campaign",
+            "--practice",
+            "--state",
+            "--telemetry",
+            "--wide",
+        ],
+    ),
+    (
+        "--train",
+        &[
+            "--level",
+            "--edit",
+            "--script",
+            "--campaign",
+            "--practice",
+            "--state",
+            "--telemetry",
+        ],
+    ),
+    (
+        "--level",
+        &["--edit", "--campaign", "--practice", "--state"],
+    ),
+    ("--edit", &["--campaign", "--practice", "--state"]),
+    ("--campaign", &["--practice", "--state"]),
+    //rewinds and save states have no room for the physics and obstacles
+    ("--script", &["--practice", "--state"]),
+    ("--practice", &["--state"]),
+];
+
+/// The value after `flag`, ending the game when it is missing.
+fn value<'a>(args: &'a Args, flag: &str) -> Option<&'a str> {
+    args.value(flag).unwrap_or_else(|error| exit(&error))
+}
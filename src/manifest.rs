@@ -0,0 +1,369 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+use macroquad::prelude::*;
+use serde::Deserialize;
+
+use crate::animation::{AnimationClip, ClipFrame};
+use crate::atlas::DEFAULT_FRAME_DURATION;
+
+pub const MANIFEST_PATH: &str = "assets/manifest.json";
+
+/// Every asset the game looks up by name, listed in `assets/manifest.json`.
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Manifest {
+    #[serde(default)]
+    pub textures: Vec<TextureEntry>,
+    #[serde(default)]
+    pub sounds: Vec<SoundEntry>,
+    #[serde(default)]
+    pub fonts: Vec<FontEntry>,
+    #[serde(default)]
+    pub sprite_sheets: Vec<SpriteSheetEntry>,
+    #[serde(default)]
+    pub animations: Vec<AnimationEntry>,
+    #[serde(default)]
+    pub atlases: Vec<AtlasEntry>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TextureEntry {
+    pub name: String,
+    pub path: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SoundEntry {
+    pub name: String,
+    pub path: String,
+}
+
+/// Glyph metrics in `path`, drawn from the texture called `texture`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct FontEntry {
+    pub name: String,
+    pub texture: String,
+    pub path: String,
+}
+
+/// A texture cut into equally sized tiles, one animation clip per row.
+#[derive(Deserialize, Debug, Clone)]
+pub struct SpriteSheetEntry {
+    pub name: String,
+    pub texture: String,
+    pub tile_width: u32,
+    pub tile_height: u32,
+}
+
+/// An Aseprite or TexturePacker JSON export describing frames and clips of
+/// `texture`. `frame_duration` is used for frames without their own.
+#[derive(Deserialize, Debug, Clone)]
+pub struct AtlasEntry {
+    pub name: String,
+    pub texture: String,
+    pub path: String,
+    #[serde(default = "default_frame_duration")]
+    pub frame_duration: f32,
+}
+
+fn default_frame_duration() -> f32 {
+    DEFAULT_FRAME_DURATION
+}
+
+/// Clips cut from a sprite sheet, available as `<name>/<clip name>`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct AnimationEntry {
+    pub name: String,
+    pub sheet: String,
+    pub clips: Vec<ClipEntry>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ClipEntry {
+    pub name: String,
+    pub row: u32,
+    pub frames: u32,
+    pub fps: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ManifestError {
+    Parse(String),
+    Duplicate {
+        section: &'static str,
+        name: String,
+    },
+    MissingFile {
+        section: &'static str,
+        name: String,
+        path: String,
+    },
+    UnknownReference {
+        section: &'static str,
+        name: String,
+        reference: String,
+    },
+    MissingKey {
+        section: &'static str,
+        name: String,
+    },
+    EmptyClip {
+        animation: String,
+        clip: String,
+    },
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestError::Parse(error) => write!(f, "cannot parse manifest: {error}"),
+            ManifestError::Duplicate { section, name } => {
+                write!(f, "{section} \"{name}\" is defined more than once")
+            }
+            ManifestError::MissingFile {
+                section,
+                name,
+                path,
+            } => write!(f, "{section} \"{name}\" points to missing file {path}"),
+            ManifestError::UnknownReference {
+                section,
+                name,
+                reference,
+            } => write!(f, "{section} \"{name}\" refers to unknown \"{reference}\""),
+            ManifestError::MissingKey { section, name } => {
+                write!(
+                    f,
+                    "{section} \"{name}\" is used by the game but not defined"
+                )
+            }
+            ManifestError::EmptyClip { animation, clip } => write!(
+                f,
+                "clip \"{clip}\" of animation \"{animation}\" needs at least one frame and fps"
+            ),
+        }
+    }
+}
+
+/// Names the manifest has to define, see `asset_loader::REQUIRED_ASSETS`.
+pub struct RequiredAssets {
+    pub textures: &'static [&'static str],
+    pub sounds: &'static [&'static str],
+    pub fonts: &'static [&'static str],
+    /// Checked once atlases are loaded, see `AssetLoader::missing_clips`.
+    pub clips: &'static [&'static str],
+}
+
+impl Manifest {
+    pub fn parse(json: &str) -> Result<Self, ManifestError> {
+        serde_json::from_str(json).map_err(|error| ManifestError::Parse(error.to_string()))
+    }
+
+    /// Parses and validates a manifest in one step.
+    pub fn load_from_str(
+        json: &str,
+        required: &RequiredAssets,
+    ) -> Result<Self, Vec<ManifestError>> {
+        let manifest = Self::parse(json).map_err(|error| vec![error])?;
+        let errors = manifest.validate(required);
+
+        if errors.is_empty() {
+            Ok(manifest)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Checks the whole manifest and returns every problem found, so all of
+    /// them can be fixed in one go.
+    pub fn validate(&self, required: &RequiredAssets) -> Vec<ManifestError> {
+        let mut errors = Vec::new();
+
+        let textures = unique_names(
+            "texture",
+            self.textures.iter().map(|entry| &entry.name),
+            &mut errors,
+        );
+        let sounds = unique_names(
+            "sound",
+            self.sounds.iter().map(|entry| &entry.name),
+            &mut errors,
+        );
+        let fonts = unique_names(
+            "font",
+            self.fonts.iter().map(|entry| &entry.name),
+            &mut errors,
+        );
+        let sheets = unique_names(
+            "sprite sheet",
+            self.sprite_sheets.iter().map(|entry| &entry.name),
+            &mut errors,
+        );
+        unique_names(
+            "animation",
+            self.animations.iter().map(|entry| &entry.name),
+            &mut errors,
+        );
+        unique_names(
+            "atlas",
+            self.atlases.iter().map(|entry| &entry.name),
+            &mut errors,
+        );
+
+        let files = self
+            .textures
+            .iter()
+            .map(|entry| ("texture", &entry.name, &entry.path))
+            .chain(
+                self.sounds
+                    .iter()
+                    .map(|entry| ("sound", &entry.name, &entry.path)),
+            )
+            .chain(
+                self.fonts
+                    .iter()
+                    .map(|entry| ("font", &entry.name, &entry.path)),
+            )
+            .chain(
+                self.atlases
+                    .iter()
+                    .map(|entry| ("atlas", &entry.name, &entry.path)),
+            );
+        for (section, name, path) in files {
+            if !Path::new(path).exists() {
+                errors.push(ManifestError::MissingFile {
+                    section,
+                    name: name.clone(),
+                    path: path.clone(),
+                });
+            }
+        }
+
+        let references = self
+            .fonts
+            .iter()
+            .map(|entry| ("font", &entry.name, &entry.texture, &textures))
+            .chain(
+                self.sprite_sheets
+                    .iter()
+                    .map(|entry| ("sprite sheet", &entry.name, &entry.texture, &textures)),
+            )
+            .chain(
+                self.animations
+                    .iter()
+                    .map(|entry| ("animation", &entry.name, &entry.sheet, &sheets)),
+            )
+            .chain(
+                self.atlases
+                    .iter()
+                    .map(|entry| ("atlas", &entry.name, &entry.texture, &textures)),
+            );
+        for (section, name, reference, known) in references {
+            if !known.contains(reference.as_str()) {
+                errors.push(ManifestError::UnknownReference {
+                    section,
+                    name: name.clone(),
+                    reference: reference.clone(),
+                });
+            }
+        }
+
+        for animation in &self.animations {
+            for clip in &animation.clips {
+                if clip.frames == 0 || clip.fps == 0 {
+                    errors.push(ManifestError::EmptyClip {
+                        animation: animation.name.clone(),
+                        clip: clip.name.clone(),
+                    });
+                }
+            }
+        }
+
+        let required = [
+            ("texture", required.textures, &textures),
+            ("sound", required.sounds, &sounds),
+            ("font", required.fonts, &fonts),
+        ];
+        for (section, names, defined) in required {
+            for name in names {
+                if !defined.contains(name) {
+                    errors.push(ManifestError::MissingKey {
+                        section,
+                        name: name.to_string(),
+                    });
+                }
+            }
+        }
+
+        errors
+    }
+
+    pub fn sprite_sheet(&self, name: &str) -> Option<&SpriteSheetEntry> {
+        self.sprite_sheets.iter().find(|sheet| sheet.name == name)
+    }
+
+    /// Cuts the clips of an entry out of its sprite sheet, one row each.
+    pub fn build_clips(&self, entry: &AnimationEntry) -> Result<Vec<AnimationClip>, ManifestError> {
+        let sheet =
+            self.sprite_sheet(&entry.sheet)
+                .ok_or_else(|| ManifestError::UnknownReference {
+                    section: "animation",
+                    name: entry.name.clone(),
+                    reference: entry.sheet.clone(),
+                })?;
+        let size = Vec2 {
+            x: sheet.tile_width as f32,
+            y: sheet.tile_height as f32,
+        };
+
+        let clips = entry
+            .clips
+            .iter()
+            .map(|clip| AnimationClip {
+                name: format!("{}/{}", entry.name, clip.name),
+                texture: sheet.texture.clone(),
+                frames: (0..clip.frames)
+                    .map(|frame| ClipFrame {
+                        source: Rect::new(
+                            frame as f32 * size.x,
+                            clip.row as f32 * size.y,
+                            size.x,
+                            size.y,
+                        ),
+                        offset: Vec2::ZERO,
+                        size,
+                        duration: 1.0 / clip.fps.max(1) as f32,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Ok(clips)
+    }
+}
+
+/// One line per problem, for panics and logs.
+pub fn report(errors: &[ManifestError]) -> String {
+    errors
+        .iter()
+        .map(|error| format!("  - {error}"))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn unique_names<'a>(
+    section: &'static str,
+    names: impl Iterator<Item = &'a String>,
+    errors: &mut Vec<ManifestError>,
+) -> HashSet<&'a str> {
+    let mut seen = HashSet::new();
+    for name in names {
+        if !seen.insert(name.as_str()) {
+            errors.push(ManifestError::Duplicate {
+                section,
+                name: name.clone(),
+            });
+        }
+    }
+    seen
+}
@@ -0,0 +1,178 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use bevy_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::bot::AttractMode;
+use crate::error::{Error, Result};
+use crate::game::{GameState, GameStates, MatchSettings};
+use crate::level::Level;
+use crate::player::{Player, best_player};
+use crate::simulation::{Distance, TICKS_PER_SECOND, Tick};
+
+/// Seconds a time attack round lasts.
+pub const TIME_LIMIT: u32 = 60;
+/// How far the pipes scroll for a metre flown.
+pub const PIXELS_PER_METRE: f32 = 12.0;
+/// Scores kept per mode.
+pub const HIGH_SCORE_COUNT: usize = 5;
+pub const HIGH_SCORES_PATH: &str = "highscores.json";
+
+/// What a round is played for. Changed on the start screen with Tab.
+#[derive(
+    Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMode {
+    /// As many pipes as possible, until the birds crash.
+    #[default]
+    Endless,
+    /// As many pipes as possible in [`TIME_LIMIT`] seconds.
+    TimeAttack,
+    /// As many metres as possible, until the birds crash.
+    Distance,
+}
+
+impl GameMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Endless => "ENDLESS",
+            GameMode::TimeAttack => "TIME ATTACK",
+            GameMode::Distance => "DISTANCE",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            GameMode::Endless => GameMode::TimeAttack,
+            GameMode::TimeAttack => GameMode::Distance,
+            GameMode::Distance => GameMode::Endless,
+        }
+    }
+
+    /// What the round scored in this mode: pipes, or metres in distance
+    /// mode.
+    pub fn score(&self, game_state: &GameState, distance: &Distance) -> u32 {
+        match self {
+            GameMode::Endless | GameMode::TimeAttack => game_state.score,
+            GameMode::Distance => distance.metres(),
+        }
+    }
+}
+
+impl Distance {
+    pub fn metres(&self) -> u32 {
+        (self.0 / PIXELS_PER_METRE) as u32
+    }
+}
+
+/// Whole seconds left in a time attack round that is `tick` ticks old,
+/// rounded up so the timer shows 0 only once the time is up.
+pub fn seconds_left(tick: &Tick) -> u32 {
+    (TIME_LIMIT * TICKS_PER_SECOND)
+        .saturating_sub(tick.0)
+        .div_ceil(TICKS_PER_SECOND)
+}
+
+/// Ends a time attack round once its time is up. The best score wins.
+pub fn time_up(
+    settings: Res<MatchSettings>,
+    tick: Res<Tick>,
+    mut game_state: ResMut<GameState>,
+    player_query: Query<&Player>,
+) {
+    if settings.mode == GameMode::TimeAttack
+        && game_state.game_state == GameStates::Play
+        && tick.0 >= TIME_LIMIT * TICKS_PER_SECOND
+    {
+        let players: Vec<&Player> = player_query.iter().collect();
+        game_state.winner = best_player(&players);
+        game_state.game_state = GameStates::GameOver;
+    }
+}
+
+/// The best scores of every mode, best first, saved to a file.
+#[derive(Resource, Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighScores {
+    pub tables: BTreeMap<GameMode, Vec<u32>>,
+    /// Place of the last round in its table, if it made it.
+    #[serde(skip)]
+    pub last_rank: Option<usize>,
+    /// Whether the round that ended was already looked at.
+    #[serde(skip)]
+    recorded: bool,
+    #[serde(skip)]
+    path: String,
+}
+
+impl HighScores {
+    /// Reads the scores saved at `path`, or starts empty when there are none.
+    pub fn load(path: &str) -> Result<Self> {
+        let mut high_scores: HighScores = if Path::new(path).exists() {
+            let json = std::fs::read_to_string(path).map_err(|error| Error::asset(path, error))?;
+            serde_json::from_str(&json).map_err(|error| Error::asset(path, error))?
+        } else {
+            HighScores::default()
+        };
+        high_scores.path = path.to_string();
+        Ok(high_scores)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let json =
+            serde_json::to_string_pretty(self).map_err(|error| Error::write(&self.path, error))?;
+        std::fs::write(&self.path, json).map_err(|error| Error::write(&self.path, error))
+    }
+
+    pub fn table(&self, mode: GameMode) -> &[u32] {
+        self.tables.get(&mode).map_or(&[], Vec::as_slice)
+    }
+
+    /// Puts `score` into the table of `mode` and returns its place, or
+    /// `None` when it is not good enough.
+    pub fn add(&mut self, mode: GameMode, score: u32) -> Option<usize> {
+        let table = self.tables.entry(mode).or_default();
+        let rank = table
+            .iter()
+            .position(|best| score > *best)
+            .unwrap_or(table.len());
+        if rank >= HIGH_SCORE_COUNT || score == 0 {
+            return None;
+        }
+        table.insert(rank, score);
+        table.truncate(HIGH_SCORE_COUNT);
+        Some(rank)
+    }
+}
+
+/// Enters the score of every round that ended into the table of its mode.
+/// Rounds with bots and levels don't count, and the game doesn't keep the
+/// tables in practice, scripted games or network games.
+pub fn record_high_score(
+    game_state: Res<GameState>,
+    settings: Res<MatchSettings>,
+    distance: Res<Distance>,
+    attract: Res<AttractMode>,
+    level: Option<Res<Level>>,
+    mut high_scores: ResMut<HighScores>,
+) {
+    match game_state.game_state {
+        GameStates::GameOver if !high_scores.recorded => {
+            high_scores.recorded = true;
+            high_scores.last_rank = None;
+            if settings.has_bots() || attract.active() || level.is_some() {
+                return;
+            }
+            let score = settings.mode.score(&game_state, &distance);
+            high_scores.last_rank = high_scores.add(settings.mode, score);
+            if high_scores.last_rank.is_some()
+                && let Err(error) = high_scores.save()
+            {
+                eprintln!("{error}");
+            }
+        }
+        GameStates::Play => high_scores.recorded = false,
+        _ => {}
+    }
+}
@@ -0,0 +1,602 @@
+use std::collections::VecDeque;
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use bevy_ecs::prelude::*;
+use macroquad::logging::{info, warn};
+
+use crate::args::Args;
+use crate::game::{GameState, GameStates, MatchSettings, WinRule};
+use crate::player::{Controller, Player};
+use crate::simulation::{GameEvent, GameEvents, GameRng, PlayerInput, Snapshot, TICK};
+
+/// How far the local game may run ahead of the last frame with known remote
+/// input. Further ahead it waits, so a rollback never replays more frames.
+pub const MAX_ROLLBACK_FRAMES: u32 = 12;
+/// Every this many frames, the checksum of the confirmed state is kept so
+/// two machines can compare them.
+pub const CHECKSUM_INTERVAL: u32 = 60;
+const MAX_INPUTS_PER_PACKET: usize = 64;
+const HELLO_INTERVAL: Duration = Duration::from_millis(100);
+const MAX_PACKET_SIZE: usize = 512;
+
+const HELLO_PACKET: u8 = b'H';
+const INPUTS_PACKET: u8 = b'I';
+
+/// Delay and loss added to outgoing packets, to try the netcode on one
+/// machine over loopback.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LinkConditions {
+    pub latency: Duration,
+    pub jitter: Duration,
+    /// Share of packets dropped, from 0 to 1.
+    pub loss: f32,
+}
+
+/// Everything needed to play against one other machine.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetplayConfig {
+    pub local: SocketAddr,
+    pub peer: SocketAddr,
+    /// Index of the bird played on this machine, 0 or 1.
+    pub player: usize,
+    /// Seed of the pipe layout, the same on both machines.
+    pub seed: u64,
+    pub conditions: LinkConditions,
+}
+
+impl NetplayConfig {
+    /// Reads `--local ADDR --peer ADDR [--player N] [--seed N] [--latency MS]
+    /// [--jitter MS] [--loss FRACTION]`. Without `--peer` there is no
+    /// network game and the result is `None`.
+    pub fn from_args(args: &Args) -> Result<Option<Self>, String> {
+        let Some(peer) = args.get("--peer")? else {
+            return Ok(None);
+        };
+        let local = args.get("--local")?.ok_or("--peer needs --local too")?;
+        let milliseconds = |flag: &str| -> Result<Duration, String> {
+            Ok(Duration::from_millis(args.parse(flag, 0)?))
+        };
+
+        let config = Self {
+            local,
+            peer,
+            player: args.parse("--player", 0)?,
+            seed: args.parse("--seed", 0)?,
+            conditions: LinkConditions {
+                latency: milliseconds("--latency")?,
+                jitter: milliseconds("--jitter")?,
+                loss: args.parse("--loss", 0.0)?,
+            },
+        };
+        if config.player > 1 {
+            return Err("--player must be 0 or 1".to_string());
+        }
+        Ok(Some(config))
+    }
+
+    /// Two birds, and the round ends when one of them crashes.
+    pub fn match_settings(&self) -> MatchSettings {
+        MatchSettings {
+            players: 2,
+            win_rule: WinRule::LastBirdStanding,
+            ..Default::default()
+        }
+    }
+
+    /// Hands the other bird to the peer, once the birds are spawned.
+    pub fn assign_controllers(&self, world: &mut World) {
+        let mut query = world.query::<(&Player, &mut Controller)>();
+        for (player, mut controller) in query.iter_mut(world) {
+            if player.index != self.player {
+                *controller = Controller::Remote;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Packet {
+    /// Sent until the peer answers, to agree on who plays which bird.
+    Hello { player: u8, seed: u64 },
+    /// The sender's flaps from frame `start` on, and how many of the
+    /// receiver's inputs it has so far.
+    Inputs {
+        ack: u32,
+        start: u32,
+        flaps: Vec<bool>,
+    },
+}
+
+impl Packet {
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        match self {
+            Packet::Hello { player, seed } => {
+                bytes.push(HELLO_PACKET);
+                bytes.push(*player);
+                bytes.extend_from_slice(&seed.to_le_bytes());
+            }
+            Packet::Inputs { ack, start, flaps } => {
+                bytes.push(INPUTS_PACKET);
+                bytes.extend_from_slice(&ack.to_le_bytes());
+                bytes.extend_from_slice(&start.to_le_bytes());
+                bytes.extend_from_slice(&(flaps.len() as u16).to_le_bytes());
+                for chunk in flaps.chunks(8) {
+                    let bits = chunk
+                        .iter()
+                        .enumerate()
+                        .fold(0u8, |bits, (bit, flap)| bits | ((*flap as u8) << bit));
+                    bytes.push(bits);
+                }
+            }
+        }
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Self> {
+        let u32_at = |at: usize| Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+
+        match *bytes.first()? {
+            HELLO_PACKET => Some(Packet::Hello {
+                player: *bytes.get(1)?,
+                seed: u64::from_le_bytes(bytes.get(2..10)?.try_into().ok()?),
+            }),
+            INPUTS_PACKET => {
+                let count = u16::from_le_bytes(bytes.get(9..11)?.try_into().ok()?) as usize;
+                let bits = bytes.get(11..11 + count.div_ceil(8))?;
+                Some(Packet::Inputs {
+                    ack: u32_at(1)?,
+                    start: u32_at(5)?,
+                    flaps: (0..count)
+                        .map(|index| bits[index / 8] & (1 << (index % 8)) != 0)
+                        .collect(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A non-blocking UDP socket talking to a single peer, which holds back or
+/// drops outgoing packets according to its [`LinkConditions`].
+pub struct Link {
+    socket: UdpSocket,
+    peer: SocketAddr,
+    conditions: LinkConditions,
+    rng: GameRng,
+    queue: Vec<(Instant, Vec<u8>)>,
+}
+
+impl Link {
+    pub fn bind(config: &NetplayConfig) -> io::Result<Self> {
+        let socket = UdpSocket::bind(config.local)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            peer: config.peer,
+            conditions: config.conditions,
+            rng: GameRng::new(config.seed ^ config.player as u64),
+            queue: Vec::new(),
+        })
+    }
+
+    pub fn send(&mut self, bytes: Vec<u8>) {
+        if self.rng.gen_range(0.0, 1.0) < self.conditions.loss {
+            return;
+        }
+
+        let jitter = self.conditions.jitter.as_secs_f32() * self.rng.gen_range(-1.0, 1.0);
+        let delay = (self.conditions.latency.as_secs_f32() + jitter).max(0.0);
+        self.queue
+            .push((Instant::now() + Duration::from_secs_f32(delay), bytes));
+        self.flush();
+    }
+
+    /// Sends the held back packets that are due.
+    pub fn flush(&mut self) {
+        let now = Instant::now();
+        let (due, waiting) = self.queue.drain(..).partition(|(time, _)| *time <= now);
+        self.queue = waiting;
+
+        for (_time, bytes) in due {
+            //a full send buffer is just one more lost packet
+            if let Err(error) = self.socket.send_to(&bytes, self.peer)
+                && error.kind() != io::ErrorKind::WouldBlock
+            {
+                warn!("cannot send to {}: {}", self.peer, error);
+            }
+        }
+    }
+
+    pub fn receive(&mut self) -> Vec<Vec<u8>> {
+        let mut packets = Vec::new();
+        let mut buffer = [0u8; MAX_PACKET_SIZE];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((length, from)) if from == self.peer => packets.push(buffer[..length].to_vec()),
+                Ok(_) => {}
+                //the peer's port is closed until it starts, that's fine
+                Err(error) if error.kind() == io::ErrorKind::ConnectionRefused => {}
+                Err(_) => break,
+            }
+        }
+        packets
+    }
+}
+
+/// One input per frame, from the first frame still needed on.
+#[derive(Debug, Clone, Default)]
+struct InputLog {
+    /// The frame of the first input kept.
+    first: u32,
+    inputs: VecDeque<bool>,
+}
+
+impl InputLog {
+    /// The frame after the last input.
+    fn end(&self) -> u32 {
+        self.first + self.inputs.len() as u32
+    }
+
+    fn get(&self, frame: u32) -> Option<bool> {
+        let index = frame.checked_sub(self.first)?;
+        self.inputs.get(index as usize).copied()
+    }
+
+    fn push(&mut self, input: bool) {
+        self.inputs.push_back(input);
+    }
+
+    /// The inputs of `frames`, as far as they are kept.
+    fn range(&self, frames: std::ops::Range<u32>) -> Vec<bool> {
+        frames.filter_map(|frame| self.get(frame)).collect()
+    }
+
+    /// Forgets the inputs from `frame` on.
+    fn truncate(&mut self, frame: u32) {
+        self.inputs
+            .truncate(frame.saturating_sub(self.first) as usize);
+    }
+
+    /// Forgets the inputs before `frame`.
+    fn forget_before(&mut self, frame: u32) {
+        let count = frame
+            .saturating_sub(self.first)
+            .min(self.inputs.len() as u32);
+        self.inputs.drain(..count as usize);
+        self.first += count;
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SessionStats {
+    pub rollbacks: u32,
+    pub resimulated_frames: u32,
+    pub stalled_ticks: u32,
+}
+
+/// A two player game over the network with rollback.
+///
+/// Both machines run the same deterministic simulation. Local flaps are
+/// applied at once; the remote bird is predicted not to flap until its
+/// inputs arrive. When they disagree with the prediction, the world is put
+/// back to the snapshot of that frame and the frames since are simulated
+/// again with the real inputs.
+pub struct RollbackSession {
+    link: Link,
+    local_player: usize,
+    seed: u64,
+    connected: bool,
+    last_hello: Option<Instant>,
+    accumulator: f32,
+    /// A local flap waiting for the next frame.
+    pending_flap: bool,
+    /// The next frame to simulate.
+    frame: u32,
+    /// The local inputs the peer may still miss or a rollback may replay.
+    local_inputs: InputLog,
+    /// Confirmed remote inputs; every frame before its end is final.
+    remote_inputs: InputLog,
+    /// The remote input each simulated frame actually used.
+    used_remote_inputs: InputLog,
+    /// How many of the local inputs the peer has.
+    remote_ack: u32,
+    /// Each frame that could still be rolled back: the world before it and
+    /// the events it produced.
+    snapshots: VecDeque<(u32, Snapshot, Vec<GameEvent>)>,
+    /// Checksums not taken yet.
+    checksums: Vec<(u32, u64)>,
+    pub stats: SessionStats,
+}
+
+impl RollbackSession {
+    pub fn new(config: &NetplayConfig) -> io::Result<Self> {
+        Ok(Self {
+            link: Link::bind(config)?,
+            local_player: config.player,
+            seed: config.seed,
+            connected: false,
+            last_hello: None,
+            accumulator: 0.0,
+            pending_flap: false,
+            frame: 0,
+            local_inputs: InputLog::default(),
+            remote_inputs: InputLog::default(),
+            used_remote_inputs: InputLog::default(),
+            remote_ack: 0,
+            snapshots: VecDeque::new(),
+            checksums: Vec::new(),
+            stats: SessionStats::default(),
+        })
+    }
+
+    /// The address the session listens on, to bind on port 0 and tell the
+    /// peer afterwards.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.link.socket.local_addr()
+    }
+
+    /// Talks to `peer` from now on, for a peer only known once this session
+    /// is bound.
+    pub fn set_peer(&mut self, peer: SocketAddr) {
+        self.link.peer = peer;
+    }
+
+    pub fn connected(&self) -> bool {
+        self.connected
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    /// The first frame whose remote input is not known yet.
+    pub fn confirmed_frame(&self) -> u32 {
+        self.remote_inputs.end()
+    }
+
+    /// Whether the peer has every local input up to `frame`.
+    pub fn peer_has_inputs_until(&self, frame: u32) -> bool {
+        self.remote_ack >= frame
+    }
+
+    /// Checksums of confirmed states, every [`CHECKSUM_INTERVAL`] frames,
+    /// since the last time they were taken.
+    pub fn take_checksums(&mut self) -> Vec<(u32, u64)> {
+        std::mem::take(&mut self.checksums)
+    }
+
+    /// Exchanges packets and runs the ticks that are due, up to `max_frame`.
+    /// The local bird's pending flap is sent along with the next tick.
+    pub fn update(
+        &mut self,
+        world: &mut World,
+        simulation: &mut Schedule,
+        frame_time: f32,
+        max_frame: u32,
+    ) {
+        //a rollback restores the inputs too, so the local flap is taken first
+        self.pending_flap |= take_flap(world, self.local_player);
+        self.link.flush();
+        self.receive(world, simulation);
+
+        if !self.connected {
+            let due = self
+                .last_hello
+                .is_none_or(|time| time.elapsed() >= HELLO_INTERVAL);
+            if due {
+                self.send_hello();
+            }
+            return;
+        }
+
+        self.accumulator = (self.accumulator + frame_time).min(TICK * 5.0);
+        while self.accumulator >= TICK && self.frame < max_frame {
+            if self.frame >= self.confirmed_frame() + MAX_ROLLBACK_FRAMES {
+                //too far ahead of the peer, give it time to catch up
+                self.stats.stalled_ticks += 1;
+                self.accumulator = 0.0;
+                break;
+            }
+            self.accumulator -= TICK;
+
+            let flap = std::mem::take(&mut self.pending_flap);
+            self.local_inputs.push(flap);
+            self.simulate(world, simulation);
+        }
+
+        self.send_inputs();
+    }
+
+    fn send_hello(&mut self) {
+        self.last_hello = Some(Instant::now());
+        self.link.send(
+            Packet::Hello {
+                player: self.local_player as u8,
+                seed: self.seed,
+            }
+            .encode(),
+        );
+    }
+
+    fn send_inputs(&mut self) {
+        let start = self.remote_ack;
+        let end = self
+            .local_inputs
+            .end()
+            .min(start + MAX_INPUTS_PER_PACKET as u32);
+        let packet = Packet::Inputs {
+            ack: self.confirmed_frame(),
+            start,
+            flaps: self.local_inputs.range(start..end),
+        };
+        self.link.send(packet.encode());
+    }
+
+    fn receive(&mut self, world: &mut World, simulation: &mut Schedule) {
+        let previously_confirmed = self.confirmed_frame();
+
+        for bytes in self.link.receive() {
+            match Packet::decode(&bytes) {
+                Some(Packet::Hello { player, seed }) => {
+                    if seed != self.seed || player as usize == self.local_player {
+                        warn!(
+                            "peer plays bird {} with seed {}, expected bird {} with seed {}",
+                            player,
+                            seed,
+                            1 - self.local_player,
+                            self.seed
+                        );
+                        continue;
+                    }
+                    //answer every hello, the peer may have missed ours
+                    self.send_hello();
+                    self.connect(world);
+                }
+                //only a hello starts the game, and the link only hears the peer
+                Some(Packet::Inputs { .. }) if !self.connected => {}
+                Some(Packet::Inputs { ack, start, flaps }) => {
+                    self.remote_ack = self.remote_ack.max(ack);
+                    for (frame, flap) in (start..).zip(flaps) {
+                        if frame == self.confirmed_frame() {
+                            self.remote_inputs.push(flap);
+                        }
+                    }
+                }
+                None => warn!("ignoring a malformed packet"),
+            }
+        }
+
+        //roll back to the first frame that was predicted wrong
+        let checked = previously_confirmed..self.confirmed_frame().min(self.frame);
+        let mispredicted = checked
+            .into_iter()
+            .find(|frame| self.remote_inputs.get(*frame) != self.used_remote_inputs.get(*frame));
+        if let Some(frame) = mispredicted {
+            self.rollback(world, simulation, frame);
+        }
+
+        //snapshots before the confirmed frame can't be needed again
+        while let Some((frame, snapshot, _events)) = self.snapshots.front() {
+            if *frame >= self.confirmed_frame() {
+                break;
+            }
+            if frame % CHECKSUM_INTERVAL == 0 {
+                self.checksums.push((*frame, snapshot.checksum()));
+            }
+            self.snapshots.pop_front();
+        }
+
+        //so are the inputs, but for those the peer may still miss
+        let confirmed = self.confirmed_frame();
+        self.remote_inputs.forget_before(confirmed);
+        self.used_remote_inputs.forget_before(confirmed);
+        self.local_inputs
+            .forget_before(confirmed.min(self.remote_ack));
+    }
+
+    fn connect(&mut self, world: &mut World) {
+        if self.connected {
+            return;
+        }
+        self.connected = true;
+        world.resource_mut::<GameState>().game_state = GameStates::Play;
+        info!("connected, playing bird {}", self.local_player + 1);
+    }
+
+    fn rollback(&mut self, world: &mut World, simulation: &mut Schedule, frame: u32) {
+        let Some(index) = self
+            .snapshots
+            .iter()
+            .position(|(saved, ..)| *saved == frame)
+        else {
+            warn!("no snapshot for frame {}, cannot roll back", frame);
+            return;
+        };
+        self.snapshots[index].1.restore(world);
+
+        //the events of these frames were played, except those still waiting
+        let mut played: Vec<GameEvent> = self
+            .snapshots
+            .drain(index..)
+            .flat_map(|(_frame, _snapshot, events)| events)
+            .collect();
+        let mut waiting = std::mem::take(&mut world.resource_mut::<GameEvents>().0);
+        //those of earlier frames stay, the others are simulated again
+        waiting.retain(|event| !take_event(&mut played, event));
+
+        let current = self.frame;
+        self.frame = frame;
+        while self.frame < current {
+            self.simulate(world, simulation);
+        }
+
+        //what the corrected frames brought anew is still to be played
+        let mut events = world.resource_mut::<GameEvents>();
+        let resimulated = std::mem::take(&mut events.0);
+        events.0 = waiting;
+        events.0.extend(
+            resimulated
+                .into_iter()
+                .filter(|event| !take_event(&mut played, event)),
+        );
+
+        self.stats.rollbacks += 1;
+        self.stats.resimulated_frames += current - frame;
+    }
+
+    /// Simulates `self.frame` with the best inputs known for it.
+    fn simulate(&mut self, world: &mut World, simulation: &mut Schedule) {
+        let frame = self.frame;
+        let snapshot = Snapshot::take(world);
+        let events_seen = world.resource::<GameEvents>().0.len();
+
+        let remote = self.remote_inputs.get(frame).unwrap_or(false);
+        self.used_remote_inputs.truncate(frame);
+        self.used_remote_inputs.push(remote);
+
+        let local = self
+            .local_inputs
+            .get(frame)
+            .expect("local inputs are kept until they are confirmed");
+        let mut query = world.query::<(&Player, &mut PlayerInput)>();
+        for (player, mut input) in query.iter_mut(world) {
+            input.flap = if player.index == self.local_player {
+                local
+            } else {
+                remote
+            };
+        }
+
+        simulation.run(world);
+        let events = world.resource::<GameEvents>().0[events_seen..].to_vec();
+        self.snapshots.push_back((self.frame, snapshot, events));
+        self.frame += 1;
+    }
+}
+
+/// Removes one `event` from `events`, returning whether there was one.
+fn take_event(events: &mut Vec<GameEvent>, event: &GameEvent) -> bool {
+    match events.iter().position(|played| played == event) {
+        Some(index) => {
+            events.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Takes the pending flap of one bird, leaving it to the session to apply.
+fn take_flap(world: &mut World, index: usize) -> bool {
+    let mut query = world.query::<(&Player, &mut PlayerInput)>();
+    let mut flap = false;
+    for (player, mut input) in query.iter_mut(world) {
+        if player.index == index {
+            flap = std::mem::take(&mut input.flap);
+        }
+    }
+    flap
+}
@@ -0,0 +1,433 @@
+use std::fmt;
+
+use bevy_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::args::Args;
+use crate::bot::next_gap;
+use crate::error::{Error, Result};
+use crate::game::{GameState, GameStates, SCREEN_SIZE};
+use crate::pipe::{
+    PIPE_WIDTH, PIPES_H_SEPARATION, PIPES_V_SEPARATION, Pipe, PipeSpawn, reset_pipes,
+};
+use crate::player::{
+    Controller, JUMP_SPEED, PLAYER_SPRITE_SIZES, Player, PlayerBundle, add_gravity, collide_player,
+    start_position,
+};
+use crate::simulation::{GameRng, PlayerInput, TICKS_PER_SECOND, Tick, simulation_schedule};
+use crate::transform::{Position, Velocity};
+
+/// What a network sees: the bird's height and speed, and where the next gap
+/// is compared to the bird.
+pub const INPUTS: usize = 4;
+pub const HIDDEN: usize = 6;
+/// Weights of the network, biases included.
+pub const WEIGHTS: usize = (INPUTS + 1) * HIDDEN + HIDDEN + 1;
+/// Fitness for every pipe passed, on top of one per tick flown.
+const PIPE_BONUS: f32 = 50.0;
+/// Fitness lost per pixel between the bird and the middle of the gap it
+/// crashed into, so early generations learn to aim for the gaps.
+const GAP_ERROR_PENALTY: f32 = 0.25;
+
+/// The weights of a small neural network that decides when a bird flaps:
+/// one hidden layer with tanh, one output that flaps when positive.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Genome {
+    pub inputs: usize,
+    pub hidden: usize,
+    pub weights: Vec<f32>,
+}
+
+impl Genome {
+    pub fn random(rng: &mut GameRng) -> Self {
+        Self {
+            inputs: INPUTS,
+            hidden: HIDDEN,
+            weights: (0..WEIGHTS).map(|_| rng.gen_range(-1.0, 1.0)).collect(),
+        }
+    }
+
+    pub fn decide(&self, senses: [f32; INPUTS]) -> bool {
+        let (hidden_weights, output_weights) = self.weights.split_at((INPUTS + 1) * HIDDEN);
+
+        let mut output = output_weights[HIDDEN];
+        for (neuron, weights) in hidden_weights.chunks(INPUTS + 1).enumerate() {
+            let sum: f32 = weights[INPUTS]
+                + senses
+                    .iter()
+                    .zip(weights)
+                    .map(|(sense, weight)| sense * weight)
+                    .sum::<f32>();
+            output += sum.tanh() * output_weights[neuron];
+        }
+        output > 0.0
+    }
+
+    /// A child taking every weight from either parent at random.
+    pub fn crossover(&self, other: &Genome, rng: &mut GameRng) -> Genome {
+        Genome {
+            weights: self
+                .weights
+                .iter()
+                .zip(&other.weights)
+                .map(|(a, b)| if rng.next_u32() & 1 == 0 { *a } else { *b })
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Nudges each weight with probability `rate` by up to `strength`.
+    pub fn mutate(&mut self, rate: f32, strength: f32, rng: &mut GameRng) {
+        for weight in &mut self.weights {
+            if rng.gen_range(0.0, 1.0) < rate {
+                *weight += rng.gen_range(-strength, strength);
+            }
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Genome> {
+        let json = std::fs::read_to_string(path).map_err(|error| Error::asset(path, error))?;
+        let genome: Genome =
+            serde_json::from_str(&json).map_err(|error| Error::asset(path, error))?;
+        if genome.inputs != INPUTS || genome.hidden != HIDDEN || genome.weights.len() != WEIGHTS {
+            return Err(Error::asset(
+                path,
+                format!(
+                    "expected a network with {INPUTS} inputs, {HIDDEN} hidden neurons and {WEIGHTS} weights"
+                ),
+            ));
+        }
+        Ok(genome)
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(|error| Error::write(path, error))?;
+        std::fs::write(path, json).map_err(|error| Error::write(path, error))
+    }
+}
+
+/// A neural network flying a bird whose [`Controller`] is `Controller::Brain`.
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Brain(pub Genome);
+
+/// How well a bird did so far.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Fitness {
+    pub ticks: u32,
+    /// Distance from the bird to the middle of the next gap, last time it
+    /// was alive.
+    pub gap_error: f32,
+}
+
+impl Fitness {
+    pub fn value(&self, score: u32) -> f32 {
+        self.ticks as f32 + score as f32 * PIPE_BONUS - self.gap_error * GAP_ERROR_PENALTY
+    }
+}
+
+fn senses(position: &Position, velocity: &Velocity, gap: Option<(Position, f32)>) -> [f32; INPUTS] {
+    let center = position.y + PLAYER_SPRITE_SIZES.y / 2.0;
+    let (gap_middle, gap_height, distance) = match gap {
+        Some((pipe, gap_height)) => (
+            pipe.y + gap_height / 2.0,
+            gap_height,
+            pipe.x + PIPE_WIDTH - position.x,
+        ),
+        None => (-SCREEN_SIZE / 2.0, PIPES_V_SEPARATION, PIPES_H_SEPARATION),
+    };
+    [
+        center / SCREEN_SIZE,
+        velocity.y / JUMP_SPEED,
+        (gap_middle - center) / gap_height,
+        distance / PIPES_H_SEPARATION,
+    ]
+}
+
+/// Lets every brain decide whether its bird flaps this tick.
+pub fn think(
+    game_state: Res<GameState>,
+    pipe_query: Query<(&Position, &Pipe)>,
+    mut brain_query: Query<(&Player, &Position, &Velocity, &Brain, &mut PlayerInput)>,
+) {
+    if game_state.game_state != GameStates::Play {
+        return;
+    }
+
+    let pipes: Vec<(&Position, f32)> = pipe_query
+        .iter()
+        .map(|(pipe, Pipe { gap_height, .. })| (pipe, *gap_height))
+        .collect();
+    for (player, position, velocity, brain, mut input) in &mut brain_query {
+        if player.alive {
+            let gap = next_gap(pipes.iter().copied(), position.x);
+            input.flap = brain.0.decide(senses(position, velocity, gap));
+        }
+    }
+}
+
+pub fn track_fitness(
+    game_state: Res<GameState>,
+    pipe_query: Query<(&Position, &Pipe)>,
+    mut query: Query<(&Player, &Position, &mut Fitness)>,
+) {
+    if game_state.game_state != GameStates::Play {
+        return;
+    }
+
+    for (player, position, mut fitness) in &mut query {
+        if player.alive {
+            fitness.ticks += 1;
+            let center = position.y + PLAYER_SPRITE_SIZES.y / 2.0;
+            let pipes = pipe_query
+                .iter()
+                .map(|(pipe, Pipe { gap_height, .. })| (pipe, *gap_height));
+            fitness.gap_error = next_gap(pipes, position.x).map_or(0.0, |(pipe, gap_height)| {
+                (pipe.y + gap_height / 2.0 - center).abs()
+            });
+        }
+    }
+}
+
+/// The simulation with brains flying the birds and their fitness tracked.
+pub fn training_schedule() -> Schedule {
+    let mut schedule = simulation_schedule();
+    schedule.add_systems((
+        think.before(add_gravity),
+        track_fitness.after(collide_player),
+    ));
+    schedule
+}
+
+/// How generations are bred. Set with `--population N`, `--elite N`,
+/// `--tournament N`, `--crossover RATE`, `--mutation-rate RATE`,
+/// `--mutation-strength N`, `--seed N` and `--max-seconds N`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EvolutionConfig {
+    pub population: usize,
+    /// Best genomes copied unchanged into the next generation.
+    pub elite: usize,
+    /// Genomes compared to pick each parent.
+    pub tournament: usize,
+    /// Share of children bred from two parents instead of one.
+    pub crossover_rate: f32,
+    /// Share of weights changed in every child.
+    pub mutation_rate: f32,
+    /// Largest change of a mutated weight.
+    pub mutation_strength: f32,
+    /// Seed of the pipe layout every generation flies, and of the breeding.
+    pub seed: u64,
+    /// Ticks after which a generation ends even if birds are still flying.
+    pub max_ticks: u32,
+}
+
+impl Default for EvolutionConfig {
+    fn default() -> Self {
+        Self {
+            population: 200,
+            elite: 10,
+            tournament: 4,
+            crossover_rate: 0.7,
+            mutation_rate: 0.1,
+            mutation_strength: 0.5,
+            seed: 0,
+            max_ticks: 5 * 60 * TICKS_PER_SECOND,
+        }
+    }
+}
+
+impl EvolutionConfig {
+    pub fn from_args(args: &Args) -> std::result::Result<Self, String> {
+        let default = Self::default();
+        let max_seconds = args.parse("--max-seconds", default.max_ticks / TICKS_PER_SECOND)?;
+        let config = Self {
+            population: args.parse("--population", default.population)?,
+            elite: args.parse("--elite", default.elite)?,
+            tournament: args.parse("--tournament", default.tournament)?,
+            crossover_rate: args.parse("--crossover", default.crossover_rate)?,
+            mutation_rate: args.parse("--mutation-rate", default.mutation_rate)?,
+            mutation_strength: args.parse("--mutation-strength", default.mutation_strength)?,
+            seed: args.parse("--seed", default.seed)?,
+            max_ticks: max_seconds * TICKS_PER_SECOND,
+        };
+        if config.population == 0 || config.elite > config.population {
+            return Err("--elite must not be larger than --population".to_string());
+        }
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GenerationStats {
+    pub generation: u32,
+    pub best_fitness: f32,
+    pub mean_fitness: f32,
+    pub best_score: u32,
+    pub ticks: u32,
+}
+
+impl fmt::Display for GenerationStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "generation {:4}  best {:8.1}  mean {:8.1}  best score {:4}  ticks {:6}",
+            self.generation, self.best_fitness, self.mean_fitness, self.best_score, self.ticks
+        )
+    }
+}
+
+/// Breeds a population of brains, one generation per round.
+#[derive(Resource, Debug)]
+pub struct Trainer {
+    pub config: EvolutionConfig,
+    rng: GameRng,
+    generation: u32,
+    genomes: Vec<Genome>,
+    champion: Option<(Genome, f32)>,
+}
+
+impl Trainer {
+    /// A first generation grown from `ancestors`, or random if there are none.
+    pub fn new(config: EvolutionConfig, ancestors: Vec<Genome>) -> Self {
+        let mut rng = GameRng::new(config.seed);
+        let genomes = (0..config.population)
+            .map(|index| match ancestors.get(index) {
+                Some(ancestor) => ancestor.clone(),
+                None if ancestors.is_empty() => Genome::random(&mut rng),
+                None => {
+                    let mut child = ancestors[index % ancestors.len()].clone();
+                    child.mutate(config.mutation_rate, config.mutation_strength, &mut rng);
+                    child
+                }
+            })
+            .collect();
+
+        Self {
+            config,
+            rng,
+            generation: 0,
+            genomes,
+            champion: None,
+        }
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// The fittest genome of every generation so far.
+    pub fn champion(&self) -> Option<&Genome> {
+        self.champion.as_ref().map(|(genome, _fitness)| genome)
+    }
+
+    /// Replaces the birds with the current generation and starts the round
+    /// on the configured layout.
+    pub fn start(&self, world: &mut World) {
+        let mut birds = world.query_filtered::<Entity, With<Player>>();
+        for bird in birds.iter(world).collect::<Vec<_>>() {
+            world.despawn(bird);
+        }
+
+        let mut rng = GameRng::new(self.config.seed);
+        let spawn = *world.resource::<PipeSpawn>();
+        let mut pipes = world.query::<(&mut Position, &mut Pipe)>();
+        reset_pipes(pipes.iter_mut(world), &spawn, &mut rng);
+        world.insert_resource(rng);
+        world.insert_resource(Tick(0));
+
+        //every bird starts at the same spot, the flock spreads out as it flies
+        for (index, genome) in self.genomes.iter().enumerate() {
+            world.spawn((
+                PlayerBundle {
+                    position: start_position(0, 1),
+                    controller: Controller::Brain,
+                    ..PlayerBundle::new(index, 1)
+                },
+                Brain(genome.clone()),
+                Fitness::default(),
+            ));
+        }
+
+        let mut game_state = world.resource_mut::<GameState>();
+        game_state.score = 0;
+        game_state.winner = None;
+        game_state.game_state = GameStates::Play;
+    }
+
+    /// Ends the generation once every bird crashed or time is up, breeds
+    /// the next one and starts it.
+    pub fn update(&mut self, world: &mut World) -> Option<GenerationStats> {
+        let ticks = world.resource::<Tick>().0;
+        let over = world.resource::<GameState>().game_state == GameStates::GameOver;
+        if !over && ticks < self.config.max_ticks {
+            return None;
+        }
+
+        let stats = self.breed(world);
+        self.start(world);
+        Some(stats)
+    }
+
+    fn breed(&mut self, world: &mut World) -> GenerationStats {
+        let mut query = world.query::<(&Player, &Brain, &Fitness)>();
+        let mut ranked: Vec<(f32, u32, Genome)> = query
+            .iter(world)
+            .map(|(player, brain, fitness)| {
+                (fitness.value(player.score), player.score, brain.0.clone())
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let stats = GenerationStats {
+            generation: self.generation,
+            best_fitness: ranked.first().map_or(0.0, |(fitness, ..)| *fitness),
+            mean_fitness: ranked.iter().map(|(fitness, ..)| fitness).sum::<f32>()
+                / ranked.len().max(1) as f32,
+            best_score: ranked.iter().map(|(_, score, _)| *score).max().unwrap_or(0),
+            ticks: world.resource::<Tick>().0,
+        };
+        if let Some((fitness, _, genome)) = ranked.first()
+            && self
+                .champion
+                .as_ref()
+                .is_none_or(|(_, best)| fitness > best)
+        {
+            self.champion = Some((genome.clone(), *fitness));
+        }
+
+        let config = self.config;
+        let mut next: Vec<Genome> = ranked
+            .iter()
+            .take(config.elite)
+            .map(|(.., genome)| genome.clone())
+            .collect();
+        while next.len() < config.population && !ranked.is_empty() {
+            let parent = self.tournament(&ranked);
+            let mut child = if self.rng.gen_range(0.0, 1.0) < config.crossover_rate {
+                let other = self.tournament(&ranked);
+                parent.crossover(other, &mut self.rng)
+            } else {
+                parent.clone()
+            };
+            child.mutate(
+                config.mutation_rate,
+                config.mutation_strength,
+                &mut self.rng,
+            );
+            next.push(child);
+        }
+
+        self.genomes = next;
+        self.generation += 1;
+        stats
+    }
+
+    /// The fittest of a few genomes picked at random.
+    fn tournament<'a>(&mut self, ranked: &'a [(f32, u32, Genome)]) -> &'a Genome {
+        //ranked is sorted, so the lowest index drawn is the fittest
+        let best = (0..self.config.tournament.max(1))
+            .map(|_| self.rng.gen_index(ranked.len()))
+            .min()
+            .unwrap_or(0);
+        &ranked[best].2
+    }
+}
@@ -1,77 +1,166 @@
 use bevy_ecs::prelude::*;
-use macroquad::{prelude::clamp, time::get_frame_time};
+use macroquad::prelude::clamp;
+use serde::{Deserialize, Serialize};
 
+use crate::asset_loader::PIPE_TEXTURE;
+use crate::game::{GameState, GameStates, SCREEN_SIZE};
+use crate::level::{GapBounds, Level, spawn_level};
+use crate::simulation::{GameEvent, GameEvents, GameRng, TICK};
+use crate::sprite::{CompositeSprite, LAYER_PIPES, Sprite};
 use crate::transform::{Position, Velocity};
-use crate::{
-    asset_loader::AssetLoader,
-    game::{GameState, GameStates, SCREEN_SIZE},
-};
 
-#[derive(Component)]
+/// A pair of pipes around a gap; [`Position`] is the top left of the gap.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Pipe {
     pub can_score: bool,
+    /// Height of the gap, [`PIPES_V_SEPARATION`] except in levels.
+    pub gap_height: f32,
 }
 
+/// Pipes in a round without a level, reused as they scroll off.
 pub const OBSTACLE_AMOUNT: u32 = 5;
+/// Height of the gap between a top and a bottom pipe.
 pub const PIPES_V_SEPARATION: f32 = 110.0;
+/// Distance from one pipe to the next.
 pub const PIPES_H_SEPARATION: f32 = 150.0;
+/// Width of a pipe.
 pub const PIPE_WIDTH: f32 = 52.0;
+/// Least room between a gap and the floor.
 pub const PIPE_FLOOR_PADDING: f32 = 65.0;
+/// Least room between a gap and the top of the screen.
 pub const PIPE_CEILING_PADDING: f32 = 20.0;
+/// Units per second the pipes scroll left by.
+pub const PIPE_SPEED: f32 = 120.0;
+
+/// Where the pipes are laid out from: the right edge of the widest view the
+/// game is shown in, so no pipe is on screen before a round starts. Pipes that
+/// come back on the right always land past it.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct PipeSpawn {
+    pub x: f32,
+}
+
+impl Default for PipeSpawn {
+    fn default() -> Self {
+        Self { x: SCREEN_SIZE }
+    }
+}
 
 #[derive(Bundle)]
 pub struct PipeBundle {
-    position: Position,
-    velocity: Velocity,
-    is_type: Pipe,
+    pub position: Position,
+    pub velocity: Velocity,
+    pub sprite: CompositeSprite,
+    pub is_type: Pipe,
 }
 
 impl PipeBundle {
     pub fn new(x: f32, y: f32) -> Self {
+        Self::with_gap(x, y, PIPES_V_SEPARATION)
+    }
+
+    pub fn with_gap(x: f32, y: f32, gap_height: f32) -> Self {
         PipeBundle {
             position: Position { x, y },
-            velocity: Velocity { x: -120.0, y: 0.0 },
-            is_type: Pipe { can_score: true },
+            velocity: Velocity {
+                x: -PIPE_SPEED,
+                y: 0.0,
+            },
+            sprite: CompositeSprite(vec![
+                Sprite::new(PIPE_TEXTURE, LAYER_PIPES)
+                    .with_anchor(0.0, 1.0)
+                    .with_flip(false, true),
+                Sprite::new(PIPE_TEXTURE, LAYER_PIPES).with_offset(0.0, gap_height),
+            ]),
+            is_type: Pipe {
+                can_score: true,
+                gap_height,
+            },
         }
     }
 }
 
+/// A random height for the top of a gap, keeping it clear of the ceiling and
+/// the floor.
+pub fn random_gap_y(rng: &mut GameRng) -> f32 {
+    let random = rng.gen_range(0.0, 1.0);
+    clamp(
+        random * (-SCREEN_SIZE),
+        -SCREEN_SIZE + PIPE_CEILING_PADDING,
+        -PIPES_V_SEPARATION - PIPE_FLOOR_PADDING,
+    )
+}
+
+/// Scrolls the pipes. Random pipes come back on the right with a new gap
+/// once they left the screen, the pipes of a level only pass by once.
 pub fn move_pipe(
     game_state: Res<GameState>,
-    mut query: Query<(&mut Position, &Velocity, &mut Pipe)>,
-    assets: Res<AssetLoader>,
+    level: Option<Res<Level>>,
+    mut rng: ResMut<GameRng>,
+    mut events: ResMut<GameEvents>,
+    mut query: Query<(
+        Entity,
+        &mut Position,
+        &mut Velocity,
+        &mut Pipe,
+        Option<&GapBounds>,
+    )>,
 ) {
-    let pipe_texture = assets.get_texture("pipe");
     if game_state.game_state == GameStates::Play {
-        for (mut position, velocity, mut pipe) in &mut query {
-            position.x += velocity.x * get_frame_time();
-            position.y += velocity.y * get_frame_time();
+        for (entity, mut position, mut velocity, mut pipe, bounds) in &mut query {
+            position.x += velocity.x * TICK;
+            position.y += velocity.y * TICK;
 
-            if position.x < -pipe_texture.width() {
-                let random: f32 = macroquad::rand::gen_range(0.0, 1.0);
+            if let Some(bounds) = bounds {
+                if position.y > bounds.bottom {
+                    position.y = bounds.bottom;
+                    velocity.y = -velocity.y.abs();
+                } else if position.y < bounds.top {
+                    position.y = bounds.top;
+                    velocity.y = velocity.y.abs();
+                }
+            }
+
+            if level.is_none() && position.x < -PIPE_WIDTH {
                 position.x += (OBSTACLE_AMOUNT as f32) * PIPES_H_SEPARATION;
-                position.y = clamp(
-                    random * (-SCREEN_SIZE),
-                    -SCREEN_SIZE + PIPE_CEILING_PADDING,
-                    -PIPES_V_SEPARATION - PIPE_FLOOR_PADDING,
-                );
+                position.y = random_gap_y(&mut rng);
                 pipe.can_score = true;
+                events.0.push(GameEvent::PipeSpawned { pipe: entity });
             }
         }
     }
 }
 
-pub fn spawn_pipes(mut commands: Commands) {
-    for i in 0..OBSTACLE_AMOUNT {
-        let random: f32 = macroquad::rand::gen_range(0.0, 1.0);
+/// Puts the pipes back where [`spawn_pipes`] placed them, with new gaps.
+pub fn reset_pipes<'a>(
+    pipes: impl Iterator<Item = (Mut<'a, Position>, Mut<'a, Pipe>)>,
+    spawn: &PipeSpawn,
+    rng: &mut GameRng,
+) {
+    for (index, (mut position, mut pipe)) in pipes.enumerate() {
+        position.x = spawn.x + index as f32 * PIPES_H_SEPARATION;
+        position.y = random_gap_y(rng);
+        pipe.can_score = true;
+    }
+}
 
+/// Spawns the pipes of the level, or [`OBSTACLE_AMOUNT`] random ones from
+/// [`PipeSpawn`].
+pub fn spawn_pipes(
+    mut commands: Commands,
+    spawn: Res<PipeSpawn>,
+    mut rng: ResMut<GameRng>,
+    level: Option<Res<Level>>,
+) {
+    if let Some(level) = level {
+        spawn_level(&mut commands, &level, 0.0);
+        return;
+    }
+
+    for i in 0..OBSTACLE_AMOUNT {
         commands.spawn(PipeBundle::new(
-            SCREEN_SIZE + (i as f32) * PIPES_H_SEPARATION,
-            clamp(
-                random * (-SCREEN_SIZE),
-                -SCREEN_SIZE + PIPE_CEILING_PADDING,
-                -PIPES_V_SEPARATION - PIPE_FLOOR_PADDING,
-            ),
+            spawn.x + (i as f32) * PIPES_H_SEPARATION,
+            random_gap_y(&mut rng),
         ));
     }
 }
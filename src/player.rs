@@ -1,120 +1,368 @@
 use bevy_ecs::prelude::*;
 use macroquad::audio;
 use macroquad::prelude::*;
+use std::f32::consts::PI;
 
 use crate::{
-    asset_loader::AssetLoader,
-    game::{GameState, GameStates, SCREEN_SIZE},
-    pipe::{Pipe, PIPES_V_SEPARATION, PIPE_WIDTH},
+    animation::Animator,
+    asset_loader::{
+        AssetLoader, BIRD_BLUE_CLIP, BIRD_RED_CLIP, BIRD_YELLOW_CLIP, ClipHandle, FLAP_SOUND,
+        HIT_SOUND, SCORE_SOUND, SPRITE_SHEET_TEXTURE,
+    },
+    game::{GameState, GameStates, MatchSettings, SCREEN_SIZE},
+    pipe::{PIPE_WIDTH, Pipe},
+    simulation::{CrashCause, GameEvent, GameEvents, Physics, PlayerInput, TICK},
+    sprite::{LAYER_PLAYER, Sprite},
     transform::{Position, Velocity},
 };
+/// Upward speed of a bird right after it flaps.
 pub const JUMP_SPEED: f32 = 450.0;
+/// Units per second squared the birds fall by.
 pub const GRAVITY: f32 = 1900.0;
+/// Size of a bird sprite.
 pub const PLAYER_SPRITE_SIZES: Vec2 = Vec2 { x: 36.0, y: 36.0 };
+/// Size of the box around a bird that crashes into pipes.
 pub const PLAYER_COLLISION: Vec2 = Vec2 { x: 20.0, y: 20.0 };
+/// Where the birds fly, horizontally; the pipes come to them.
+pub const PLAYER_START_X: f32 = 50.0;
+/// Vertical distance between the birds on the start screen.
+pub const PLAYER_SPACING: f32 = 24.0;
+/// Where the bottom of a bird's collision box meets the floor.
+pub const FLOOR_COLLISION_Y: f32 = -36.0;
 
-#[derive(Component)]
-pub struct Player;
+/// Birds that can play on one keyboard.
+pub const MAX_PLAYERS: usize = 4;
+/// The flap key of each player.
+pub const FLAP_KEYS: [KeyCode; MAX_PLAYERS] = [KeyCode::Space, KeyCode::W, KeyCode::Up, KeyCode::L];
+/// The names of [`FLAP_KEYS`] on the start screen.
+pub const FLAP_KEY_NAMES: [&str; MAX_PLAYERS] = ["SPACE", "W", "UP", "L"];
+/// The animation of each player's bird.
+const PLAYER_CLIPS: [ClipHandle; MAX_PLAYERS] = [
+    BIRD_YELLOW_CLIP,
+    BIRD_BLUE_CLIP,
+    BIRD_RED_CLIP,
+    BIRD_BLUE_CLIP,
+];
+//there are only three birds in the sprite sheet, the fourth is a green tinted blue one
+const PLAYER_TINTS: [Color; MAX_PLAYERS] = [WHITE, WHITE, WHITE, GREEN];
+/// The colour of each player's name and score in the UI.
+pub const PLAYER_COLORS: [Color; MAX_PLAYERS] = [GOLD, BLUE, RED, GREEN];
+/// The tint of a crashed bird in a round with several.
+const CRASHED_TINT: Color = GRAY;
+
+/// One bird. `index` decides its flap key, colour and starting place.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Player {
+    pub index: usize,
+    pub flap_key: KeyCode,
+    pub score: u32,
+    pub alive: bool,
+}
+
+impl Player {
+    pub fn new(index: usize) -> Self {
+        Self {
+            index,
+            flap_key: FLAP_KEYS[index % MAX_PLAYERS],
+            score: 0,
+            alive: true,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        format!("P{}", self.index + 1)
+    }
+
+    pub fn clip(&self) -> ClipHandle {
+        PLAYER_CLIPS[self.index % MAX_PLAYERS]
+    }
+
+    pub fn tint(&self) -> Color {
+        PLAYER_TINTS[self.index % MAX_PLAYERS]
+    }
+}
+
+/// Who decides when a bird flaps, by filling in its [`PlayerInput`].
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub enum Controller {
+    /// The player's flap key on this machine.
+    #[default]
+    Keyboard,
+    /// A player on another machine, see `net::RollbackSession`.
+    Remote,
+    /// The autopilot, see [`Bot`](crate::bot::Bot).
+    Bot,
+    /// A neural network in training, see [`Brain`](crate::neuro::Brain).
+    Brain,
+}
+
+/// Where bird `index` of `players` waits for the round to start.
+pub fn start_position(index: usize, players: usize) -> Position {
+    let middle = (players.max(1) - 1) as f32 / 2.0;
+    Position {
+        x: PLAYER_START_X,
+        y: -SCREEN_SIZE / 2.0 + (index as f32 - middle) * PLAYER_SPACING,
+    }
+}
 
 #[derive(Bundle)]
 pub struct PlayerBundle {
     pub position: Position,
     pub velocity: Velocity,
+    pub sprite: Sprite,
+    pub animator: Animator,
+    pub input: PlayerInput,
+    pub controller: Controller,
     pub player: Player,
 }
 
 impl PlayerBundle {
-    pub fn new() -> Self {
+    pub fn new(index: usize, players: usize) -> Self {
+        let player = Player::new(index);
         Self {
-            position: Position {
-                x: 50.0,
-                y: -SCREEN_SIZE / 2.0,
-            },
+            position: start_position(index, players),
             velocity: Velocity { x: 0.0, y: 0.0 },
-            player: Player {},
+            sprite: Sprite::new(SPRITE_SHEET_TEXTURE, LAYER_PLAYER).with_tint(player.tint()),
+            animator: Animator::new(player.clip()),
+            input: PlayerInput::default(),
+            controller: Controller::Keyboard,
+            player,
+        }
+    }
+}
+
+impl Default for PlayerBundle {
+    fn default() -> Self {
+        Self::new(0, 1)
+    }
+}
+
+/// Turns key presses into flaps for the birds played on this keyboard.
+pub fn read_keyboard_input(mut query: Query<(&Player, &Controller, &mut PlayerInput)>) {
+    for (player, controller, mut input) in &mut query {
+        if *controller == Controller::Keyboard && is_key_pressed(player.flap_key) {
+            input.flap = true;
         }
     }
 }
 
+/// Moves the birds while the round is played and flaps those that were asked
+/// to.
 pub fn move_player(
-    game_state: ResMut<GameState>,
-    mut assets: ResMut<AssetLoader>,
-    mut query: Query<(&mut Position, &mut Velocity), With<Player>>,
+    game_state: Res<GameState>,
+    physics: Res<Physics>,
+    mut events: ResMut<GameEvents>,
+    mut query: Query<(
+        &mut Position,
+        &mut Velocity,
+        &mut Animator,
+        &mut PlayerInput,
+        &Player,
+    )>,
 ) {
-    let flap_sound = assets.get_sound("flap_sound");
+    for (mut position, mut velocity, mut animator, mut input, player) in &mut query {
+        //a bird keeps flapping until it hits something
+        animator.playing = player.alive
+            && matches!(
+                game_state.game_state,
+                GameStates::PressStart | GameStates::Play
+            );
 
-    match game_state.game_state {
-        GameStates::PressStart => {
-            assets.update_animation("player_animations");
-        }
-        GameStates::Play => {
-            for (mut position, mut velocity) in &mut query {
-                position.y += velocity.y * get_frame_time();
+        if game_state.game_state == GameStates::Play {
+            position.x += velocity.x * TICK;
+            position.y += velocity.y * TICK;
 
-                if is_key_pressed(KeyCode::Space) {
-                    velocity.y = -JUMP_SPEED;
-                    audio::play_sound_once(flap_sound);
-                }
+            //crashed birds drop to the floor and scroll away with the pipes
+            position.y = position
+                .y
+                .min(FLOOR_COLLISION_Y - PLAYER_SPRITE_SIZES.y / 2.0);
+
+            if player.alive && input.flap {
+                velocity.y = -physics.jump_speed;
+                events.0.push(GameEvent::Flap {
+                    player: player.index,
+                    y: position.y,
+                });
             }
-            assets.update_animation("player_animations");
         }
-        _ => {}
+        input.flap = false;
     }
 }
 
-pub fn add_gravity(game_state: Res<GameState>, player_query: Single<&mut Velocity, With<Player>>) {
+/// Pulls the birds down while the round is played.
+pub fn add_gravity(
+    game_state: Res<GameState>,
+    physics: Res<Physics>,
+    mut player_query: Query<&mut Velocity, With<Player>>,
+) {
     if game_state.game_state == GameStates::Play {
-        let mut velocity = player_query.into_inner();
-        velocity.y += GRAVITY * get_frame_time();
+        for mut velocity in &mut player_query {
+            velocity.y += physics.gravity * TICK;
+        }
     }
 }
 
+/// Crashes the birds into the floor and the pipes, scores the pipes they
+/// passed and ends the round once the win rule says so.
 pub fn collide_player(
     mut game_state: ResMut<GameState>,
-    player_query: Single<&Position, With<Player>>,
+    settings: Res<MatchSettings>,
+    physics: Res<Physics>,
+    mut events: ResMut<GameEvents>,
+    mut player_query: Query<(&Position, &mut Velocity, &mut Player)>,
     mut pipe_query: Query<(Entity, &Position, &mut Pipe)>,
-    assets: Res<AssetLoader>,
 ) {
-    let hit_sound = assets.get_sound("hit_sound");
-    let score_sound = assets.get_sound("score_sound");
+    if game_state.game_state != GameStates::Play {
+        return;
+    }
 
-    if game_state.game_state == GameStates::Play {
-        let player_position = player_query.into_inner();
+    //the floor is there with or without pipes
+    for (player_position, mut velocity, mut player) in &mut player_query {
+        let center_y = player_position.y + PLAYER_SPRITE_SIZES.y / 2.0;
+        if player.alive && center_y + PLAYER_COLLISION.y / 2.0 > FLOOR_COLLISION_Y {
+            player.alive = false;
+            velocity.x = -physics.pipe_speed;
+            events.0.push(GameEvent::Crash {
+                player: player.index,
+                x: player_position.x,
+                y: player_position.y,
+                cause: CrashCause::Floor,
+            });
+        }
+    }
+
+    for (_entity, pipe_position, mut pipe) in &mut pipe_query {
+        let (x_min, x_max): (f32, f32) = (pipe_position.x, pipe_position.x + PIPE_WIDTH);
+
+        let (y_min, y_max): (f32, f32) = (pipe_position.y, pipe_position.y + pipe.gap_height);
+
+        let mut passed = false;
+        for (player_position, mut velocity, mut player) in &mut player_query {
+            if !player.alive {
+                continue;
+            }
 
-        for (_entity, pipe_position, mut pipe) in &mut pipe_query {
             let player_center = Vec2 {
                 x: player_position.x + PLAYER_SPRITE_SIZES.x / 2.0,
                 y: player_position.y + PLAYER_SPRITE_SIZES.y / 2.0,
             };
 
-            let (x_min, x_max): (f32, f32) = (pipe_position.x, pipe_position.x + PIPE_WIDTH);
-
-            let (y_min, y_max): (f32, f32) =
-                (pipe_position.y, pipe_position.y + PIPES_V_SEPARATION);
-
-            //pipe collision with pipes and floor
-            if player_center.x - PLAYER_COLLISION.x / 2.0 < x_max
+            //pipe collision
+            let hit_pipe = player_center.x - PLAYER_COLLISION.x / 2.0 < x_max
                 && player_center.x + PLAYER_COLLISION.x / 2.0 > x_min
                 && !(player_center.y - PLAYER_COLLISION.y / 2.0 > y_min
-                    && player_center.y + PLAYER_COLLISION.y / 2.0 < y_max - 10.0)
-                || player_center.y + PLAYER_COLLISION.y / 2.0 > -36.0
-            {
-                game_state.game_state = GameStates::GameOver;
-                audio::play_sound_once(hit_sound);
-                println!("{} In pipe bounds", pipe_position.x)
+                    && player_center.y + PLAYER_COLLISION.y / 2.0 < y_max - 10.0);
+            if hit_pipe {
+                player.alive = false;
+                velocity.x = -physics.pipe_speed;
+                events.0.push(GameEvent::Crash {
+                    player: player.index,
+                    x: player_position.x,
+                    y: player_position.y,
+                    cause: if player_center.y < y_min + pipe.gap_height / 2.0 {
+                        CrashCause::PipeTop
+                    } else {
+                        CrashCause::PipeBottom
+                    },
+                });
+                continue;
             }
 
-            //if player passes through pipe, score point
+            //every bird that passes through the pipe scores a point
             if player_center.x > x_max && pipe.can_score {
-                pipe.can_score = false;
-                game_state.score += 1;
-                audio::play_sound_once(score_sound);
-                println!("Scored a point: {}", game_state.score);
+                passed = true;
+                player.score += 1;
+                events.0.push(GameEvent::Score {
+                    player: player.index,
+                    score: player.score,
+                });
+            }
+        }
+
+        if passed {
+            pipe.can_score = false;
+        }
+    }
+
+    let players: Vec<&Player> = player_query.iter().map(|(.., player)| player).collect();
+    game_state.score = players.iter().map(|player| player.score).max().unwrap_or(0);
+
+    let alive: Vec<&&Player> = players.iter().filter(|player| player.alive).collect();
+    if settings.round_over(alive.len()) {
+        game_state.winner = match alive.as_slice() {
+            [survivor] => Some(survivor.index),
+            _ => best_player(&players),
+        };
+        game_state.game_state = GameStates::GameOver;
+    }
+}
+
+/// Plays the sounds for everything that happened since the last frame.
+pub fn play_sounds(mut events: ResMut<GameEvents>, assets: Res<AssetLoader>) {
+    let mut scored = false;
+    for event in events.0.drain(..) {
+        match event {
+            GameEvent::Flap { .. } => play_sound(assets.get_sound(FLAP_SOUND)),
+            GameEvent::Crash { .. } => play_sound(assets.get_sound(HIT_SOUND)),
+            //birds passing a pipe together get a single sound
+            GameEvent::Score { .. } => {
+                if !scored {
+                    play_sound(assets.get_sound(SCORE_SOUND));
+                    scored = true;
+                }
             }
+            GameEvent::PipeSpawned { .. } => {}
         }
     }
 }
 
-pub fn spawn_player(mut commands: Commands) {
-    commands.spawn(PlayerBundle::new());
+fn play_sound(sound: Option<&audio::Sound>) {
+    if let Some(sound) = sound {
+        audio::play_sound_once(sound);
+    }
+}
+
+/// Greys out crashed birds while the others fly on.
+pub fn tint_players(settings: Res<MatchSettings>, mut query: Query<(&Player, &mut Sprite)>) {
+    for (player, mut sprite) in &mut query {
+        sprite.tint = if settings.is_multiplayer() && !player.alive {
+            CRASHED_TINT
+        } else {
+            player.tint()
+        };
+    }
+}
+
+/// The player with the best score, `None` on a tie.
+pub(crate) fn best_player(players: &[&Player]) -> Option<usize> {
+    let best = players.iter().map(|player| player.score).max()?;
+    match players
+        .iter()
+        .filter(|player| player.score == best)
+        .collect::<Vec<_>>()
+        .as_slice()
+    {
+        [winner] => Some(winner.index),
+        _ => None,
+    }
+}
+
+/// Tilts the bird with its vertical speed while playing.
+pub fn rotate_player_sprite(
+    game_state: Res<GameState>,
+    mut query: Query<(&Velocity, &mut Sprite), With<Player>>,
+) {
+    for (velocity, mut sprite) in &mut query {
+        sprite.rotation = match game_state.game_state {
+            GameStates::Play => clamp(PI / 20.0 * (velocity.y * TICK), -PI / 4.0, PI / 2.0),
+            _ => 0.0,
+        };
+    }
+}
+
+/// Spawns a bird for each player of the match.
+pub fn spawn_players(mut commands: Commands, settings: Res<MatchSettings>) {
+    for index in 0..settings.players {
+        commands.spawn(PlayerBundle::new(index, settings.players));
+    }
 }
@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+
+use bevy_ecs::prelude::*;
+use macroquad::prelude::*;
+
+use crate::game::{GameState, GameStates, MatchSettings};
+use crate::mode::GameMode;
+use crate::pipe::{Pipe, PipeSpawn, random_gap_y, reset_pipes};
+use crate::player::{Controller, Player};
+use crate::simulation::{GameRng, PlayerInput, Snapshot, TICKS_PER_SECOND, Tick};
+use crate::transform::Position;
+
+/// Seconds of play kept to rewind through.
+pub const HISTORY_SECONDS: u32 = 10;
+/// Seconds a single rewind goes back.
+pub const REWIND_SECONDS: u32 = 2;
+
+/// A practice run: the same pipes every time, starting from any of them, and
+/// crashes rewind the last seconds instead of ending the run.
+#[derive(Resource, Debug, Clone)]
+pub struct Practice {
+    /// Pipes skipped at the start, which the score starts from.
+    pub from: u32,
+    /// Crashes since the run started.
+    pub deaths: u32,
+    seed: u64,
+    /// The last [`HISTORY_SECONDS`] of play, a snapshot per tick, oldest
+    /// first.
+    history: VecDeque<Snapshot>,
+    /// Whether the pipes have to be laid out again on the start screen.
+    dirty: bool,
+    /// Whether the run left the start screen.
+    started: bool,
+}
+
+impl Practice {
+    /// Practices on the pipes of `seed`, starting after `from` of them.
+    pub fn new(seed: u64, from: u32) -> Self {
+        Self {
+            from,
+            deaths: 0,
+            seed,
+            history: VecDeque::new(),
+            dirty: true,
+            started: false,
+        }
+    }
+
+    /// The snapshots kept to rewind through, oldest first.
+    pub fn history(&self) -> &VecDeque<Snapshot> {
+        &self.history
+    }
+
+    /// Keeps the snapshot of a tick, dropping the ones older than
+    /// [`HISTORY_SECONDS`]. A tick is kept once, however often it is
+    /// recorded.
+    pub fn record(&mut self, snapshot: Snapshot) {
+        if self
+            .history
+            .back()
+            .is_some_and(|last| last.tick == snapshot.tick)
+        {
+            return;
+        }
+        self.history.push_back(snapshot);
+        while self.history.len() > (HISTORY_SECONDS * TICKS_PER_SECOND) as usize {
+            self.history.pop_front();
+        }
+    }
+
+    /// Forgets the history newer than [`REWIND_SECONDS`] before `tick` and
+    /// returns the snapshot to go back to, or the oldest one kept.
+    pub fn rewind(&mut self, tick: Tick) -> Option<Snapshot> {
+        let target = tick.0.saturating_sub(REWIND_SECONDS * TICKS_PER_SECOND);
+        while self.history.len() > 1
+            && self
+                .history
+                .back()
+                .is_some_and(|snapshot| snapshot.tick.0 > target)
+        {
+            self.history.pop_back();
+        }
+        self.history.back().cloned()
+    }
+}
+
+/// Lays out the pipes of the practice seed with the skipped ones left out,
+/// so the first pipe on screen is pipe `from`.
+pub fn lay_out(world: &mut World, seed: u64, from: u32) {
+    let mut rng = GameRng::new(seed);
+    //every pipe draws its gap in turn, the skipped ones included
+    for _ in 0..from {
+        random_gap_y(&mut rng);
+    }
+    let spawn = *world.resource::<PipeSpawn>();
+    let mut pipe_query = world.query::<(&mut Position, &mut Pipe)>();
+    reset_pipes(pipe_query.iter_mut(world), &spawn, &mut rng);
+    world.insert_resource(rng);
+}
+
+/// Goes back [`REWIND_SECONDS`] and waits for a flap to go on.
+fn rewind(world: &mut World, practice: &mut Practice) {
+    let tick = *world.resource::<Tick>();
+    if let Some(snapshot) = practice.rewind(tick) {
+        snapshot.restore(world);
+    }
+    world.resource_mut::<GameState>().game_state = GameStates::Paused;
+}
+
+/// Runs a practice run: lays out the pipes on the start screen, where Up and
+/// Down choose the first pipe, keeps the history while playing and rewinds
+/// on a crash or Backspace. Runs after the simulation and before the UI is
+/// updated, so a crash never shows the game over sign.
+pub fn practice(world: &mut World) {
+    world.resource_scope(|world, mut practice: Mut<Practice>| {
+        match world.resource::<GameState>().game_state {
+            GameStates::PressStart => {
+                //R brought the run back to the start
+                if practice.started {
+                    practice.started = false;
+                    practice.dirty = true;
+                }
+                if is_key_pressed(KeyCode::Up) {
+                    practice.from += 1;
+                    practice.dirty = true;
+                }
+                if is_key_pressed(KeyCode::Down) && practice.from > 0 {
+                    practice.from -= 1;
+                    practice.dirty = true;
+                }
+                //a timer or a distance would go back with every rewind
+                if world.resource::<MatchSettings>().mode != GameMode::Endless {
+                    world.resource_mut::<MatchSettings>().mode = GameMode::Endless;
+                }
+                if practice.dirty {
+                    lay_out(world, practice.seed, practice.from);
+                    practice.history.clear();
+                    practice.deaths = 0;
+                    practice.dirty = false;
+                }
+            }
+            GameStates::Play => {
+                if !practice.started {
+                    practice.started = true;
+                    world.resource_mut::<GameState>().score = practice.from;
+                    let mut player_query = world.query::<&mut Player>();
+                    for mut player in player_query.iter_mut(world) {
+                        player.score = practice.from;
+                    }
+                }
+                if is_key_pressed(KeyCode::Backspace) {
+                    rewind(world, &mut practice);
+                } else {
+                    practice.record(Snapshot::take(world));
+                }
+            }
+            GameStates::GameOver => {
+                practice.deaths += 1;
+                rewind(world, &mut practice);
+            }
+            GameStates::Paused => {
+                //the flap that goes on is not lost while the game waits
+                let mut flapped = false;
+                let mut player_query = world.query::<(&Player, &Controller, &mut PlayerInput)>();
+                for (player, controller, mut input) in player_query.iter_mut(world) {
+                    if *controller == Controller::Keyboard && is_key_pressed(player.flap_key) {
+                        input.flap = true;
+                        flapped = true;
+                    }
+                }
+
+                if flapped {
+                    world.resource_mut::<GameState>().game_state = GameStates::Play;
+                } else if is_key_pressed(KeyCode::Backspace) {
+                    rewind(world, &mut practice);
+                } else if is_key_pressed(KeyCode::R) {
+                    world.resource_mut::<GameState>().game_state = GameStates::Restart;
+                }
+            }
+            _ => {}
+        }
+    });
+}
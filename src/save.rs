@@ -0,0 +1,265 @@
+use bevy_ecs::prelude::*;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::animation::Animator;
+use crate::asset_loader::BIRD_CLIPS;
+use crate::background::Floor;
+use crate::error::{Error, Result};
+use crate::game::GameState;
+use crate::pipe::Pipe;
+use crate::player::{MAX_PLAYERS, Player};
+use crate::simulation::{Distance, GameRng, PlayerInput, Tick};
+use crate::transform::{Position, Velocity};
+
+/// Changes whenever the format does, so older files are refused instead of
+/// loading into a different game.
+pub const SAVE_STATE_VERSION: u32 = 1;
+pub const QUICKSAVE_PATH: &str = "quicksave.json";
+
+/// The whole game, written to and read from a JSON file.
+///
+/// Unlike a [`Snapshot`](crate::simulation::Snapshot) it names no entities:
+/// birds are matched by their index, pipes and floor tiles by the order they
+/// were spawned in, so it loads into any world set up the same way.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveState {
+    pub version: u32,
+    pub tick: Tick,
+    pub distance: Distance,
+    pub game: GameState,
+    pub rng: GameRng,
+    pub birds: Vec<SavedBird>,
+    pub pipes: Vec<SavedPipe>,
+    pub floor: Vec<Position>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedBird {
+    pub index: usize,
+    pub score: u32,
+    pub alive: bool,
+    /// A flap that no tick consumed yet.
+    pub flap: bool,
+    pub position: Position,
+    pub velocity: Velocity,
+    pub animation: Option<SavedAnimation>,
+}
+
+/// Where a bird's animation is. Its speed and looping come with the bird.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedAnimation {
+    pub clip: String,
+    pub frame: usize,
+    pub timer: f32,
+    pub playing: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedPipe {
+    pub position: Position,
+    pub velocity: Velocity,
+    pub pipe: Pipe,
+}
+
+impl SaveState {
+    pub fn take(world: &mut World) -> Self {
+        let mut birds: Vec<SavedBird> = world
+            .query::<(
+                &Player,
+                &PlayerInput,
+                &Position,
+                &Velocity,
+                Option<&Animator>,
+            )>()
+            .iter(world)
+            .map(|(player, input, position, velocity, animator)| SavedBird {
+                index: player.index,
+                score: player.score,
+                alive: player.alive,
+                flap: input.flap,
+                position: *position,
+                velocity: *velocity,
+                animation: animator.map(|animator| SavedAnimation {
+                    clip: animator.clip.name().to_string(),
+                    frame: animator.frame,
+                    timer: animator.timer,
+                    playing: animator.playing,
+                }),
+            })
+            .collect();
+        birds.sort_by_key(|bird| bird.index);
+
+        let mut pipes: Vec<(Entity, SavedPipe)> = world
+            .query::<(Entity, &Position, &Velocity, &Pipe)>()
+            .iter(world)
+            .map(|(entity, position, velocity, pipe)| {
+                let saved = SavedPipe {
+                    position: *position,
+                    velocity: *velocity,
+                    pipe: *pipe,
+                };
+                (entity, saved)
+            })
+            .collect();
+        pipes.sort_by_key(|(entity, _)| *entity);
+
+        Self {
+            version: SAVE_STATE_VERSION,
+            tick: *world.resource::<Tick>(),
+            distance: *world.resource::<Distance>(),
+            game: world.resource::<GameState>().clone(),
+            rng: *world.resource::<GameRng>(),
+            birds,
+            pipes: pipes.into_iter().map(|(_entity, pipe)| pipe).collect(),
+            floor: floor_tiles(world)
+                .into_iter()
+                .map(|(_entity, position)| position)
+                .collect(),
+        }
+    }
+
+    /// Puts the saved game into `world`, which must have as many birds,
+    /// pipes and floor tiles. Nothing changes when it doesn't.
+    pub fn restore(&self, world: &mut World) -> Result<()> {
+        let mut birds = Vec::new();
+        let mut bird_query = world.query::<(Entity, &Player)>();
+        for bird in &self.birds {
+            let Some((entity, _player)) = bird_query
+                .iter(world)
+                .find(|(_entity, player)| player.index == bird.index)
+            else {
+                return Err(Error::State(format!("there is no bird {}", bird.index + 1)));
+            };
+            let clip = match &bird.animation {
+                Some(animation) => Some(
+                    BIRD_CLIPS
+                        .into_iter()
+                        .find(|clip| clip.name() == animation.clip)
+                        .ok_or_else(|| Error::State(format!("unknown clip {}", animation.clip)))?,
+                ),
+                None => None,
+            };
+            birds.push((entity, bird, clip));
+        }
+        let count = bird_query.iter(world).count();
+        if count != self.birds.len() {
+            return Err(Error::State(format!(
+                "saved with {} birds, not {count}",
+                self.birds.len()
+            )));
+        }
+
+        let mut pipe_entities: Vec<Entity> = world
+            .query_filtered::<Entity, With<Pipe>>()
+            .iter(world)
+            .collect();
+        pipe_entities.sort();
+        if pipe_entities.len() != self.pipes.len() {
+            return Err(Error::State(format!(
+                "saved with {} pipes, not {}",
+                self.pipes.len(),
+                pipe_entities.len()
+            )));
+        }
+        let floor = floor_tiles(world);
+        if floor.len() != self.floor.len() {
+            return Err(Error::State(format!(
+                "saved with {} floor tiles, not {}",
+                self.floor.len(),
+                floor.len()
+            )));
+        }
+
+        world.insert_resource(self.tick);
+        world.insert_resource(self.distance);
+        world.insert_resource(self.game.clone());
+        world.insert_resource(self.rng);
+        for (entity, bird, clip) in birds {
+            let mut entity = world.entity_mut(entity);
+            entity.insert((
+                bird.position,
+                bird.velocity,
+                PlayerInput { flap: bird.flap },
+            ));
+            if let Some(mut player) = entity.get_mut::<Player>() {
+                player.score = bird.score;
+                player.alive = bird.alive;
+            }
+            if let (Some(animation), Some(clip), Some(mut animator)) =
+                (&bird.animation, clip, entity.get_mut::<Animator>())
+            {
+                animator.clip = clip;
+                animator.frame = animation.frame;
+                animator.timer = animation.timer;
+                animator.playing = animation.playing;
+            }
+        }
+        for (entity, saved) in pipe_entities.into_iter().zip(&self.pipes) {
+            world
+                .entity_mut(entity)
+                .insert((saved.position, saved.velocity, saved.pipe));
+        }
+        for ((entity, _position), position) in floor.into_iter().zip(&self.floor) {
+            world.entity_mut(entity).insert(*position);
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        let json = std::fs::read_to_string(path).map_err(|error| Error::asset(path, error))?;
+        let state: SaveState =
+            serde_json::from_str(&json).map_err(|error| Error::asset(path, error))?;
+        if state.birds.is_empty() || state.birds.len() > MAX_PLAYERS {
+            return Err(Error::asset(
+                path,
+                format!("a game has 1 to {MAX_PLAYERS} birds"),
+            ));
+        }
+        if state.version != SAVE_STATE_VERSION {
+            return Err(Error::asset(
+                path,
+                format!(
+                    "saved by version {}, this game reads version {SAVE_STATE_VERSION}",
+                    state.version
+                ),
+            ));
+        }
+        Ok(state)
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(|error| Error::write(path, error))?;
+        std::fs::write(path, json).map_err(|error| Error::write(path, error))
+    }
+}
+
+/// The floor tiles in the order they were spawned.
+fn floor_tiles(world: &mut World) -> Vec<(Entity, Position)> {
+    let mut floor: Vec<(Entity, Position)> = world
+        .query_filtered::<(Entity, &Position), With<Floor>>()
+        .iter(world)
+        .map(|(entity, position)| (entity, *position))
+        .collect();
+    floor.sort_by_key(|(entity, _)| *entity);
+    floor
+}
+
+/// Loads the save state at `path` into `world`.
+pub fn load_state(world: &mut World, path: &str) -> Result<()> {
+    SaveState::load(path)?.restore(world)
+}
+
+/// F5 saves the game to [`QUICKSAVE_PATH`], F9 loads it back.
+pub fn quicksave(world: &mut World) {
+    let result = if is_key_pressed(KeyCode::F5) {
+        SaveState::take(world).save(QUICKSAVE_PATH)
+    } else if is_key_pressed(KeyCode::F9) {
+        load_state(world, QUICKSAVE_PATH)
+    } else {
+        return;
+    };
+    if let Err(error) = result {
+        eprintln!("{error}");
+    }
+}
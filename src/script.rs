@@ -0,0 +1,404 @@
+use std::sync::{Arc, Mutex};
+
+use bevy_ecs::prelude::*;
+use macroquad::logging::warn;
+use macroquad::math::Vec2;
+use rhai::{AST, CallFnOptions, Dynamic, Engine, EvalAltResult, FLOAT, FuncArgs, INT, Map};
+
+use crate::asset_loader::PIPE_TEXTURE;
+use crate::background::Floor;
+use crate::bot::drive_bots;
+use crate::error::{Error, Result};
+use crate::game::{GameState, GameStates};
+use crate::level::LevelStart;
+use crate::pipe::{Pipe, move_pipe};
+use crate::player::{PLAYER_COLLISION, PLAYER_SPRITE_SIZES, Player, collide_player, move_player};
+use crate::simulation::{
+    CrashCause, GameEvent, GameEvents, Physics, TICK, Tick, advance_tick, simulation_schedule,
+};
+use crate::sprite::{LAYER_PIPES, Sprite};
+use crate::transform::{Position, Velocity};
+
+/// What moves at the speed of the pipes.
+type Scrolling = Or<(With<Pipe>, With<Floor>, With<LevelStart>)>;
+
+/// How far off the left edge of the screen obstacles go before they are
+/// despawned.
+const OBSTACLE_MARGIN: f32 = 100.0;
+
+/// A box that crashes the birds touching it, spawned by a script.
+/// `Position` is its top left corner.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct Obstacle {
+    pub width: f32,
+    pub height: f32,
+}
+
+/// What a script asked for through the functions it can call, applied to
+/// the world once its hooks returned.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScriptCommand {
+    SpawnObstacle {
+        position: Position,
+        velocity: Velocity,
+        obstacle: Obstacle,
+    },
+    AddScore {
+        player: usize,
+        points: u32,
+    },
+}
+
+/// A Rhai script that changes the rules of the game. Only compiled with the
+/// `scripting` feature.
+///
+/// The script defines any of `on_spawn_pipe(pipe)`, `on_score(player,
+/// score)`, `on_flap(player)` and `on_tick(tick)`, run on every tick of a
+/// round for what happened in it. Inside them `this` is the game, with
+/// `this.score`, `this.tick`, `this.state` (`"play"` or `"game_over"`, which
+/// ends the round), and `this.gravity`, `this.jump_speed` and
+/// `this.pipe_speed`, which can be changed. Other fields a script sets on
+/// `this` are kept until the game restarts. `on_spawn_pipe` gets the pipe's
+/// `x` and `y` and may return a number to move its gap there. Scripts can
+/// also call `spawn_obstacle(x, y, width, height, velocity_x, velocity_y)`
+/// and `add_score(player, points)`.
+#[derive(Resource)]
+pub struct Scripts {
+    path: String,
+    engine: Engine,
+    ast: AST,
+    commands: Arc<Mutex<Vec<ScriptCommand>>>,
+    /// `this` in the hooks.
+    game: Map,
+    /// Events already in [`GameEvents`] when the current tick started.
+    events_seen: usize,
+}
+
+impl Scripts {
+    /// Compiles the script at `path` and runs its top level statements.
+    pub fn load(path: &str) -> Result<Self> {
+        let commands = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = Engine::new();
+        engine.on_print(|text| eprintln!("{text}"));
+
+        let queue = commands.clone();
+        engine.register_fn(
+            "spawn_obstacle",
+            move |x: Dynamic,
+                  y: Dynamic,
+                  width: Dynamic,
+                  height: Dynamic,
+                  velocity_x: Dynamic,
+                  velocity_y: Dynamic|
+                  -> std::result::Result<(), Box<EvalAltResult>> {
+                queue.lock().unwrap().push(ScriptCommand::SpawnObstacle {
+                    position: Position {
+                        x: number(&x)?,
+                        y: number(&y)?,
+                    },
+                    velocity: Velocity {
+                        x: number(&velocity_x)?,
+                        y: number(&velocity_y)?,
+                    },
+                    obstacle: Obstacle {
+                        width: number(&width)?,
+                        height: number(&height)?,
+                    },
+                });
+                Ok(())
+            },
+        );
+        let queue = commands.clone();
+        engine.register_fn("add_score", move |player: INT, points: INT| {
+            queue.lock().unwrap().push(ScriptCommand::AddScore {
+                player: player.max(0) as usize,
+                points: points.max(0) as u32,
+            });
+        });
+
+        let source = std::fs::read_to_string(path).map_err(|error| Error::asset(path, error))?;
+        let ast = engine
+            .compile(source)
+            .map_err(|error| Error::asset(path, error))?;
+        engine
+            .run_ast(&ast)
+            .map_err(|error| Error::asset(path, error))?;
+
+        Ok(Self {
+            path: path.to_string(),
+            engine,
+            ast,
+            commands,
+            game: Map::new(),
+            events_seen: 0,
+        })
+    }
+
+    fn defines(&self, hook: &str) -> bool {
+        self.ast
+            .iter_functions()
+            .any(|function| function.name == hook)
+    }
+
+    /// Calls `hook` if the script defines it. Errors are logged and the game
+    /// goes on, as if the hook returned nothing.
+    fn call(&self, game: &mut Dynamic, hook: &str, args: impl FuncArgs) -> Dynamic {
+        if !self.defines(hook) {
+            return Dynamic::UNIT;
+        }
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(game);
+        self.engine
+            .call_fn_with_options(options, &mut Default::default(), &self.ast, hook, args)
+            .unwrap_or_else(|error| {
+                warn!("{}: {}: {}", self.path, hook, error);
+                Dynamic::UNIT
+            })
+    }
+}
+
+/// A number given to a script function, which scripts may write with or
+/// without a decimal point.
+fn number(value: &Dynamic) -> std::result::Result<f32, Box<EvalAltResult>> {
+    value
+        .as_float()
+        .or_else(|_| value.as_int().map(|value| value as FLOAT))
+        .map_err(|type_name| format!("expected a number, got {type_name}").into())
+}
+
+fn state_name(state: GameStates) -> &'static str {
+    match state {
+        GameStates::Play => "play",
+        GameStates::Paused => "paused",
+        GameStates::GameOver => "game_over",
+        GameStates::LevelComplete => "level_complete",
+        GameStates::PressStart => "press_start",
+        GameStates::Restart => "restart",
+    }
+}
+
+/// The simulation with the script hooks and the obstacles scripts spawn.
+pub fn scripted_schedule() -> Schedule {
+    let mut schedule = simulation_schedule();
+    schedule.add_systems((
+        begin_script_tick.before(drive_bots),
+        move_obstacles.after(move_player).before(collide_obstacles),
+        collide_obstacles.after(move_pipe).before(collide_player),
+        run_scripts.after(collide_player).before(advance_tick),
+        despawn_obstacles.after(run_scripts),
+    ));
+    schedule
+}
+
+fn begin_script_tick(mut scripts: ResMut<Scripts>, events: Res<GameEvents>) {
+    scripts.events_seen = events.0.len();
+}
+
+pub fn move_obstacles(
+    game_state: Res<GameState>,
+    mut query: Query<(&mut Position, &Velocity), With<Obstacle>>,
+) {
+    if game_state.game_state == GameStates::Play {
+        for (mut position, velocity) in &mut query {
+            position.x += velocity.x * TICK;
+            position.y += velocity.y * TICK;
+        }
+    }
+}
+
+pub fn collide_obstacles(
+    game_state: Res<GameState>,
+    physics: Res<Physics>,
+    mut events: ResMut<GameEvents>,
+    obstacle_query: Query<(&Position, &Obstacle)>,
+    mut player_query: Query<(&Position, &mut Velocity, &mut Player), Without<Obstacle>>,
+) {
+    if game_state.game_state != GameStates::Play {
+        return;
+    }
+
+    for (position, obstacle) in &obstacle_query {
+        for (player_position, mut velocity, mut player) in &mut player_query {
+            if !player.alive {
+                continue;
+            }
+
+            let player_center = Vec2 {
+                x: player_position.x + PLAYER_SPRITE_SIZES.x / 2.0,
+                y: player_position.y + PLAYER_SPRITE_SIZES.y / 2.0,
+            };
+            if player_center.x - PLAYER_COLLISION.x / 2.0 < position.x + obstacle.width
+                && player_center.x + PLAYER_COLLISION.x / 2.0 > position.x
+                && player_center.y - PLAYER_COLLISION.y / 2.0 < position.y + obstacle.height
+                && player_center.y + PLAYER_COLLISION.y / 2.0 > position.y
+            {
+                player.alive = false;
+                velocity.x = -physics.pipe_speed;
+                events.0.push(GameEvent::Crash {
+                    player: player.index,
+                    x: player_position.x,
+                    y: player_position.y,
+                    cause: CrashCause::Obstacle,
+                });
+            }
+        }
+    }
+}
+
+/// Calls the script's hooks for the events of this tick, then applies what
+/// the script changed.
+pub fn run_scripts(world: &mut World) {
+    if world.resource::<GameState>().game_state != GameStates::Play {
+        return;
+    }
+
+    world.resource_scope(|world, mut scripts: Mut<Scripts>| {
+        let events = world.resource::<GameEvents>().0[scripts.events_seen..].to_vec();
+        let physics = *world.resource::<Physics>();
+        let tick = world.resource::<Tick>().0;
+        let game_state = world.resource::<GameState>();
+        let mut game = std::mem::take(&mut scripts.game);
+        game.insert("score".into(), (game_state.score as INT).into());
+        game.insert("tick".into(), (tick as INT).into());
+        game.insert("state".into(), state_name(game_state.game_state).into());
+        game.insert("gravity".into(), physics.gravity.into());
+        game.insert("jump_speed".into(), physics.jump_speed.into());
+        game.insert("pipe_speed".into(), physics.pipe_speed.into());
+        let mut game = Dynamic::from_map(game);
+
+        for event in events {
+            match event {
+                GameEvent::Flap { player, .. } => {
+                    let _ = scripts.call(&mut game, "on_flap", (player as INT,));
+                }
+                GameEvent::Score { player, score } => {
+                    let _ = scripts.call(&mut game, "on_score", (player as INT, score as INT));
+                }
+                GameEvent::PipeSpawned { pipe } => {
+                    let Some(position) = world.get::<Position>(pipe).copied() else {
+                        continue;
+                    };
+                    let mut pipe_map = Map::new();
+                    pipe_map.insert("x".into(), position.x.into());
+                    pipe_map.insert("y".into(), position.y.into());
+                    let gap = scripts.call(&mut game, "on_spawn_pipe", (pipe_map,));
+                    if let Ok(y) = number(&gap)
+                        && let Some(mut position) = world.get_mut::<Position>(pipe)
+                    {
+                        position.y = y;
+                    }
+                }
+                GameEvent::Crash { .. } => {}
+            }
+        }
+        let _ = scripts.call(&mut game, "on_tick", (tick as INT,));
+
+        scripts.game = game.cast::<Map>();
+        apply_game(world, &scripts.path, &scripts.game, physics);
+        let commands: Vec<ScriptCommand> = scripts.commands.lock().unwrap().drain(..).collect();
+        for command in commands {
+            apply_command(world, command);
+        }
+    });
+}
+
+/// Copies the fields of `this` the script changed back into the world.
+fn apply_game(world: &mut World, path: &str, game: &Map, old_physics: Physics) {
+    let field = |name: &str, old: f32| {
+        game.get(name)
+            .map_or(Ok(old), number)
+            .unwrap_or_else(|error| {
+                warn!("{path}: this.{name}: {error}");
+                old
+            })
+    };
+    let physics = Physics {
+        gravity: field("gravity", old_physics.gravity),
+        jump_speed: field("jump_speed", old_physics.jump_speed),
+        pipe_speed: field("pipe_speed", old_physics.pipe_speed),
+    };
+    *world.resource_mut::<Physics>() = physics;
+
+    //pipes and the floor scroll together, birds already crashed keep going
+    if physics.pipe_speed != old_physics.pipe_speed {
+        let mut scrolling = world.query_filtered::<&mut Velocity, Scrolling>();
+        for mut velocity in scrolling.iter_mut(world) {
+            velocity.x = -physics.pipe_speed;
+        }
+    }
+
+    let state = game
+        .get("state")
+        .and_then(|state| state.clone().into_immutable_string().ok());
+    let current = state_name(world.resource::<GameState>().game_state);
+    match state.as_deref() {
+        None => {}
+        Some(state) if state == current => {}
+        Some("game_over") => world.resource_mut::<GameState>().game_state = GameStates::GameOver,
+        Some(other) => warn!("{path}: this.state can't be set to {other}"),
+    }
+}
+
+fn apply_command(world: &mut World, command: ScriptCommand) {
+    match command {
+        ScriptCommand::SpawnObstacle {
+            position,
+            velocity,
+            obstacle,
+        } => {
+            let mut sprite = Sprite::new(PIPE_TEXTURE, LAYER_PIPES);
+            sprite.dest_size = Some(Vec2::new(obstacle.width, obstacle.height));
+            world.spawn((position, velocity, obstacle, sprite));
+        }
+        ScriptCommand::AddScore { player, points } => {
+            let mut players = world.query::<&mut Player>();
+            let mut best = 0;
+            let mut scored = None;
+            for mut bird in players.iter_mut(world) {
+                if bird.index == player {
+                    bird.score += points;
+                    scored = Some(bird.score);
+                }
+                best = best.max(bird.score);
+            }
+            world.resource_mut::<GameState>().score = best;
+            if let Some(score) = scored {
+                world
+                    .resource_mut::<GameEvents>()
+                    .0
+                    .push(GameEvent::Score { player, score });
+            }
+        }
+    }
+}
+
+/// Despawns obstacles that scrolled off the screen.
+pub fn despawn_obstacles(mut commands: Commands, query: Query<(Entity, &Position, &Obstacle)>) {
+    for (entity, position, obstacle) in &query {
+        if position.x + obstacle.width < -OBSTACLE_MARGIN {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Undoes what the script changed in the last round when the game
+/// restarts: obstacles, physics and the fields it set on `this`. Runs
+/// before [`restart_game`](crate::game::restart_game).
+pub fn restart_scripts(
+    mut commands: Commands,
+    game_state: Res<GameState>,
+    mut scripts: ResMut<Scripts>,
+    mut physics: ResMut<Physics>,
+    obstacle_query: Query<Entity, With<Obstacle>>,
+    mut scrolling_query: Query<&mut Velocity, Scrolling>,
+) {
+    if game_state.game_state == GameStates::Restart {
+        for entity in &obstacle_query {
+            commands.entity(entity).despawn();
+        }
+        *physics = Physics::default();
+        for mut velocity in &mut scrolling_query {
+            velocity.x = -physics.pipe_speed;
+        }
+        scripts.game.clear();
+    }
+}
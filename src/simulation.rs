@@ -0,0 +1,361 @@
+use bevy_ecs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::background::{spawn_background, update_background};
+use crate::bot::drive_bots;
+use crate::game::{GameState, GameStates, MatchSettings};
+use crate::level::{collect_pickups, finish_level, move_level};
+use crate::mode::time_up;
+use crate::pipe::PIPE_SPEED;
+use crate::pipe::{Pipe, PipeSpawn, move_pipe, spawn_pipes};
+use crate::player::{
+    GRAVITY, JUMP_SPEED, Player, add_gravity, collide_player, move_player, spawn_players,
+};
+use crate::transform::{Position, Velocity};
+
+pub const TICKS_PER_SECOND: u32 = 60;
+/// Seconds simulated by one tick. Gameplay always advances in whole ticks,
+/// so the same seed and inputs give the same game on every machine.
+pub const TICK: f32 = 1.0 / TICKS_PER_SECOND as f32;
+/// Ticks caught up in a single rendered frame at most; after a long stall
+/// the game slows down instead of jumping ahead.
+pub const MAX_TICKS_PER_FRAME: u32 = 5;
+
+const PCG_INCREMENT: u64 = 1442695040888963407;
+const PCG_MULTIPLIER: u64 = 6364136223846793005;
+
+/// The random numbers used by gameplay, seeded per round.
+///
+/// Same PCG generator as `macroquad::rand`, but its state is plain data that
+/// can be copied into snapshots and sent to other machines.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameRng {
+    state: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Self { state: 0 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let old_state = self.state;
+        self.state = old_state
+            .wrapping_mul(PCG_MULTIPLIER)
+            .wrapping_add(PCG_INCREMENT);
+        let xor_shifted = (((old_state >> 18) ^ old_state) >> 27) as u32;
+        let rotation = (old_state >> 59) as u32;
+        xor_shifted.rotate_right(rotation)
+    }
+
+    /// A number in `low..high`.
+    pub fn gen_range(&mut self, low: f32, high: f32) -> f32 {
+        let r = self.next_u32() as f64 / (u32::MAX as f64 + 1.0);
+        (low as f64 + (high as f64 - low as f64) * r) as f32
+    }
+
+    /// An index in `0..len`.
+    pub fn gen_index(&mut self, len: usize) -> usize {
+        let r = self.next_u32() as f64 / (u32::MAX as f64 + 1.0);
+        (len as f64 * r) as usize
+    }
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+/// Number of ticks simulated since the round started.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tick(pub u32);
+
+/// Pixels the pipes scrolled since the round started.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Distance(pub f32);
+
+/// What a bird's controller wants to do on the next tick. A flap stays
+/// pending until a tick consumes it, so short key presses are never lost.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PlayerInput {
+    pub flap: bool,
+}
+
+/// Something that happened during a tick, for sounds and logs. Gameplay
+/// systems only record events, so a tick can be re-run without side effects.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameEvent {
+    Flap {
+        player: usize,
+        y: f32,
+    },
+    Score {
+        player: usize,
+        score: u32,
+    },
+    /// Where the bird was when it crashed.
+    Crash {
+        player: usize,
+        x: f32,
+        y: f32,
+        cause: CrashCause,
+    },
+    /// A pipe that scrolled off the screen came back on the right with a
+    /// new gap.
+    PipeSpawned {
+        pipe: Entity,
+    },
+}
+
+/// What a bird crashed into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrashCause {
+    PipeTop,
+    PipeBottom,
+    Floor,
+    /// An obstacle spawned by a script.
+    Obstacle,
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct GameEvents(pub Vec<GameEvent>);
+
+/// Drops the events of the last frame, for games nobody listens to.
+pub fn discard_events(mut events: ResMut<GameEvents>) {
+    events.0.clear();
+}
+
+/// The forces of the game, which scripts may change during a round.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct Physics {
+    pub gravity: f32,
+    pub jump_speed: f32,
+    pub pipe_speed: f32,
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Self {
+            gravity: GRAVITY,
+            jump_speed: JUMP_SPEED,
+            pipe_speed: PIPE_SPEED,
+        }
+    }
+}
+
+/// Leftover real time that was not enough for a whole tick yet.
+#[derive(Resource, Debug, Default)]
+pub struct FixedTimestep {
+    pub accumulator: f32,
+}
+
+/// Adds the resources the simulation needs, with the pipe layout seeded by
+/// `seed`. Must run before the startup systems spawn the pipes.
+pub fn insert_simulation_resources(world: &mut World, seed: u64) {
+    world.insert_resource(GameRng::new(seed));
+    world.init_resource::<Tick>();
+    world.init_resource::<Distance>();
+    world.init_resource::<Physics>();
+    world.init_resource::<PipeSpawn>();
+    world.init_resource::<GameEvents>();
+    world.init_resource::<FixedTimestep>();
+}
+
+/// A world with just what the simulation needs, on the start screen: no
+/// assets, window or keyboard, so games can be played without them.
+pub fn headless_world(settings: MatchSettings, seed: u64) -> World {
+    let mut world = World::default();
+    world.init_resource::<GameState>();
+    world.insert_resource(settings);
+    insert_simulation_resources(&mut world, seed);
+
+    let mut startup_schedule = Schedule::default();
+    startup_schedule.add_systems((spawn_pipes, spawn_players, spawn_background).chain());
+    startup_schedule.run(&mut world);
+    world
+}
+
+pub(crate) fn advance_tick(
+    mut tick: ResMut<Tick>,
+    mut distance: ResMut<Distance>,
+    game_state: Res<GameState>,
+    physics: Res<Physics>,
+) {
+    if game_state.game_state == GameStates::Play {
+        tick.0 += 1;
+        distance.0 += physics.pipe_speed * TICK;
+    }
+}
+
+/// The deterministic part of the game: everything that moves, collides and
+/// scores. Input, sounds and drawing stay outside.
+pub fn simulation_schedule() -> Schedule {
+    let mut schedule = Schedule::default();
+    schedule.add_systems(
+        (
+            drive_bots,
+            add_gravity,
+            move_player,
+            move_pipe,
+            move_level,
+            update_background,
+            collide_player,
+            collect_pickups,
+            finish_level,
+            advance_tick,
+            time_up,
+        )
+            .chain(),
+    );
+    schedule
+}
+
+/// Runs as many ticks as fit in the real time that passed.
+pub fn run_fixed_ticks(world: &mut World, simulation: &mut Schedule, frame_time: f32) {
+    let ticks = {
+        let mut timestep = world.resource_mut::<FixedTimestep>();
+        timestep.accumulator =
+            (timestep.accumulator + frame_time).min(TICK * MAX_TICKS_PER_FRAME as f32);
+        let ticks = (timestep.accumulator / TICK) as u32;
+        timestep.accumulator -= ticks as f32 * TICK;
+        ticks
+    };
+
+    for _ in 0..ticks {
+        simulation.run(world);
+    }
+}
+
+/// Everything the simulation reads or writes, copied out of the world.
+///
+/// Entities are kept as they are, so a snapshot can only be restored into
+/// the world it was taken from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub tick: Tick,
+    pub distance: Distance,
+    pub game_state: GameStates,
+    pub score: u32,
+    pub winner: Option<usize>,
+    pub rng: GameRng,
+    pub bodies: Vec<(Entity, Position, Velocity)>,
+    pub pipes: Vec<(Entity, Pipe)>,
+    pub players: Vec<(Entity, Player, PlayerInput)>,
+}
+
+impl Snapshot {
+    pub fn take(world: &mut World) -> Self {
+        let game_state = world.resource::<GameState>();
+        let (state, score, winner) = (game_state.game_state, game_state.score, game_state.winner);
+
+        //queries visit archetypes in the order they were first matched, which
+        //differs between machines, so entities are sorted for the checksum
+        let mut snapshot = Self {
+            tick: world.get_resource::<Tick>().copied().unwrap_or_default(),
+            distance: world
+                .get_resource::<Distance>()
+                .copied()
+                .unwrap_or_default(),
+            game_state: state,
+            score,
+            winner,
+            rng: world.get_resource::<GameRng>().copied().unwrap_or_default(),
+            bodies: world
+                .query::<(Entity, &Position, &Velocity)>()
+                .iter(world)
+                .map(|(entity, position, velocity)| (entity, *position, *velocity))
+                .collect(),
+            pipes: world
+                .query::<(Entity, &Pipe)>()
+                .iter(world)
+                .map(|(entity, pipe)| (entity, *pipe))
+                .collect(),
+            players: world
+                .query::<(Entity, &Player, &PlayerInput)>()
+                .iter(world)
+                .map(|(entity, player, input)| (entity, *player, *input))
+                .collect(),
+        };
+        snapshot.bodies.sort_by_key(|(entity, ..)| *entity);
+        snapshot.pipes.sort_by_key(|(entity, _)| *entity);
+        snapshot.players.sort_by_key(|(entity, ..)| *entity);
+        snapshot
+    }
+
+    pub fn restore(&self, world: &mut World) {
+        world.insert_resource(self.tick);
+        world.insert_resource(self.distance);
+        world.insert_resource(self.rng);
+        {
+            let mut game_state = world.resource_mut::<GameState>();
+            game_state.game_state = self.game_state;
+            game_state.score = self.score;
+            game_state.winner = self.winner;
+        }
+
+        for (entity, position, velocity) in &self.bodies {
+            if let Ok(mut body) = world.get_entity_mut(*entity) {
+                body.insert((*position, *velocity));
+            }
+        }
+        for (entity, pipe) in &self.pipes {
+            if let Ok(mut body) = world.get_entity_mut(*entity) {
+                body.insert(*pipe);
+            }
+        }
+        for (entity, player, input) in &self.players {
+            if let Ok(mut body) = world.get_entity_mut(*entity) {
+                body.insert((*player, *input));
+            }
+        }
+    }
+
+    /// A hash of the simulated state, equal on two machines exactly when
+    /// their games are in sync.
+    pub fn checksum(&self) -> u64 {
+        let mut hash = Fnv::default();
+        hash.write_u64(self.tick.0 as u64);
+        hash.write_u64(self.distance.0.to_bits() as u64);
+        hash.write_u64(self.game_state as u64);
+        hash.write_u64(self.score as u64);
+        hash.write_u64(self.rng.state);
+        for (_entity, position, velocity) in &self.bodies {
+            for value in [position.x, position.y, velocity.x, velocity.y] {
+                hash.write_u64(value.to_bits() as u64);
+            }
+        }
+        for (_entity, pipe) in &self.pipes {
+            hash.write_u64(pipe.can_score as u64);
+        }
+        for (_entity, player, _input) in &self.players {
+            hash.write_u64(player.index as u64);
+            hash.write_u64(player.score as u64);
+            hash.write_u64(player.alive as u64);
+        }
+        hash.0
+    }
+}
+
+/// 64 bit FNV-1a, stable across runs and platforms unlike `DefaultHasher`.
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Fnv(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv {
+    fn write_u64(&mut self, value: u64) {
+        for byte in value.to_le_bytes() {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufWriter;
+
+use macroquad::prelude::*;
+
+use crate::draw::{DrawCommand, RenderBackend};
+use crate::error::{Error, Result};
+use crate::game::SCREEN_SIZE;
+use crate::manifest::Manifest;
+
+/// CPU rasterizer that draws the same command list as the game into an RGBA
+/// buffer, so screens can be rendered and compared on machines without a GPU.
+pub struct SoftwareBackend {
+    pub width: u32,
+    pub height: u32,
+    view: Rect,
+    pixels: Vec<[u8; 4]>,
+    textures: HashMap<String, Image>,
+}
+
+impl SoftwareBackend {
+    /// `view` is the region of the game world that fills the image.
+    pub fn new(width: u32, height: u32, view: Rect) -> Self {
+        Self {
+            width,
+            height,
+            view,
+            pixels: vec![[0, 0, 0, 0]; (width * height) as usize],
+            textures: HashMap::new(),
+        }
+    }
+
+    /// A backend showing the same area as the game camera.
+    pub fn game_view(scale: u32) -> Self {
+        let size = SCREEN_SIZE as u32 * scale;
+        Self::new(
+            size,
+            size,
+            Rect::new(0.0, -SCREEN_SIZE, SCREEN_SIZE, SCREEN_SIZE),
+        )
+    }
+
+    pub fn load_texture(&mut self, name: &str, path: &str) -> Result<&mut Self> {
+        let bytes = std::fs::read(path).map_err(|error| Error::asset(path, error))?;
+        let image = Image::from_file_with_format(&bytes, None)
+            .map_err(|error| Error::asset(path, error))?;
+        self.textures.insert(name.to_string(), image);
+
+        Ok(self)
+    }
+
+    pub fn load_manifest_textures(&mut self, manifest: &Manifest) -> Result<&mut Self> {
+        for texture in &manifest.textures {
+            self.load_texture(&texture.name, &texture.path)?;
+        }
+        Ok(self)
+    }
+
+    pub fn pixels(&self) -> &[[u8; 4]] {
+        &self.pixels
+    }
+
+    pub fn save_png(&self, path: &str) -> Result<()> {
+        let file = File::create(path).map_err(|error| Error::write(path, error))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        encoder
+            .write_header()
+            .and_then(|mut writer| {
+                writer.write_image_data(self.pixels.as_flattened())?;
+                writer.finish()
+            })
+            .map_err(|error| Error::write(path, error))
+    }
+}
+
+/// Straight alpha "over" blending of `color` onto an 8 bit pixel.
+fn blend(pixel: &mut [u8; 4], color: [f32; 4]) {
+    let alpha = color[3];
+
+    for channel in 0..3 {
+        let under = pixel[channel] as f32 / 255.0;
+        let blended = color[channel] * alpha + under * (1.0 - alpha);
+        pixel[channel] = (blended * 255.0).round() as u8;
+    }
+    let under_alpha = pixel[3] as f32 / 255.0;
+    pixel[3] = ((alpha + under_alpha * (1.0 - alpha)) * 255.0).round() as u8;
+}
+
+impl RenderBackend for SoftwareBackend {
+    //a texture that isn't loaded has no size, as it isn't drawn either
+    fn texture_size(&self, texture: &str) -> Vec2 {
+        self.textures.get(texture).map_or(Vec2::ZERO, |image| Vec2 {
+            x: image.width as f32,
+            y: image.height as f32,
+        })
+    }
+
+    fn clear(&mut self, color: Color) {
+        let color: [u8; 4] = color.into();
+        self.pixels.fill(color);
+    }
+
+    fn draw(&mut self, command: &DrawCommand) {
+        let Some(texture) = self.textures.get(command.texture) else {
+            return;
+        };
+        let source = command.source.unwrap_or(Rect::new(
+            0.0,
+            0.0,
+            texture.width as f32,
+            texture.height as f32,
+        ));
+        let dest = command.dest;
+        let scale = Vec2 {
+            x: self.width as f32 / self.view.w,
+            y: self.height as f32 / self.view.h,
+        };
+
+        //same convention as macroquad: rotate around the pivot, or the centre
+        let pivot = command.pivot.unwrap_or(dest.center());
+        let (sin, cos) = command.rotation.sin_cos();
+        let rotate = |point: Vec2| {
+            let offset = point - pivot;
+            pivot
+                + Vec2 {
+                    x: offset.x * cos - offset.y * sin,
+                    y: offset.x * sin + offset.y * cos,
+                }
+        };
+
+        //bounding box of the rotated rectangle, in pixels
+        let corners = [
+            rotate(dest.point()),
+            rotate(dest.point() + vec2(dest.w, 0.0)),
+            rotate(dest.point() + vec2(0.0, dest.h)),
+            rotate(dest.point() + dest.size()),
+        ];
+        let min = corners
+            .iter()
+            .fold(Vec2::MAX, |min, corner| min.min(*corner));
+        let max = corners
+            .iter()
+            .fold(Vec2::MIN, |max, corner| max.max(*corner));
+        let to_pixel = |point: Vec2| (point - self.view.point()) * scale;
+        let (min, max) = (to_pixel(min), to_pixel(max));
+
+        let x_range = (min.x.floor().max(0.0) as u32)..(max.x.ceil().min(self.width as f32) as u32);
+        let y_range =
+            (min.y.floor().max(0.0) as u32)..(max.y.ceil().min(self.height as f32) as u32);
+        let tint: [f32; 4] = command.tint.into();
+
+        for y in y_range {
+            for x in x_range.clone() {
+                //centre of the pixel, back in world space and then in the
+                //sprite's own unrotated space
+                let world = self.view.point()
+                    + Vec2 {
+                        x: (x as f32 + 0.5) / scale.x,
+                        y: (y as f32 + 0.5) / scale.y,
+                    };
+                let offset = world - pivot;
+                let local = pivot
+                    + Vec2 {
+                        x: offset.x * cos + offset.y * sin,
+                        y: -offset.x * sin + offset.y * cos,
+                    }
+                    - dest.point();
+
+                if local.x < 0.0 || local.y < 0.0 || local.x >= dest.w || local.y >= dest.h {
+                    continue;
+                }
+
+                let mut u = local.x / dest.w;
+                let mut v = local.y / dest.h;
+                if command.flip_x {
+                    u = 1.0 - u;
+                }
+                if command.flip_y {
+                    v = 1.0 - v;
+                }
+
+                let texel_x =
+                    ((source.x + u * source.w).floor() as u32).min(texture.width as u32 - 1);
+                let texel_y =
+                    ((source.y + v * source.h).floor() as u32).min(texture.height as u32 - 1);
+                let texel: [f32; 4] = texture.get_pixel(texel_x, texel_y).into();
+
+                if texel[3] > 0.0 {
+                    blend(
+                        &mut self.pixels[(y * self.width + x) as usize],
+                        [
+                            texel[0] * tint[0],
+                            texel[1] * tint[1],
+                            texel[2] * tint[2],
+                            texel[3] * tint[3],
+                        ],
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,100 @@
+use bevy_ecs::prelude::*;
+use macroquad::prelude::*;
+
+use crate::animation::Animator;
+use crate::asset_loader::{AssetLoader, TextureHandle};
+
+pub const LAYER_PIPES: i32 = 0;
+pub const LAYER_FLOOR: i32 = 1;
+pub const LAYER_PLAYER: i32 = 2;
+pub const LAYER_UI: i32 = 10;
+
+/// Everything the renderer needs to draw one texture for an entity.
+///
+/// `anchor` is given in fractions of the drawn size, so `(0.5, 0.5)` centres
+/// the sprite on the entity's position and `(0.0, 1.0)` puts its bottom left
+/// corner there. `pivot` is relative to the sprite's top left corner.
+///
+/// `frame_size` and `trim_offset` place a frame that was trimmed by an atlas
+/// exporter inside its untrimmed size, which is what `anchor` refers to.
+#[derive(Component, Debug, Clone)]
+pub struct Sprite {
+    pub texture: TextureHandle,
+    pub source: Option<Rect>,
+    pub dest_size: Option<Vec2>,
+    pub frame_size: Option<Vec2>,
+    pub trim_offset: Vec2,
+    pub offset: Vec2,
+    pub anchor: Vec2,
+    pub rotation: f32,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    pub pivot: Option<Vec2>,
+    pub tint: Color,
+    pub layer: i32,
+    pub visible: bool,
+}
+
+impl Sprite {
+    pub fn new(texture: TextureHandle, layer: i32) -> Self {
+        Self {
+            texture,
+            source: None,
+            dest_size: None,
+            frame_size: None,
+            trim_offset: Vec2::ZERO,
+            offset: Vec2::ZERO,
+            anchor: Vec2::ZERO,
+            rotation: 0.0,
+            flip_x: false,
+            flip_y: false,
+            pivot: None,
+            tint: WHITE,
+            layer,
+            visible: true,
+        }
+    }
+
+    pub fn with_offset(mut self, x: f32, y: f32) -> Self {
+        self.offset = Vec2 { x, y };
+        self
+    }
+
+    pub fn with_anchor(mut self, x: f32, y: f32) -> Self {
+        self.anchor = Vec2 { x, y };
+        self
+    }
+
+    pub fn with_flip(mut self, flip_x: bool, flip_y: bool) -> Self {
+        self.flip_x = flip_x;
+        self.flip_y = flip_y;
+        self
+    }
+
+    pub fn with_tint(mut self, tint: Color) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    pub fn hidden(mut self) -> Self {
+        self.visible = false;
+        self
+    }
+}
+
+/// Several sprites drawn for a single entity, such as the two halves of a pipe.
+#[derive(Component, Debug, Clone)]
+pub struct CompositeSprite(pub Vec<Sprite>);
+
+/// Copies the current frame of each entity's animation into its sprite.
+pub fn animate_sprites(mut query: Query<(&Animator, &mut Sprite)>, assets: Res<AssetLoader>) {
+    for (animator, mut sprite) in &mut query {
+        let clip = assets.get_clip(animator.clip);
+        if let Some(frame) = animator.current_frame(clip) {
+            sprite.source = Some(frame.source);
+            sprite.dest_size = Some(frame.source.size());
+            sprite.frame_size = Some(frame.size);
+            sprite.trim_offset = frame.offset;
+        }
+    }
+}
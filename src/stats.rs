@@ -0,0 +1,235 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+use bevy_ecs::prelude::*;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::game::{GameState, GameStates};
+use crate::player::{Controller, Player};
+use crate::simulation::{CrashCause, GameEvent, GameEvents, TICK, Tick};
+use crate::sprite::Sprite;
+
+pub const STATS_PATH: &str = "stats.json";
+pub const STATS_CSV_PATH: &str = "stats.csv";
+/// Rows of the score histogram on the stats screen at most.
+pub const HISTOGRAM_ROWS: u32 = 6;
+
+/// How often birds crashed into what.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Deaths {
+    pub pipe_top: u32,
+    pub pipe_bottom: u32,
+    pub floor: u32,
+    pub obstacle: u32,
+}
+
+/// Everything played on this machine, from the keyboard, leaving out
+/// practice, scripted games and the editor. A round counts once, with the
+/// best score of its keyboard birds.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub games_played: u32,
+    pub total_score: u64,
+    pub best_score: u32,
+    pub total_flaps: u64,
+    pub seconds_played: f64,
+    /// Seconds of the longest round.
+    pub longest_run: f32,
+    pub deaths: Deaths,
+    /// How many rounds ended with each score.
+    pub scores: BTreeMap<u32, u32>,
+}
+
+impl Stats {
+    /// Reads the stats saved at `path`, or starts anew when there are none.
+    pub fn load(path: &str) -> Result<Self> {
+        if !Path::new(path).exists() {
+            return Ok(Self::default());
+        }
+        let json = std::fs::read_to_string(path).map_err(|error| Error::asset(path, error))?;
+        serde_json::from_str(&json).map_err(|error| Error::asset(path, error))
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(|error| Error::write(path, error))?;
+        std::fs::write(path, json).map_err(|error| Error::write(path, error))
+    }
+
+    pub fn average_score(&self) -> f64 {
+        if self.games_played == 0 {
+            return 0.0;
+        }
+        self.total_score as f64 / self.games_played as f64
+    }
+
+    /// Rounds per range of scores, in at most [`HISTOGRAM_ROWS`] ranges of
+    /// the same width from 0 to the best score: `(first score, last score,
+    /// rounds)`.
+    pub fn histogram(&self) -> Vec<(u32, u32, u32)> {
+        let width = (self.best_score + 1).div_ceil(HISTOGRAM_ROWS).max(1);
+        (0..=self.best_score)
+            .step_by(width as usize)
+            .map(|first| {
+                let last = first + width - 1;
+                let rounds = self
+                    .scores
+                    .range(first..=last)
+                    .map(|(_, count)| count)
+                    .sum();
+                (first, last, rounds)
+            })
+            .collect()
+    }
+
+    /// The stats as `stat,value` rows, one per score for the histogram.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("stat,value\n");
+        let rows = [
+            ("games_played", self.games_played.to_string()),
+            ("total_score", self.total_score.to_string()),
+            ("average_score", format!("{:.2}", self.average_score())),
+            ("best_score", self.best_score.to_string()),
+            ("total_flaps", self.total_flaps.to_string()),
+            ("seconds_played", format!("{:.2}", self.seconds_played)),
+            ("longest_run", format!("{:.2}", self.longest_run)),
+            ("deaths_pipe_top", self.deaths.pipe_top.to_string()),
+            ("deaths_pipe_bottom", self.deaths.pipe_bottom.to_string()),
+            ("deaths_floor", self.deaths.floor.to_string()),
+            ("deaths_obstacle", self.deaths.obstacle.to_string()),
+        ];
+        for (stat, value) in rows {
+            let _ = writeln!(csv, "{stat},{value}");
+        }
+        for (score, rounds) in &self.scores {
+            let _ = writeln!(csv, "rounds_with_score_{score},{rounds}");
+        }
+        csv
+    }
+
+    pub fn export_csv(&self, path: &str) -> Result<()> {
+        std::fs::write(path, self.to_csv()).map_err(|error| Error::write(path, error))
+    }
+}
+
+/// The lifetime stats, saved after every round.
+#[derive(Resource, Debug, Clone)]
+pub struct LifetimeStats {
+    pub stats: Stats,
+    path: String,
+    /// Whether the stats screen is open.
+    pub screen_open: bool,
+    /// Whether the round that ended was already counted.
+    recorded: bool,
+}
+
+impl LifetimeStats {
+    pub fn load(path: &str) -> Result<Self> {
+        Ok(Self {
+            stats: Stats::load(path)?,
+            path: path.to_string(),
+            screen_open: false,
+            recorded: false,
+        })
+    }
+}
+
+/// Counts the flaps and crashes of the birds flown from the keyboard, and
+/// every round they played once it ends. Runs before the events are drained
+/// by [`play_sounds`](crate::player::play_sounds).
+pub fn record_stats(
+    game_state: Res<GameState>,
+    events: Res<GameEvents>,
+    tick: Res<Tick>,
+    mut lifetime: ResMut<LifetimeStats>,
+    player_query: Query<(&Player, &Controller)>,
+) {
+    let keyboard = |index: usize| {
+        player_query.iter().any(|(player, controller)| {
+            player.index == index && *controller == Controller::Keyboard
+        })
+    };
+
+    let stats = &mut lifetime.stats;
+    for event in &events.0 {
+        match *event {
+            GameEvent::Flap { player, .. } if keyboard(player) => stats.total_flaps += 1,
+            GameEvent::Crash { player, cause, .. } if keyboard(player) => {
+                let deaths = &mut stats.deaths;
+                match cause {
+                    CrashCause::PipeTop => deaths.pipe_top += 1,
+                    CrashCause::PipeBottom => deaths.pipe_bottom += 1,
+                    CrashCause::Floor => deaths.floor += 1,
+                    CrashCause::Obstacle => deaths.obstacle += 1,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if game_state.game_state == GameStates::Play {
+        lifetime.recorded = false;
+    }
+    if !game_state.is_over() || lifetime.recorded {
+        return;
+    }
+    lifetime.recorded = true;
+    let Some(score) = player_query
+        .iter()
+        .filter(|(_player, controller)| **controller == Controller::Keyboard)
+        .map(|(player, _controller)| player.score)
+        .max()
+    else {
+        return;
+    };
+
+    let seconds = tick.0 as f32 * TICK;
+    let stats = &mut lifetime.stats;
+    stats.games_played += 1;
+    stats.total_score += score as u64;
+    stats.best_score = stats.best_score.max(score);
+    stats.seconds_played += seconds as f64;
+    stats.longest_run = stats.longest_run.max(seconds);
+    *stats.scores.entry(score).or_default() += 1;
+    if let Err(error) = lifetime.stats.save(&lifetime.path) {
+        eprintln!("{error}");
+    }
+}
+
+/// S on the start screen opens the stats, S, Escape or the space bar close
+/// them again and E exports them to [`STATS_CSV_PATH`]. Works like the
+/// [achievements screen](crate::achievement::achievement_screen).
+pub fn stats_screen(
+    mut game_state: ResMut<GameState>,
+    mut lifetime: ResMut<LifetimeStats>,
+    mut player_query: Query<&mut Sprite, With<Player>>,
+) {
+    if lifetime.screen_open
+        && is_key_pressed(KeyCode::E)
+        && let Err(error) = lifetime.stats.export_csv(STATS_CSV_PATH)
+    {
+        eprintln!("{error}");
+    }
+
+    let toggled = is_key_pressed(KeyCode::S);
+    let open = if lifetime.screen_open {
+        !(toggled || is_key_pressed(KeyCode::Escape) || is_key_pressed(KeyCode::Space))
+    } else {
+        toggled && game_state.game_state == GameStates::PressStart
+    };
+    if open == lifetime.screen_open {
+        return;
+    }
+
+    lifetime.screen_open = open;
+    game_state.game_state = if open {
+        GameStates::Paused
+    } else {
+        GameStates::PressStart
+    };
+    for mut sprite in &mut player_query {
+        sprite.visible = !open;
+    }
+}
@@ -0,0 +1,304 @@
+use std::fs::{File, OpenOptions};
+use std::io::{LineWriter, Write};
+use std::path::PathBuf;
+
+use bevy_ecs::prelude::*;
+use macroquad::miniquad::date;
+use serde::Serialize;
+
+use crate::bot::{Difficulty, drive_bots};
+use crate::error::{Error, Result};
+use crate::game::{GameState, GameStates, MatchSettings, WinRule};
+use crate::level::Level;
+use crate::mode::{GameMode, time_up};
+use crate::player::Player;
+use crate::simulation::{CrashCause, Distance, GameEvent, GameEvents, GameRng, Tick};
+
+pub const TELEMETRY_DIR: &str = "telemetry";
+/// Bytes a log grows to before it is rotated.
+pub const MAX_LOG_BYTES: u64 = 1024 * 1024;
+/// Rotated logs kept besides the current one; older ones are deleted.
+pub const ROTATED_LOGS: u32 = 5;
+
+/// What a line of the log is about.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Record {
+    /// A round started, with everything needed to tell rounds apart.
+    RunStart {
+        seed: u64,
+        /// The random numbers the pipes of this round are drawn from.
+        rng: GameRng,
+        /// Seconds since the Unix epoch.
+        time: f64,
+        players: usize,
+        /// The difficulty of each bot, by player index; `null` for a human.
+        bots: Vec<Option<Difficulty>>,
+        win_rule: WinRule,
+        mode: GameMode,
+        level: Option<String>,
+    },
+    Flap {
+        player: usize,
+        y: f32,
+    },
+    Score {
+        player: usize,
+        score: u32,
+    },
+    Death {
+        player: usize,
+        cause: CrashCause,
+        x: f32,
+        y: f32,
+        distance: f32,
+    },
+    /// The round ended or was left, with the score of each bird.
+    RunEnd {
+        scores: Vec<u32>,
+        distance: f32,
+    },
+}
+
+/// A line of the log: a record and when it happened.
+#[derive(Debug, Serialize)]
+struct Line<'a> {
+    run: u32,
+    tick: u32,
+    #[serde(flatten)]
+    record: &'a Record,
+}
+
+/// An opt-in log of every round, one JSON object per line in
+/// `telemetry.jsonl`. A log that grows past [`MAX_LOG_BYTES`] moves to
+/// `telemetry.1.jsonl`, that one to `telemetry.2.jsonl` and so on.
+#[derive(Resource, Debug)]
+pub struct Telemetry {
+    dir: PathBuf,
+    /// `None` once writing failed, so a full disk is reported once.
+    writer: Option<LineWriter<File>>,
+    bytes: u64,
+    seed: u64,
+    /// Rounds started since the game started, the current one included.
+    pub run: u32,
+    /// Whether a round is being logged.
+    running: bool,
+    events_seen: usize,
+    tick_seen: Tick,
+}
+
+impl Telemetry {
+    /// Logs to `dir`, which is created when needed, the rounds of a game
+    /// seeded by `seed`.
+    pub fn open(dir: &str, seed: u64) -> Result<Self> {
+        std::fs::create_dir_all(dir).map_err(|error| Error::write(dir, error))?;
+        let mut telemetry = Self {
+            dir: PathBuf::from(dir),
+            writer: None,
+            bytes: 0,
+            seed,
+            run: 0,
+            running: false,
+            events_seen: 0,
+            tick_seen: Tick::default(),
+        };
+        telemetry.open_log()?;
+        Ok(telemetry)
+    }
+
+    fn log_path(&self, index: u32) -> PathBuf {
+        match index {
+            0 => self.dir.join("telemetry.jsonl"),
+            index => self.dir.join(format!("telemetry.{index}.jsonl")),
+        }
+    }
+
+    fn open_log(&mut self) -> Result<()> {
+        let path = self.log_path(0);
+        let error = |error| Error::write(&path.display().to_string(), error);
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(error)?;
+        self.bytes = file.metadata().map_err(error)?.len();
+        self.writer = Some(LineWriter::new(file));
+        Ok(())
+    }
+
+    /// Moves every log one index up, deletes the oldest and starts a new one.
+    fn rotate(&mut self) -> Result<()> {
+        self.writer = None;
+        let oldest = self.log_path(ROTATED_LOGS);
+        if oldest.exists() {
+            std::fs::remove_file(&oldest)
+                .map_err(|error| Error::write(&oldest.display().to_string(), error))?;
+        }
+        for index in (0..ROTATED_LOGS).rev() {
+            let (from, to) = (self.log_path(index), self.log_path(index + 1));
+            if from.exists() {
+                std::fs::rename(&from, &to)
+                    .map_err(|error| Error::write(&to.display().to_string(), error))?;
+            }
+        }
+        self.open_log()
+    }
+
+    /// Appends `record`, rotating the log first when it would grow too big.
+    /// Stops logging after the first error.
+    pub fn write(&mut self, tick: Tick, record: &Record) {
+        if self.writer.is_none() {
+            return;
+        }
+        let line = Line {
+            run: self.run,
+            tick: tick.0,
+            record,
+        };
+        let mut json = serde_json::to_string(&line).expect("records are always valid JSON");
+        json.push('\n');
+
+        let result = if self.bytes > 0 && self.bytes + json.len() as u64 > MAX_LOG_BYTES {
+            self.rotate()
+        } else {
+            Ok(())
+        };
+        let result = result.and_then(|()| {
+            let path = self.log_path(0).display().to_string();
+            let writer = self.writer.as_mut().expect("the log was just opened");
+            writer
+                .write_all(json.as_bytes())
+                .map_err(|error| Error::write(&path, error))
+        });
+        match result {
+            Ok(()) => self.bytes += json.len() as u64,
+            Err(error) => {
+                eprintln!("{error}, telemetry is off");
+                self.writer = None;
+            }
+        }
+    }
+}
+
+/// Scores of the birds by player index.
+fn scores(player_query: &Query<&Player>) -> Vec<u32> {
+    let mut players: Vec<&Player> = player_query.iter().collect();
+    players.sort_by_key(|player| player.index);
+    players.iter().map(|player| player.score).collect()
+}
+
+/// Starts logging a round once it is played and ends it once it is over or
+/// left. Only rounds that were paused go on, as practice rewinds do.
+fn update_run(
+    telemetry: &mut Telemetry,
+    game_state: &GameState,
+    settings: &MatchSettings,
+    level: Option<&Level>,
+    rng: &GameRng,
+    distance: &Distance,
+    player_query: &Query<&Player>,
+) {
+    let tick = telemetry.tick_seen;
+    match game_state.game_state {
+        GameStates::Play if !telemetry.running => {
+            telemetry.running = true;
+            telemetry.run += 1;
+            let record = Record::RunStart {
+                seed: telemetry.seed,
+                rng: *rng,
+                time: date::now(),
+                players: settings.players,
+                bots: settings.bots[..settings.players].to_vec(),
+                win_rule: settings.win_rule,
+                mode: settings.mode,
+                level: level.map(|level| level.name.clone()),
+            };
+            telemetry.write(tick, &record);
+        }
+        GameStates::Play | GameStates::Paused => {}
+        _ if telemetry.running => {
+            telemetry.running = false;
+            let record = Record::RunEnd {
+                scores: scores(player_query),
+                distance: distance.0,
+            };
+            telemetry.write(tick, &record);
+        }
+        _ => {}
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn begin_telemetry_tick(
+    mut telemetry: ResMut<Telemetry>,
+    (game_state, settings, level): (Res<GameState>, Res<MatchSettings>, Option<Res<Level>>),
+    (rng, tick, distance): (Res<GameRng>, Res<Tick>, Res<Distance>),
+    events: Res<GameEvents>,
+    player_query: Query<&Player>,
+) {
+    telemetry.events_seen = events.0.len();
+    telemetry.tick_seen = *tick;
+    update_run(
+        &mut telemetry,
+        &game_state,
+        &settings,
+        level.as_deref(),
+        &rng,
+        &distance,
+        &player_query,
+    );
+}
+
+/// Logs the flaps, scores and crashes of this tick, and the end of the round
+/// when they ended it.
+#[allow(clippy::type_complexity)]
+fn log_telemetry(
+    mut telemetry: ResMut<Telemetry>,
+    (game_state, settings, level): (Res<GameState>, Res<MatchSettings>, Option<Res<Level>>),
+    (rng, distance): (Res<GameRng>, Res<Distance>),
+    events: Res<GameEvents>,
+    player_query: Query<&Player>,
+) {
+    if !telemetry.running {
+        return;
+    }
+    let tick = telemetry.tick_seen;
+    for event in &events.0[telemetry.events_seen.min(events.0.len())..] {
+        let record = match *event {
+            GameEvent::Flap { player, y } => Record::Flap { player, y },
+            GameEvent::Score { player, score } => Record::Score { player, score },
+            GameEvent::Crash {
+                player,
+                x,
+                y,
+                cause,
+            } => Record::Death {
+                player,
+                cause,
+                x,
+                y,
+                distance: distance.0,
+            },
+            GameEvent::PipeSpawned { .. } => continue,
+        };
+        telemetry.write(tick, &record);
+    }
+    update_run(
+        &mut telemetry,
+        &game_state,
+        &settings,
+        level.as_deref(),
+        &rng,
+        &distance,
+        &player_query,
+    );
+}
+
+/// Adds telemetry to a simulation schedule: the events of every tick are
+/// logged before [`play_sounds`](crate::player::play_sounds) drains them.
+pub fn add_telemetry(schedule: &mut Schedule) {
+    schedule.add_systems((
+        begin_telemetry_tick.before(drive_bots),
+        log_telemetry.after(time_up),
+    ));
+}
@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+
+use bevy_ecs::prelude::*;
+use macroquad::prelude::*;
+use serde::Deserialize;
+
+use crate::asset_loader::FontHandle;
+use crate::sprite::LAYER_UI;
+
+/// One glyph as it is stored in a font description file.
+#[derive(Deserialize, Debug, Clone)]
+pub struct GlyphDescription {
+    pub char: char,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub advance: f32,
+}
+
+/// Extra horizontal offset applied between two specific glyphs.
+#[derive(Deserialize, Debug, Clone)]
+pub struct KerningPair {
+    pub pair: String,
+    pub offset: f32,
+}
+
+/// Glyph metrics of a font atlas, loaded from `assets/fonts/*.json`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct FontDescription {
+    pub line_height: f32,
+    #[serde(default)]
+    pub spacing: f32,
+    #[serde(default)]
+    pub kerning: Vec<KerningPair>,
+    pub glyphs: Vec<GlyphDescription>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Glyph {
+    pub source: Rect,
+    pub advance: f32,
+}
+
+/// A glyph placed relative to the text origin, ready to be drawn.
+#[derive(Debug, Clone, Copy)]
+pub struct PlacedGlyph {
+    pub source: Rect,
+    pub dest: Rect,
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub enum TextAlign {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+#[derive(Default, Debug, Clone)]
+pub struct BitmapFont {
+    pub texture: String,
+    pub line_height: f32,
+    pub spacing: f32,
+    glyphs: HashMap<char, Glyph>,
+    kerning: HashMap<(char, char), f32>,
+}
+
+impl BitmapFont {
+    /// Builds the font of a description, which fails when a glyph is
+    /// described twice or a kerning pair isn't two glyphs of the font.
+    pub fn new(texture: &str, description: FontDescription) -> Result<Self, String> {
+        let mut glyphs = HashMap::new();
+        for glyph in &description.glyphs {
+            let placed = Glyph {
+                source: Rect::new(glyph.x, glyph.y, glyph.width, glyph.height),
+                advance: glyph.advance,
+            };
+            if glyphs.insert(glyph.char, placed).is_some() {
+                return Err(format!("glyph '{}' is described twice", glyph.char));
+            }
+        }
+
+        let mut kerning = HashMap::new();
+        for pair in &description.kerning {
+            let mut chars = pair.pair.chars();
+            let (Some(left), Some(right), None) = (chars.next(), chars.next(), chars.next()) else {
+                return Err(format!(
+                    "kerning pair \"{}\" is not two characters",
+                    pair.pair
+                ));
+            };
+            if let Some(unknown) = [left, right].into_iter().find(|c| !glyphs.contains_key(c)) {
+                return Err(format!(
+                    "kerning pair \"{}\" has no glyph for '{unknown}'",
+                    pair.pair
+                ));
+            }
+            kerning.insert((left, right), pair.offset);
+        }
+
+        Ok(Self {
+            texture: texture.to_string(),
+            line_height: description.line_height,
+            spacing: description.spacing,
+            glyphs,
+            kerning,
+        })
+    }
+
+    /// Looks up a glyph, falling back to the uppercase letter for fonts
+    /// that only ship capitals.
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs
+            .get(&c)
+            .or_else(|| self.glyphs.get(&c.to_ascii_uppercase()))
+    }
+
+    pub fn kerning(&self, left: char, right: char) -> f32 {
+        self.kerning.get(&(left, right)).copied().unwrap_or(0.0)
+    }
+
+    /// Width of a single line of text, without scaling.
+    fn line_width(&self, line: &str, letter_spacing: f32) -> f32 {
+        let mut width = 0.0;
+        let mut last_glyph: Option<(char, &Glyph)> = None;
+
+        for c in line.chars() {
+            let Some(glyph) = self.glyph(c) else {
+                continue;
+            };
+            if let Some((previous, _)) = last_glyph {
+                width += self.spacing + letter_spacing + self.kerning(previous, c);
+            }
+            width += glyph.advance;
+            last_glyph = Some((c, glyph));
+        }
+
+        //the last glyph is as wide as its source, not as its advance
+        if let Some((_, glyph)) = last_glyph {
+            width += glyph.source.w - glyph.advance;
+        }
+        width
+    }
+
+    pub fn measure(&self, text: &str, letter_spacing: f32, scale: f32) -> Vec2 {
+        let width = text
+            .lines()
+            .map(|line| self.line_width(line, letter_spacing))
+            .fold(0.0, f32::max);
+        let lines = text.lines().count().max(1);
+
+        Vec2 {
+            x: width * scale,
+            y: lines as f32 * self.line_height * scale,
+        }
+    }
+
+    /// Places every glyph of `text` relative to an origin at the top of the
+    /// text; `align` decides whether the origin is the left edge, the
+    /// centre or the right edge of each line. Characters without a glyph
+    /// take no room.
+    pub fn layout(
+        &self,
+        text: &str,
+        align: TextAlign,
+        letter_spacing: f32,
+        scale: f32,
+    ) -> Vec<PlacedGlyph> {
+        let mut placed = Vec::new();
+
+        for (row, line) in text.lines().enumerate() {
+            let width = self.line_width(line, letter_spacing);
+            let mut x = match align {
+                TextAlign::Left => 0.0,
+                TextAlign::Center => -width / 2.0,
+                TextAlign::Right => -width,
+            };
+            let y = row as f32 * self.line_height;
+            let mut previous: Option<char> = None;
+
+            for c in line.chars() {
+                let Some(glyph) = self.glyph(c) else {
+                    continue;
+                };
+                if let Some(previous) = previous {
+                    x += self.spacing + letter_spacing + self.kerning(previous, c);
+                }
+
+                placed.push(PlacedGlyph {
+                    source: glyph.source,
+                    dest: Rect::new(
+                        x * scale,
+                        y * scale,
+                        glyph.source.w * scale,
+                        glyph.source.h * scale,
+                    ),
+                });
+
+                x += glyph.advance;
+                previous = Some(c);
+            }
+        }
+
+        placed
+    }
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Text {
+    pub content: String,
+    pub font: FontHandle,
+    pub align: TextAlign,
+    pub scale: f32,
+    pub letter_spacing: f32,
+    pub color: Color,
+    pub layer: i32,
+    pub visible: bool,
+}
+
+impl Text {
+    pub fn new(content: impl Into<String>, font: FontHandle) -> Self {
+        Self {
+            content: content.into(),
+            font,
+            align: TextAlign::default(),
+            scale: 1.0,
+            letter_spacing: 0.0,
+            color: WHITE,
+            layer: LAYER_UI,
+            visible: true,
+        }
+    }
+
+    pub fn with_align(mut self, align: TextAlign) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_letter_spacing(mut self, letter_spacing: f32) -> Self {
+        self.letter_spacing = letter_spacing;
+        self
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn hidden(mut self) -> Self {
+        self.visible = false;
+        self
+    }
+}
@@ -1,12 +1,16 @@
 use bevy_ecs::prelude::*;
 use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Component, Debug, Clone, Copy)]
+/// Where an entity is, in game units: `y` goes from `-SCREEN_SIZE` at the top
+/// of the view down to 0 at the bottom.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Position {
     pub x: f32,
     pub y: f32,
 }
-#[derive(Component, Debug, Clone, Copy)]
+/// Units per second an entity moves by.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Velocity {
     pub x: f32,
     pub y: f32,
@@ -1,29 +1,237 @@
 use bevy_ecs::prelude::*;
-use macroquad::prelude::{animation::AnimatedSprite, *};
+use macroquad::prelude::*;
 
+use crate::achievement::Achievements;
+use crate::asset_loader::{GAME_OVER_TEXTURE, PIXEL_FONT, PRESS_SPACE_TEXTURE, SCORE_FONT};
+use crate::bot::AttractMode;
+use crate::campaign::{Campaign, CampaignStates};
+use crate::display::CenterOnView;
+use crate::editor::{Editor, EditorStates, SNAP, Selection};
+use crate::game::{GameStates, MatchSettings};
+use crate::level::{Level, Movement};
+use crate::mode::{GameMode, HighScores, seconds_left};
+use crate::neuro::Trainer;
+use crate::player::{FLAP_KEY_NAMES, MAX_PLAYERS, PLAYER_COLORS, Player};
+use crate::practice::Practice;
+use crate::simulation::{Distance, Tick};
+use crate::sprite::{LAYER_UI, Sprite};
+use crate::stats::LifetimeStats;
+
+/// Size of a digit of the score.
 pub const UI_SPRITE_SIZE: i32 = 36;
+/// Spacing between two digits of the score, which overlap.
 pub const UI_SPRITE_SEP: i32 = -10;
+/// Above the press start sprite.
+const LEVEL_TITLE_Y: f32 = -SCREEN_SIZE + 56.0;
+/// Left edge of the level list of a campaign.
+const CAMPAIGN_TEXT_X: f32 = 40.0;
+/// Left edge of the editor's status.
+const EDITOR_TEXT_X: f32 = 4.0;
+/// Below the score.
+const MODE_TEXT_Y: f32 = -SCREEN_SIZE + 56.0;
+/// Below the time or distance.
+const TOAST_Y: f32 = -SCREEN_SIZE + 80.0;
+/// Left edge of the achievements screen.
+const ACHIEVEMENT_TEXT_X: f32 = 40.0;
+/// Left edge of the stats screen.
+const STATS_TEXT_X: f32 = 16.0;
+/// Most `#` in a bar of the stats screen.
+const STATS_BAR_WIDTH: u32 = 10;
+/// Below the game over sprite.
+const HIGH_SCORE_TEXT_Y: f32 = -SCREEN_SIZE / 2.0 + 30.0;
 
 use crate::{
     game::{GameState, SCREEN_SIZE},
+    text::{Text, TextAlign},
     transform::Position,
 };
 
 #[derive(Resource)]
 pub struct ScoreUiRes {
     pub texture: Texture2D,
-    pub animation: AnimatedSprite,
 }
 
+/// The score of the round, at the top of the screen.
 #[derive(Component)]
 pub struct Score;
 
 #[derive(Bundle)]
 pub struct ScoreUI {
     position: Position,
+    text: Text,
+    centered: CenterOnView,
     is_type: Score,
 }
 
+/// The score of one bird in a round with several, by player index.
+#[derive(Component)]
+pub struct PlayerScore(pub usize);
+
+#[derive(Bundle)]
+pub struct PlayerScoreUI {
+    position: Position,
+    text: Text,
+    centered: CenterOnView,
+    is_type: PlayerScore,
+}
+
+/// Number of players, win rule and flap keys or bots, shown on the start
+/// screen.
+#[derive(Component)]
+pub struct MatchInfo;
+
+#[derive(Bundle)]
+pub struct MatchInfoUI {
+    position: Position,
+    text: Text,
+    centered: CenterOnView,
+    is_type: MatchInfo,
+}
+
+/// The time left in time attack, or the metres flown in distance mode.
+#[derive(Component)]
+pub struct ModeInfo;
+
+#[derive(Bundle)]
+pub struct ModeInfoUI {
+    position: Position,
+    text: Text,
+    centered: CenterOnView,
+    is_type: ModeInfo,
+}
+
+/// The best scores of the mode, once the round is over.
+#[derive(Component)]
+pub struct HighScoreInfo;
+
+#[derive(Bundle)]
+pub struct HighScoreInfoUI {
+    position: Position,
+    text: Text,
+    centered: CenterOnView,
+    is_type: HighScoreInfo,
+}
+
+#[derive(Component)]
+pub struct Winner;
+
+#[derive(Bundle)]
+pub struct WinnerUI {
+    position: Position,
+    text: Text,
+    centered: CenterOnView,
+    is_type: Winner,
+}
+
+/// Shown while the bots play on their own.
+#[derive(Component)]
+pub struct Demo;
+
+#[derive(Bundle)]
+pub struct DemoUI {
+    position: Position,
+    text: Text,
+    centered: CenterOnView,
+    is_type: Demo,
+}
+
+/// The generation and how many of its birds are left, while training.
+#[derive(Component)]
+pub struct TrainingInfo;
+
+#[derive(Bundle)]
+pub struct TrainingInfoUI {
+    position: Position,
+    text: Text,
+    centered: CenterOnView,
+    is_type: TrainingInfo,
+}
+
+/// The name and par of a level before it starts, and how it went once the
+/// birds reached the finish line.
+#[derive(Component)]
+pub struct LevelInfo;
+
+#[derive(Bundle)]
+pub struct LevelInfoUI {
+    position: Position,
+    text: Text,
+    centered: CenterOnView,
+    is_type: LevelInfo,
+}
+
+/// The first pipe of a practice run on the start screen, and how to go on
+/// after a crash.
+#[derive(Component)]
+pub struct PracticeInfo;
+
+#[derive(Bundle)]
+pub struct PracticeInfoUI {
+    position: Position,
+    text: Text,
+    centered: CenterOnView,
+    is_type: PracticeInfo,
+}
+
+/// An achievement that was just unlocked.
+#[derive(Component)]
+pub struct AchievementToast;
+
+#[derive(Bundle)]
+pub struct AchievementToastUI {
+    position: Position,
+    text: Text,
+    centered: CenterOnView,
+    is_type: AchievementToast,
+}
+
+/// Every achievement, locked or not, while the achievements screen is open.
+#[derive(Component)]
+pub struct AchievementList;
+
+#[derive(Bundle)]
+pub struct AchievementListUI {
+    position: Position,
+    text: Text,
+    is_type: AchievementList,
+}
+
+/// The lifetime stats with their bar charts, while the stats screen is
+/// open.
+#[derive(Component)]
+pub struct StatsInfo;
+
+#[derive(Bundle)]
+pub struct StatsInfoUI {
+    position: Position,
+    text: Text,
+    is_type: StatsInfo,
+}
+
+/// The level select screen and the results of a clear, in a campaign.
+#[derive(Component)]
+pub struct CampaignInfo;
+
+#[derive(Bundle)]
+pub struct CampaignInfoUI {
+    position: Position,
+    text: Text,
+    is_type: CampaignInfo,
+}
+
+/// What the level editor is doing, left aligned at the top while editing
+/// and at the bottom while test-playing.
+#[derive(Component)]
+pub struct EditorInfo;
+
+#[derive(Bundle)]
+pub struct EditorInfoUI {
+    position: Position,
+    text: Text,
+    is_type: EditorInfo,
+}
+
+/// The game over sprite, shown once a round is over.
 #[derive(Component)]
 pub struct GameOver;
 
@@ -35,9 +243,12 @@ pub struct GameOverRes {
 #[derive(Bundle)]
 pub struct GameOverUI {
     position: Position,
+    sprite: Sprite,
+    centered: CenterOnView,
     is_type: GameOver,
 }
 
+/// The press start sprite of the start screen.
 #[derive(Component)]
 pub struct PressStart;
 
@@ -49,15 +260,648 @@ pub struct PressStartRes {
 #[derive(Bundle)]
 pub struct PressStartUI {
     position: Position,
+    sprite: Sprite,
+    centered: CenterOnView,
     is_type: PressStart,
 }
 
+/// Spawns every text and sprite of the UI, hidden until it is needed.
 pub fn add_ui(mut commands: Commands) {
     commands.spawn(ScoreUI {
         position: Position {
             x: SCREEN_SIZE / 2.0,
-            y: -SCREEN_SIZE + 30.0,
+            y: -SCREEN_SIZE + 30.0 - UI_SPRITE_SIZE as f32 / 2.0,
         },
+        text: Text::new("0", SCORE_FONT).with_letter_spacing(UI_SPRITE_SEP as f32),
+        centered: CenterOnView,
         is_type: Score {},
     });
+
+    commands.spawn(PressStartUI {
+        position: Position {
+            x: SCREEN_SIZE / 2.0,
+            y: -SCREEN_SIZE / 2.0,
+        },
+        sprite: Sprite::new(PRESS_SPACE_TEXTURE, LAYER_UI)
+            .with_anchor(0.5, 0.5)
+            .with_offset(0.0, -10.0),
+        centered: CenterOnView,
+        is_type: PressStart {},
+    });
+
+    commands.spawn(GameOverUI {
+        position: Position {
+            x: SCREEN_SIZE / 2.0,
+            y: -SCREEN_SIZE / 2.0,
+        },
+        sprite: Sprite::new(GAME_OVER_TEXTURE, LAYER_UI)
+            .with_anchor(0.5, 0.5)
+            .hidden(),
+        centered: CenterOnView,
+        is_type: GameOver {},
+    });
+
+    let column_width = SCREEN_SIZE / MAX_PLAYERS as f32;
+    for (index, color) in PLAYER_COLORS.into_iter().enumerate() {
+        commands.spawn(PlayerScoreUI {
+            position: Position {
+                x: column_width * (index as f32 + 0.5),
+                y: -SCREEN_SIZE + 8.0,
+            },
+            text: Text::new("", PIXEL_FONT).with_color(color).hidden(),
+            centered: CenterOnView,
+            is_type: PlayerScore(index),
+        });
+    }
+
+    commands.spawn(MatchInfoUI {
+        position: Position {
+            x: SCREEN_SIZE / 2.0,
+            y: -SCREEN_SIZE / 2.0 + 70.0,
+        },
+        text: Text::new("", PIXEL_FONT).hidden(),
+        centered: CenterOnView,
+        is_type: MatchInfo {},
+    });
+
+    commands.spawn(ModeInfoUI {
+        position: Position {
+            x: SCREEN_SIZE / 2.0,
+            y: MODE_TEXT_Y,
+        },
+        text: Text::new("", PIXEL_FONT).hidden(),
+        centered: CenterOnView,
+        is_type: ModeInfo {},
+    });
+
+    commands.spawn(HighScoreInfoUI {
+        position: Position {
+            x: SCREEN_SIZE / 2.0,
+            y: HIGH_SCORE_TEXT_Y,
+        },
+        text: Text::new("", PIXEL_FONT).hidden(),
+        centered: CenterOnView,
+        is_type: HighScoreInfo {},
+    });
+
+    commands.spawn(WinnerUI {
+        position: Position {
+            x: SCREEN_SIZE / 2.0,
+            y: -SCREEN_SIZE / 2.0 + 100.0,
+        },
+        text: Text::new("", PIXEL_FONT).hidden(),
+        centered: CenterOnView,
+        is_type: Winner {},
+    });
+
+    commands.spawn(DemoUI {
+        position: Position {
+            x: SCREEN_SIZE / 2.0,
+            y: -16.0,
+        },
+        text: Text::new("DEMO - PRESS ANY KEY", PIXEL_FONT).hidden(),
+        centered: CenterOnView,
+        is_type: Demo {},
+    });
+
+    commands.spawn(TrainingInfoUI {
+        position: Position {
+            x: SCREEN_SIZE / 2.0,
+            y: -16.0,
+        },
+        text: Text::new("", PIXEL_FONT).hidden(),
+        centered: CenterOnView,
+        is_type: TrainingInfo {},
+    });
+
+    commands.spawn(LevelInfoUI {
+        position: Position {
+            x: SCREEN_SIZE / 2.0,
+            y: LEVEL_TITLE_Y,
+        },
+        text: Text::new("", PIXEL_FONT).hidden(),
+        centered: CenterOnView,
+        is_type: LevelInfo {},
+    });
+
+    commands.spawn(PracticeInfoUI {
+        position: Position {
+            x: SCREEN_SIZE / 2.0,
+            y: LEVEL_TITLE_Y,
+        },
+        text: Text::new("", PIXEL_FONT).hidden(),
+        centered: CenterOnView,
+        is_type: PracticeInfo {},
+    });
+
+    commands.spawn(AchievementToastUI {
+        position: Position {
+            x: SCREEN_SIZE / 2.0,
+            y: TOAST_Y,
+        },
+        text: Text::new("", PIXEL_FONT).with_color(GOLD).hidden(),
+        centered: CenterOnView,
+        is_type: AchievementToast {},
+    });
+
+    commands.spawn(AchievementListUI {
+        position: Position {
+            x: ACHIEVEMENT_TEXT_X,
+            y: -SCREEN_SIZE + 40.0,
+        },
+        text: Text::new("", PIXEL_FONT)
+            .with_align(TextAlign::Left)
+            .hidden(),
+        is_type: AchievementList {},
+    });
+
+    commands.spawn(StatsInfoUI {
+        position: Position {
+            x: STATS_TEXT_X,
+            y: -SCREEN_SIZE + 8.0,
+        },
+        text: Text::new("", PIXEL_FONT)
+            .with_align(TextAlign::Left)
+            .hidden(),
+        is_type: StatsInfo {},
+    });
+
+    commands.spawn(CampaignInfoUI {
+        position: Position {
+            x: CAMPAIGN_TEXT_X,
+            y: -SCREEN_SIZE + 40.0,
+        },
+        text: Text::new("", PIXEL_FONT)
+            .with_align(TextAlign::Left)
+            .hidden(),
+        is_type: CampaignInfo {},
+    });
+
+    commands.spawn(EditorInfoUI {
+        position: Position {
+            x: EDITOR_TEXT_X,
+            y: -SCREEN_SIZE + 4.0,
+        },
+        text: Text::new("", PIXEL_FONT)
+            .with_align(TextAlign::Left)
+            .hidden(),
+        is_type: EditorInfo {},
+    });
+}
+
+/// Shows the UI elements that belong to the current game state.
+pub fn update_ui_visibility(
+    game_state: Res<GameState>,
+    settings: Res<MatchSettings>,
+    score_query: Single<&mut Text, With<Score>>,
+    press_start_query: Single<&mut Sprite, (With<PressStart>, Without<GameOver>)>,
+    game_over_query: Single<&mut Sprite, (With<GameOver>, Without<PressStart>)>,
+) {
+    let state = &game_state.game_state;
+
+    //with several birds every one gets its own score instead
+    score_query.into_inner().visible =
+        !settings.is_multiplayer() && (*state == GameStates::Play || game_state.is_over());
+    press_start_query.into_inner().visible = *state == GameStates::PressStart;
+    game_over_query.into_inner().visible = *state == GameStates::GameOver;
+}
+
+/// Fills in and shows the per-player scores, the match settings and the
+/// winner when more than one bird plays.
+#[allow(clippy::type_complexity)]
+pub fn update_match_text(
+    game_state: Res<GameState>,
+    settings: Res<MatchSettings>,
+    player_query: Query<&Player>,
+    mut score_query: Query<(&mut Text, &PlayerScore)>,
+    match_info_query: Single<&mut Text, (With<MatchInfo>, Without<PlayerScore>)>,
+    winner_query: Single<&mut Text, (With<Winner>, Without<MatchInfo>, Without<PlayerScore>)>,
+) {
+    let state = &game_state.game_state;
+    let multiplayer = settings.is_multiplayer();
+
+    for (mut text, PlayerScore(index)) in &mut score_query {
+        let player = player_query.iter().find(|player| player.index == *index);
+        text.visible =
+            multiplayer && player.is_some() && (*state == GameStates::Play || game_state.is_over());
+        if let Some(player) = player {
+            text.content = format!("{} {}", player.name(), player.score);
+        }
+    }
+
+    let mut match_info = match_info_query.into_inner();
+    match_info.visible = (multiplayer || settings.has_bots() || settings.mode != GameMode::Endless)
+        && *state == GameStates::PressStart;
+    let controls: Vec<&str> = (0..settings.players)
+        .map(|index| match settings.bots[index] {
+            Some(difficulty) => difficulty.name(),
+            None => FLAP_KEY_NAMES[index],
+        })
+        .collect();
+    match_info.content = format!("FLAP: {}", controls.join(" "));
+    //the win rule only matters with several birds
+    if multiplayer {
+        match_info.content = format!(
+            "{}P {}\n{}",
+            settings.players,
+            settings.win_rule.name(),
+            match_info.content
+        );
+    }
+    if settings.mode != GameMode::Endless {
+        match_info.content = format!("{}\n{}", settings.mode.name(), match_info.content);
+    }
+
+    let mut winner = winner_query.into_inner();
+    winner.visible = multiplayer && game_state.is_over();
+    winner.content = match game_state.winner {
+        Some(index) => format!("P{} WINS", index + 1),
+        None => "DRAW".to_string(),
+    };
+}
+
+pub fn update_mode_text(
+    game_state: Res<GameState>,
+    settings: Res<MatchSettings>,
+    tick: Res<Tick>,
+    distance: Res<Distance>,
+    mode_query: Single<&mut Text, With<ModeInfo>>,
+) {
+    let mut text = mode_query.into_inner();
+    text.visible = settings.mode != GameMode::Endless
+        && (game_state.game_state == GameStates::Play || game_state.is_over());
+    text.content = match settings.mode {
+        GameMode::Endless => String::new(),
+        GameMode::TimeAttack => {
+            let seconds = seconds_left(&tick);
+            format!("TIME {}:{:02}", seconds / 60, seconds % 60)
+        }
+        GameMode::Distance => format!("{} M", distance.metres()),
+    };
+}
+
+pub fn update_high_score_text(
+    game_state: Res<GameState>,
+    settings: Res<MatchSettings>,
+    high_scores: Option<Res<HighScores>>,
+    high_score_query: Single<&mut Text, With<HighScoreInfo>>,
+) {
+    let mut text = high_score_query.into_inner();
+    let Some(high_scores) = high_scores else {
+        text.visible = false;
+        return;
+    };
+    let table = high_scores.table(settings.mode);
+    text.visible = game_state.game_state == GameStates::GameOver && !table.is_empty();
+
+    let scores: Vec<String> = table.iter().map(u32::to_string).collect();
+    text.content = format!("{} BEST\n{}", settings.mode.name(), scores.join(" "));
+    if high_scores.last_rank == Some(0) {
+        text.content += "\nNEW BEST";
+    }
+}
+
+/// Shows the score of the round.
+pub fn update_score_text(game_state: Res<GameState>, score_query: Single<&mut Text, With<Score>>) {
+    if game_state.is_changed() {
+        let mut text = score_query.into_inner();
+        text.content = game_state.score.to_string();
+    }
+}
+
+pub fn update_level_text(
+    game_state: Res<GameState>,
+    level: Option<Res<Level>>,
+    level_query: Single<(&mut Text, &mut Position), With<LevelInfo>>,
+) {
+    let (mut text, mut position) = level_query.into_inner();
+    let Some(level) = level else {
+        text.visible = false;
+        return;
+    };
+
+    match game_state.game_state {
+        GameStates::PressStart => {
+            text.content = format!("{}\nPAR {}", level.name, level.par);
+            position.y = LEVEL_TITLE_Y;
+            text.visible = true;
+        }
+        GameStates::LevelComplete => {
+            text.content = format!(
+                "LEVEL COMPLETE\nSCORE {} PAR {}",
+                game_state.score, level.par
+            );
+            position.y = -SCREEN_SIZE / 2.0;
+            text.visible = true;
+        }
+        _ => text.visible = false,
+    }
+}
+
+pub fn update_practice_text(
+    game_state: Res<GameState>,
+    practice: Option<Res<Practice>>,
+    practice_query: Single<(&mut Text, &mut Position), With<PracticeInfo>>,
+) {
+    let (mut text, mut position) = practice_query.into_inner();
+    let Some(practice) = practice else {
+        text.visible = false;
+        return;
+    };
+
+    text.visible = true;
+    match game_state.game_state {
+        GameStates::PressStart => {
+            text.content = format!("PRACTICE FROM PIPE {}\nUP/DOWN: CHOOSE", practice.from);
+            position.y = LEVEL_TITLE_Y;
+        }
+        GameStates::Play => {
+            text.content = "BACKSPACE: REWIND".to_string();
+            position.y = -16.0;
+        }
+        GameStates::Paused => {
+            text.content = format!(
+                "CRASHES {}\n\nFLAP: GO ON\nBACKSPACE: REWIND\nR: RESTART",
+                practice.deaths
+            );
+            position.y = -SCREEN_SIZE / 2.0;
+        }
+        _ => text.visible = false,
+    }
+}
+
+pub fn update_achievement_text(
+    achievements: Option<Res<Achievements>>,
+    toast_query: Single<&mut Text, (With<AchievementToast>, Without<AchievementList>)>,
+    list_query: Single<&mut Text, (With<AchievementList>, Without<AchievementToast>)>,
+) {
+    let mut toast = toast_query.into_inner();
+    let mut list = list_query.into_inner();
+    let Some(achievements) = achievements else {
+        toast.visible = false;
+        list.visible = false;
+        return;
+    };
+
+    toast.visible = !achievements.toasts.is_empty() && !achievements.screen_open;
+    if let Some(name) = achievements.toasts.front() {
+        toast.content = format!("ACHIEVEMENT!\n{}", name.to_uppercase());
+    }
+
+    list.visible = achievements.screen_open;
+    if achievements.screen_open {
+        let mut lines = vec![
+            format!(
+                "ACHIEVEMENTS  {}/{}",
+                achievements
+                    .list
+                    .iter()
+                    .filter(|achievement| achievements.unlocked(achievement))
+                    .count(),
+                achievements.list.len()
+            ),
+            String::new(),
+        ];
+        for achievement in &achievements.list {
+            let mark = if achievements.unlocked(achievement) {
+                "*"
+            } else {
+                "-"
+            };
+            lines.push(format!("{mark} {}", achievement.name.to_uppercase()));
+        }
+        lines.push(String::new());
+        lines.push("A: CLOSE".to_string());
+        list.content = lines.join("\n");
+    }
+}
+
+/// `label` padded to `width`, a bar of `#` as long as `value` is next to
+/// `max`, and `value`.
+fn bar_line(label: &str, width: usize, value: u32, max: u32) -> String {
+    let bar = (value * STATS_BAR_WIDTH).div_ceil(max.max(1));
+    format!(
+        "{label:width$} {:bar_width$} {value}",
+        "#".repeat(bar as usize),
+        bar_width = STATS_BAR_WIDTH as usize
+    )
+}
+
+/// `seconds` as `H:MM:SS`, or `M:SS` under an hour.
+fn duration_text(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    match seconds / 3600 {
+        0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{hours}:{:02}:{:02}", seconds / 60 % 60, seconds % 60),
+    }
+}
+
+pub fn update_stats_text(
+    lifetime: Option<Res<LifetimeStats>>,
+    stats_query: Single<&mut Text, With<StatsInfo>>,
+) {
+    let mut text = stats_query.into_inner();
+    text.visible = lifetime
+        .as_ref()
+        .is_some_and(|lifetime| lifetime.screen_open);
+    let Some(lifetime) = lifetime.filter(|lifetime| lifetime.screen_open) else {
+        return;
+    };
+
+    let stats = &lifetime.stats;
+    let mut lines = vec![
+        format!(
+            "GAMES {}  AVG {:.1}",
+            stats.games_played,
+            stats.average_score()
+        ),
+        format!("BEST {}  FLAPS {}", stats.best_score, stats.total_flaps),
+        format!(
+            "TIME {}  LONGEST {}",
+            duration_text(stats.seconds_played),
+            duration_text(stats.longest_run as f64)
+        ),
+        String::new(),
+    ];
+
+    let deaths = &stats.deaths;
+    let mut causes = vec![
+        ("TOP", deaths.pipe_top),
+        ("BOTTOM", deaths.pipe_bottom),
+        ("FLOOR", deaths.floor),
+    ];
+    //only scripts spawn obstacles
+    if deaths.obstacle > 0 {
+        causes.push(("OBSTACLE", deaths.obstacle));
+    }
+    let most = causes.iter().map(|(_, count)| *count).max().unwrap_or(0);
+    for (cause, count) in causes {
+        lines.push(bar_line(cause, 6, count, most));
+    }
+    lines.push(String::new());
+
+    let histogram = stats.histogram();
+    let most = histogram
+        .iter()
+        .map(|(.., rounds)| *rounds)
+        .max()
+        .unwrap_or(0);
+    for (first, last, rounds) in histogram {
+        let label = if first == last {
+            first.to_string()
+        } else {
+            format!("{first}-{last}")
+        };
+        lines.push(bar_line(&label, 6, rounds, most));
+    }
+    lines.push("S: CLOSE  E: EXPORT CSV".to_string());
+    text.content = lines.join("\n");
+}
+
+/// `*` for each star out of `stars`, `-` for each missing one.
+fn star_text(stars: usize, out_of: usize) -> String {
+    format!(
+        "{}{}",
+        "*".repeat(stars),
+        "-".repeat(out_of.saturating_sub(stars))
+    )
+}
+
+pub fn update_campaign_text(
+    campaign: Option<Res<Campaign>>,
+    campaign_query: Single<&mut Text, With<CampaignInfo>>,
+) {
+    let mut text = campaign_query.into_inner();
+    let Some(campaign) = campaign else {
+        text.visible = false;
+        return;
+    };
+
+    match (campaign.state, &campaign.result) {
+        (CampaignStates::LevelSelect, _) => {
+            let mut lines = vec![
+                format!("{}  {} STARS", campaign.name, campaign.total_stars()),
+                String::new(),
+            ];
+            for (index, level) in campaign.levels.iter().enumerate() {
+                let cursor = if index == campaign.selected { ">" } else { " " };
+                let line = if campaign.unlocked(index) {
+                    let stars = campaign
+                        .progress(index)
+                        .map_or(0, |progress| progress.stars);
+                    format!(
+                        "{} {}",
+                        star_text(stars, level.goals.len()),
+                        level.level.name
+                    )
+                } else {
+                    "LOCKED".to_string()
+                };
+                lines.push(format!("{cursor} {} {line}", index + 1));
+            }
+            lines.push(String::new());
+            lines.push("UP/DOWN: CHOOSE".to_string());
+            lines.push("SPACE: PLAY".to_string());
+            text.content = lines.join("\n");
+            text.visible = true;
+        }
+        (CampaignStates::Results, Some(result)) => {
+            let level = campaign.level();
+            let mut lines = vec![
+                format!("{} CLEAR", level.level.name),
+                String::new(),
+                format!(
+                    "SCORE {}{}",
+                    result.score,
+                    if result.new_best { " NEW BEST" } else { "" }
+                ),
+                format!("PICKUPS {}/{}", result.pickups, result.total_pickups),
+                String::new(),
+            ];
+            for (goal, met) in level.goals.iter().zip(&result.goals) {
+                lines.push(format!("{} {}", if *met { "*" } else { "-" }, goal.name()));
+            }
+            lines.push(String::new());
+            lines.push(if campaign.selected + 1 < campaign.levels.len() {
+                "SPACE: NEXT LEVEL".to_string()
+            } else {
+                "SPACE: LEVELS".to_string()
+            });
+            lines.push("R: RETRY  ESC: LEVELS".to_string());
+            text.content = lines.join("\n");
+            text.visible = true;
+        }
+        _ => text.visible = false,
+    }
+}
+
+pub fn update_editor_text(
+    editor: Option<Res<Editor>>,
+    editor_query: Single<(&mut Text, &mut Position), With<EditorInfo>>,
+) {
+    let (mut text, mut position) = editor_query.into_inner();
+    let Some(editor) = editor else {
+        text.visible = false;
+        return;
+    };
+    text.visible = true;
+
+    if editor.state == EditorStates::Testing {
+        text.content = "ESC: BACK TO THE EDITOR".to_string();
+        position.y = -16.0;
+        return;
+    }
+
+    let level = &editor.level;
+    let status = match editor.selection {
+        _ if !editor.message.is_empty() => editor.message.clone(),
+        Some(Selection::Pipe(index)) => {
+            let pipe = &level.obstacles[index];
+            let mut status = format!("PIPE {} GAP {} {}", index + 1, pipe.gap_y, pipe.gap_height);
+            if let Movement::Bounce { range, .. } = pipe.movement {
+                status += &format!(" BOUNCE {range}");
+            }
+            status
+        }
+        Some(Selection::Pickup { pipe, index }) => {
+            format!("PICKUP +{}", level.obstacles[pipe].pickups[index].points)
+        }
+        Some(Selection::Finish) => format!("FINISH {}", level.finish),
+        None => "CLICK: PIPE  RIGHT CLICK: PICKUP".to_string(),
+    };
+    let snap = if editor.snap {
+        SNAP.to_string()
+    } else {
+        "OFF".to_string()
+    };
+    text.content = format!(
+        "{} X {} SNAP {}\n{}",
+        level.name,
+        editor.scroll.round(),
+        snap,
+        status.to_uppercase()
+    );
+    position.y = -SCREEN_SIZE + 4.0;
+}
+
+pub fn update_demo_text(attract: Res<AttractMode>, demo_query: Single<&mut Text, With<Demo>>) {
+    demo_query.into_inner().visible = attract.active();
+}
+
+pub fn update_training_text(
+    trainer: Option<Res<Trainer>>,
+    player_query: Query<&Player>,
+    training_query: Single<&mut Text, With<TrainingInfo>>,
+) {
+    let mut text = training_query.into_inner();
+    text.visible = trainer.is_some();
+    if let Some(trainer) = trainer {
+        let alive = player_query.iter().filter(|player| player.alive).count();
+        text.content = format!(
+            "GEN {} ALIVE {}/{}",
+            trainer.generation(),
+            alive,
+            player_query.iter().count()
+        );
+    }
 }
@@ -0,0 +1,122 @@
+//! Achievements unlock on exactly what their conditions ask for.
+
+use flappy::achievement::Achievements;
+use flappy::asset_loader::BIRD_CLIPS;
+use flappy::error::Result;
+use flappy::simulation::{CrashCause, GameEvent};
+
+const LIST: &str = r#"[
+  { "id": "score_10", "name": "SCORE 10", "condition": { "type": "score", "at_least": 10 } },
+  { "id": "flap_3", "name": "FLAP 3 TIMES", "condition": { "type": "flaps", "at_least": 3 } },
+  { "id": "quick_crash", "name": "DOWN IN 1 SECOND", "condition": { "type": "crash_within", "seconds": 1.0 } },
+  { "id": "every_colour", "name": "FLY EVERY COLOUR", "condition": { "type": "every_colour" } }
+]"#;
+
+/// Loads `json` as the achievements, with no progress saved yet.
+fn load(name: &str, json: &str) -> Result<Achievements> {
+    let dir =
+        std::env::temp_dir().join(format!("flappy-achievements-{}-{name}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("achievements.json");
+    std::fs::write(&path, json).unwrap();
+    let progress = dir.join("progress.json");
+    let _ = std::fs::remove_file(&progress);
+    Achievements::load(path.to_str().unwrap(), progress.to_str().unwrap())
+}
+
+fn unlocked(achievements: &Achievements) -> Vec<&str> {
+    achievements
+        .progress
+        .unlocked
+        .iter()
+        .map(String::as_str)
+        .collect()
+}
+
+fn flap(player: usize) -> GameEvent {
+    GameEvent::Flap { player, y: -160.0 }
+}
+
+fn crash(player: usize) -> GameEvent {
+    GameEvent::Crash {
+        player,
+        x: 50.0,
+        y: -40.0,
+        cause: CrashCause::Floor,
+    }
+}
+
+#[test]
+fn ids_must_be_unique() {
+    let json = r#"[
+      { "id": "score_10", "name": "SCORE 10", "condition": { "type": "score", "at_least": 10 } },
+      { "id": "score_10", "name": "TEN", "condition": { "type": "score", "at_least": 10 } }
+    ]"#;
+    let error = load("duplicate", json).unwrap_err();
+    assert!(
+        error.to_string().contains("score_10 is there twice"),
+        "{error}"
+    );
+}
+
+#[test]
+fn scores_count_from_the_keyboard_only() {
+    let mut achievements = load("score", LIST).unwrap();
+    let keyboard = [(0, None)];
+    let nine = GameEvent::Score {
+        player: 0,
+        score: 9,
+    };
+    assert!(!achievements.count(&[nine], 5.0, &keyboard));
+
+    //bird 1 is a bot
+    let bot = GameEvent::Score {
+        player: 1,
+        score: 10,
+    };
+    assert!(!achievements.count(&[bot], 5.0, &keyboard));
+    assert!(unlocked(&achievements).is_empty());
+
+    let ten = GameEvent::Score {
+        player: 0,
+        score: 10,
+    };
+    assert!(achievements.count(&[ten], 5.0, &keyboard));
+    assert_eq!(unlocked(&achievements), ["score_10"]);
+    assert_eq!(achievements.toasts, ["SCORE 10"]);
+
+    //an achievement unlocks once
+    assert!(!achievements.count(&[ten], 5.0, &keyboard));
+    assert_eq!(achievements.toasts.len(), 1);
+}
+
+#[test]
+fn flaps_add_up_over_rounds() {
+    let mut achievements = load("flaps", LIST).unwrap();
+    let keyboard = [(0, None)];
+    assert!(!achievements.count(&[flap(0), flap(0), flap(1)], 1.0, &keyboard));
+    assert_eq!(achievements.progress.flaps, 2);
+    assert!(achievements.count(&[flap(0)], 1.0, &keyboard));
+    assert_eq!(unlocked(&achievements), ["flap_3"]);
+}
+
+#[test]
+fn only_early_crashes_are_quick() {
+    let mut achievements = load("crash", LIST).unwrap();
+    let keyboard = [(0, None)];
+    assert!(!achievements.count(&[crash(0)], 1.5, &keyboard));
+    assert!(!achievements.count(&[crash(1)], 0.5, &keyboard));
+    assert!(achievements.count(&[crash(0)], 1.0, &keyboard));
+    assert_eq!(unlocked(&achievements), ["quick_crash"]);
+}
+
+#[test]
+fn every_colour_needs_every_bird() {
+    let mut achievements = load("colour", LIST).unwrap();
+    let [yellow, blue, red] = BIRD_CLIPS.map(|clip| clip.name());
+    achievements.count(&[flap(0)], 1.0, &[(0, Some(yellow))]);
+    achievements.count(&[flap(0)], 1.0, &[(0, Some(blue))]);
+    assert!(!unlocked(&achievements).contains(&"every_colour"));
+    achievements.count(&[flap(2)], 1.0, &[(2, Some(red))]);
+    assert!(unlocked(&achievements).contains(&"every_colour"));
+}
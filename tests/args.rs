@@ -0,0 +1,60 @@
+//! Flags are read the same way by the game and every tool.
+
+use flappy::args::{Args, Conflicts};
+
+fn args(line: &str) -> Args {
+    Args::new(line.split_whitespace())
+}
+
+#[test]
+fn values_follow_their_flag() {
+    let args = args("flappy --level first.json --seed 7 --wide");
+    assert!(args.has("--wide"));
+    assert_eq!(args.value("--level"), Ok(Some("first.json")));
+    assert_eq!(args.value("--edit"), Ok(None));
+    assert_eq!(args.parse("--seed", 0u64), Ok(7));
+    assert_eq!(args.parse("--rounds", 20u64), Ok(20));
+}
+
+#[test]
+fn missing_and_invalid_values_are_errors() {
+    let args = args("flappy --seed seven --level --wide");
+    assert_eq!(
+        args.parse("--seed", 0u64),
+        Err("invalid value for --seed: seven".to_string())
+    );
+    assert_eq!(
+        args.value("--level"),
+        Err("--level needs a value".to_string())
+    );
+}
+
+#[test]
+fn optional_values_fall_back_to_the_default() {
+    assert_eq!(args("flappy").parse_optional("--practice", 0u32), Ok(None));
+    assert_eq!(
+        args("flappy --practice --wide").parse_optional("--practice", 0u32),
+        Ok(Some(0))
+    );
+    assert_eq!(
+        args("flappy --practice 20").parse_optional("--practice", 0u32),
+        Ok(Some(20))
+    );
+}
+
+#[test]
+fn conflicting_flags_are_refused() {
+    const CONFLICTS: &Conflicts = &[("--peer", &["--train", "--wide"]), ("--level", &["--edit"])];
+    assert_eq!(
+        args("flappy --peer a --level b").check_conflicts(CONFLICTS),
+        Ok(())
+    );
+    assert_eq!(
+        args("flappy --level b --wide --peer a").check_conflicts(CONFLICTS),
+        Err("--peer can't be combined with --wide".to_string())
+    );
+    assert_eq!(
+        args("flappy --edit c --level b").check_conflicts(CONFLICTS),
+        Err("--level can't be combined with --edit".to_string())
+    );
+}
@@ -0,0 +1,131 @@
+//! Aseprite and TexturePacker exports become the same frames and clips.
+
+use flappy::atlas::{DEFAULT_FRAME_DURATION, parse_atlas};
+use macroquad::math::{Rect, Vec2};
+
+/// An Aseprite export of four 10 pixel frames in a row, 50 to 80
+/// milliseconds each, tagged with `direction`.
+fn aseprite(direction: &str) -> String {
+    format!(
+        r#"{{
+  "frames": {{
+    "bird 0.aseprite": {{ "frame": {{ "x": 0, "y": 0, "w": 10, "h": 10 }}, "duration": 50 }},
+    "bird 1.aseprite": {{ "frame": {{ "x": 10, "y": 0, "w": 10, "h": 10 }}, "duration": 60 }},
+    "bird 2.aseprite": {{ "frame": {{ "x": 20, "y": 0, "w": 10, "h": 10 }}, "duration": 70 }},
+    "bird 3.aseprite": {{ "frame": {{ "x": 30, "y": 0, "w": 10, "h": 10 }}, "duration": 80 }}
+  }},
+  "meta": {{
+    "app": "https://www.aseprite.org/",
+    "frameTags": [
+      {{ "name": "flap", "from": 0, "to": 3, "direction": "{direction}" }},
+      {{ "name": "glide", "from": 2, "to": 2, "direction": "forward" }}
+    ]
+  }}
+}}"#
+    )
+}
+
+/// The left edge of each frame of clip `name`, in play order.
+fn order(json: &str, name: &str) -> Vec<f32> {
+    let atlas = parse_atlas(json, "birds", DEFAULT_FRAME_DURATION).unwrap();
+    let clip = atlas.clips.iter().find(|clip| clip.name == name).unwrap();
+    clip.frames.iter().map(|frame| frame.source.x).collect()
+}
+
+#[test]
+fn aseprite_tags_become_clips() {
+    let atlas = parse_atlas(&aseprite("forward"), "birds", DEFAULT_FRAME_DURATION).unwrap();
+
+    let names: Vec<&str> = atlas.clips.iter().map(|clip| clip.name.as_str()).collect();
+    assert_eq!(names, ["flap", "glide"]);
+    assert_eq!(atlas.clips[0].texture, "birds");
+    let durations: Vec<f32> = atlas.clips[0]
+        .frames
+        .iter()
+        .map(|frame| frame.duration)
+        .collect();
+    assert_eq!(durations, [0.05, 0.06, 0.07, 0.08]);
+    assert_eq!(
+        atlas.frames["bird 1.aseprite"],
+        Rect::new(10.0, 0.0, 10.0, 10.0)
+    );
+}
+
+#[test]
+fn aseprite_directions_order_the_frames() {
+    assert_eq!(order(&aseprite("forward"), "flap"), [0.0, 10.0, 20.0, 30.0]);
+    assert_eq!(order(&aseprite("reverse"), "flap"), [30.0, 20.0, 10.0, 0.0]);
+    assert_eq!(
+        order(&aseprite("pingpong"), "flap"),
+        [0.0, 10.0, 20.0, 30.0, 20.0, 10.0]
+    );
+    assert_eq!(
+        order(&aseprite("pingpong_reverse"), "flap"),
+        [30.0, 20.0, 10.0, 0.0, 10.0, 20.0]
+    );
+    assert_eq!(order(&aseprite("pingpong"), "glide"), [20.0]);
+}
+
+#[test]
+fn unknown_aseprite_directions_are_refused() {
+    let error = parse_atlas(&aseprite("sideways"), "birds", DEFAULT_FRAME_DURATION).unwrap_err();
+    assert!(error.contains("sideways"), "{error}");
+}
+
+#[test]
+fn the_shipped_sprite_sheet_parses() {
+    let json = std::fs::read_to_string("assets/textures/sprite_sheet.json").unwrap();
+    let atlas = parse_atlas(&json, "sprite_sheet", DEFAULT_FRAME_DURATION).unwrap();
+    assert!(!atlas.clips.is_empty());
+    assert!(atlas.clips.iter().all(|clip| !clip.frames.is_empty()));
+}
+
+/// A TexturePacker export with one trimmed frame and no durations.
+const TEXTURE_PACKER: &str = r#"{
+  "frames": [
+    { "filename": "coin_1.png", "frame": { "x": 0, "y": 0, "w": 8, "h": 8 } },
+    { "filename": "coin_2.png", "frame": { "x": 8, "y": 0, "w": 6, "h": 8 }, "trimmed": true,
+      "spriteSourceSize": { "x": 1, "y": 0, "w": 6, "h": 8 }, "sourceSize": { "w": 8, "h": 8 } },
+    { "filename": "star.png", "frame": { "x": 16, "y": 0, "w": 8, "h": 8 } }
+  ],
+  "meta": { "app": "https://www.codeandweb.com/texturepacker" }
+}"#;
+
+#[test]
+fn texture_packer_frames_are_grouped_by_name() {
+    let atlas = parse_atlas(TEXTURE_PACKER, "items", 0.2).unwrap();
+
+    let names: Vec<&str> = atlas.clips.iter().map(|clip| clip.name.as_str()).collect();
+    assert_eq!(names, ["coin", "star"]);
+    let coin = &atlas.clips[0];
+    assert_eq!(coin.frames.len(), 2);
+    assert!(coin.frames.iter().all(|frame| frame.duration == 0.2));
+    //the trimmed frame keeps its place in the untrimmed sprite
+    assert_eq!(coin.frames[1].offset, Vec2 { x: 1.0, y: 0.0 });
+    assert_eq!(coin.frames[1].size, Vec2 { x: 8.0, y: 8.0 });
+}
+
+#[test]
+fn texture_packer_animations_become_clips() {
+    let json = TEXTURE_PACKER.replace(
+        r#""meta""#,
+        r#""animations": { "spin": ["coin_2.png", "coin_1.png", "coin_2.png"] }, "meta""#,
+    );
+    assert_eq!(order(&json, "spin"), [8.0, 0.0, 8.0]);
+
+    let json = TEXTURE_PACKER.replace(
+        r#""meta""#,
+        r#""animations": { "spin": ["coin_3.png"] }, "meta""#,
+    );
+    let error = parse_atlas(&json, "items", 0.2).unwrap_err();
+    assert!(error.contains("coin_3.png"), "{error}");
+}
+
+#[test]
+fn rotated_frames_are_refused() {
+    let json = TEXTURE_PACKER.replace(
+        r#""filename": "star.png","#,
+        r#""filename": "star.png", "rotated": true,"#,
+    );
+    assert!(parse_atlas(&json, "items", 0.2).is_err());
+}
@@ -0,0 +1,95 @@
+//! The batch simulator must play every game bit for bit like the game.
+
+use flappy::batch::BatchSim;
+use flappy::bot::{AIM, should_flap};
+use flappy::env::{Action, FlappyEnv, Observation, RewardConfig};
+
+const SEEDS: [u64; 4] = [0, 7, 1234, 0x9e37_79b9_7f4a_7c15];
+const TICKS: u32 = 3000;
+
+/// Flaps when the bird sinks below the middle of the next gap, a little
+/// lower or higher by game, so the games crash at different times.
+fn flap(observation: &Observation, game: usize) -> bool {
+    let gap = observation
+        .gaps
+        .first()
+        .map(|gap| (gap.top, gap.bottom - gap.top));
+    let aim = AIM - 0.1 + game as f32 * 0.05;
+    should_flap(observation.bird_y, observation.bird_velocity, gap, aim)
+}
+
+fn bits(observation: &Observation) -> Vec<u32> {
+    let mut bits = vec![
+        observation.bird_y.to_bits(),
+        observation.bird_velocity.to_bits(),
+    ];
+    for gap in &observation.gaps {
+        bits.extend([
+            gap.distance.to_bits(),
+            gap.top.to_bits(),
+            gap.bottom.to_bits(),
+        ]);
+    }
+    bits
+}
+
+fn play(threads: usize) {
+    let rewards = RewardConfig {
+        off_center: 0.5,
+        ..Default::default()
+    };
+    let mut envs: Vec<FlappyEnv> = SEEDS
+        .iter()
+        .map(|_| FlappyEnv::new(rewards, None))
+        .collect();
+    let mut observations: Vec<Observation> = envs
+        .iter_mut()
+        .zip(SEEDS)
+        .map(|(env, seed)| env.reset(seed))
+        .collect();
+    let mut batch = BatchSim::new(SEEDS, rewards);
+    let mut scored = false;
+
+    for tick in 1..=TICKS {
+        let flaps: Vec<bool> = observations
+            .iter()
+            .enumerate()
+            .map(|(game, observation)| flap(observation, game))
+            .collect();
+        if threads > 1 {
+            batch.step_parallel(&flaps, threads);
+        } else {
+            batch.step(&flaps);
+        }
+        for (game, env) in envs.iter_mut().enumerate() {
+            let action = if flaps[game] {
+                Action::Flap
+            } else {
+                Action::Idle
+            };
+            let (observation, reward, done, info) = env.step(action);
+            let context = format!("game {game} on tick {tick}");
+            assert_eq!(
+                bits(&observation),
+                bits(&batch.observation(game)),
+                "{context}"
+            );
+            assert_eq!(reward.to_bits(), batch.reward(game).to_bits(), "{context}");
+            assert_eq!(done, batch.done(game), "{context}");
+            assert_eq!(info.score, batch.score(game), "{context}");
+            scored |= info.score > 0;
+            observations[game] = observation;
+        }
+    }
+    assert!(scored, "no bird ever passed a pipe");
+}
+
+#[test]
+fn batch_matches_game() {
+    play(1);
+}
+
+#[test]
+fn parallel_batch_matches_game() {
+    play(3);
+}
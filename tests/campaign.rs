@@ -0,0 +1,85 @@
+//! The no damage star is lost to any crash since the level was chosen.
+
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::RunSystemOnce;
+use flappy::campaign::{Campaign, track_campaign_crashes};
+use flappy::simulation::{CrashCause, GameEvent, GameEvents};
+
+/// A campaign of the first shipped level in a directory of its own, so that
+/// the progress it saves stays out of `levels`.
+fn campaign(name: &str) -> Campaign {
+    let dir = std::env::temp_dir().join(format!("flappy-campaign-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::copy("levels/first_flight.json", dir.join("first_flight.json")).unwrap();
+    std::fs::write(
+        dir.join("campaign.json"),
+        r#"{ "name": "TEST", "levels": [{ "path": "first_flight.json", "stars": [{ "type": "no_damage" }] }] }"#,
+    )
+    .unwrap();
+    Campaign::load(dir.join("campaign.json").to_str().unwrap()).unwrap()
+}
+
+fn crash(world: &mut World) {
+    world.resource_mut::<GameEvents>().0 = vec![GameEvent::Crash {
+        player: 0,
+        x: 0.0,
+        y: 0.0,
+        cause: CrashCause::PipeTop,
+    }];
+    world.run_system_once(track_campaign_crashes).unwrap();
+    world.resource_mut::<GameEvents>().0.clear();
+}
+
+fn no_damage(world: &mut World) -> bool {
+    let mut campaign = world.resource_mut::<Campaign>();
+    campaign.finish(10, 0, 0);
+    campaign.result.as_ref().unwrap().no_damage
+}
+
+#[test]
+fn a_clear_without_a_crash_gets_the_star() {
+    let mut world = World::default();
+    world.insert_resource(GameEvents::default());
+    let mut campaign = campaign("clean");
+    campaign.play(0);
+    world.insert_resource(campaign);
+
+    assert!(no_damage(&mut world));
+    assert_eq!(
+        world
+            .resource::<Campaign>()
+            .result
+            .as_ref()
+            .unwrap()
+            .stars(),
+        1
+    );
+}
+
+#[test]
+fn a_crash_before_the_retry_loses_the_star() {
+    let mut world = World::default();
+    world.insert_resource(GameEvents::default());
+    let mut campaign = campaign("crash");
+    campaign.play(0);
+    world.insert_resource(campaign);
+
+    //the game over and the retry keep the level being played
+    crash(&mut world);
+    assert!(!no_damage(&mut world));
+
+    //choosing the level again starts a new attempt
+    world.resource_mut::<Campaign>().play(0);
+    assert!(no_damage(&mut world));
+}
+
+#[test]
+fn crashes_on_the_level_select_screen_do_not_count() {
+    let mut world = World::default();
+    world.insert_resource(GameEvents::default());
+    world.insert_resource(campaign("select"));
+
+    crash(&mut world);
+    assert!(!world.resource::<Campaign>().crashed);
+}
@@ -0,0 +1,137 @@
+//! Editing a level keeps the pipes that weren't touched where they are, and
+//! every edit can be undone and redone.
+
+use flappy::editor::{Editor, Selection};
+use flappy::game::SCREEN_SIZE;
+use macroquad::math::vec2;
+
+/// A new level, with its single pipe at the right edge of the screen and the
+/// finish line 150 pixels after it.
+fn editor() -> Editor {
+    Editor::open("levels/not_there.json").unwrap()
+}
+
+fn pipe_x(editor: &Editor) -> Vec<f32> {
+    editor
+        .level
+        .pipe_positions()
+        .iter()
+        .map(|position| position.x)
+        .collect()
+}
+
+#[test]
+fn a_pipe_goes_where_it_was_placed() {
+    let mut editor = editor();
+    let finish = editor.level.finish_x();
+    //centred on the click, 26 pixels left of it
+    editor.click(vec2(426.0, -100.0));
+
+    assert_eq!(pipe_x(&editor), vec![SCREEN_SIZE, 400.0]);
+    assert_eq!(editor.level.obstacles[1].gap_y, -160.0);
+    assert_eq!(editor.selection, Some(Selection::Pipe(1)));
+    assert_eq!(editor.level.finish_x(), finish);
+}
+
+#[test]
+fn no_pipe_goes_before_the_first_or_onto_another() {
+    let mut editor = editor();
+    editor.click(vec2(200.0, -100.0));
+    assert_eq!(editor.message, "the level starts at the first pipe");
+    editor.click(vec2(426.0, -100.0));
+    editor.click(vec2(380.0, -300.0));
+    assert_eq!(editor.message, "no room for a pipe here");
+
+    assert_eq!(pipe_x(&editor), vec![SCREEN_SIZE, 400.0]);
+}
+
+#[test]
+fn a_deleted_pipe_leaves_the_others_where_they_are() {
+    let mut editor = editor();
+    editor.click(vec2(426.0, -100.0));
+    editor.click(vec2(486.0, -100.0));
+    let finish = editor.level.finish_x();
+    assert_eq!(pipe_x(&editor), vec![SCREEN_SIZE, 400.0, 460.0]);
+
+    editor.selection = Some(Selection::Pipe(1));
+    editor.delete();
+    assert_eq!(pipe_x(&editor), vec![SCREEN_SIZE, 460.0]);
+    assert_eq!(editor.level.finish_x(), finish);
+}
+
+#[test]
+fn the_first_pipe_is_only_deleted_last() {
+    let mut editor = editor();
+    editor.click(vec2(426.0, -100.0));
+    let level = editor.level.clone();
+
+    editor.selection = Some(Selection::Pipe(0));
+    editor.delete();
+    assert_eq!(editor.level, level);
+    assert_eq!(editor.message, "the level starts at the first pipe");
+
+    editor.selection = Some(Selection::Pipe(1));
+    editor.delete();
+    editor.selection = Some(Selection::Pipe(0));
+    editor.delete();
+    assert!(editor.level.obstacles.is_empty());
+}
+
+#[test]
+fn a_dragged_pipe_keeps_the_next_one_in_place() {
+    let mut editor = editor();
+    editor.click(vec2(426.0, -100.0));
+    editor.click(vec2(486.0, -100.0));
+
+    //grabs the middle pipe below its gap and drags it 20 pixels left
+    editor.click(vec2(410.0, -20.0));
+    editor.drag_to(vec2(390.0, -20.0));
+    editor.drop_drag();
+    assert_eq!(pipe_x(&editor), vec![SCREEN_SIZE, 380.0, 460.0]);
+}
+
+#[test]
+fn a_gap_is_resized_by_its_edge() {
+    let mut editor = editor();
+    let pipe = editor.level.obstacles[0].clone();
+    let bottom = pipe.gap_y + pipe.gap_height;
+
+    editor.click(vec2(SCREEN_SIZE + 10.0, bottom));
+    editor.drag_to(vec2(SCREEN_SIZE + 10.0, bottom + 30.0));
+    editor.drop_drag();
+    assert_eq!(editor.level.obstacles[0].gap_y, pipe.gap_y);
+    assert_eq!(editor.level.obstacles[0].gap_height, pipe.gap_height + 30.0);
+}
+
+#[test]
+fn edits_are_undone_and_redone_in_order() {
+    let mut editor = editor();
+    let new = editor.level.clone();
+    editor.click(vec2(426.0, -100.0));
+    let added = editor.level.clone();
+    editor.click(vec2(410.0, -20.0));
+    editor.drag_to(vec2(390.0, -20.0));
+    editor.drag_to(vec2(370.0, -20.0));
+    editor.drop_drag();
+    let dragged = editor.level.clone();
+
+    //the whole drag is one step
+    editor.undo();
+    assert_eq!(editor.level, added);
+    editor.undo();
+    assert_eq!(editor.level, new);
+    editor.undo();
+    assert_eq!(editor.level, new);
+
+    editor.redo();
+    assert_eq!(editor.level, added);
+    editor.redo();
+    assert_eq!(editor.level, dragged);
+
+    //a new edit drops what could be redone
+    editor.undo();
+    editor.selection = Some(Selection::Pipe(1));
+    editor.delete();
+    editor.redo();
+    assert_eq!(editor.level, new);
+}
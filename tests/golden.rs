@@ -0,0 +1,148 @@
+//! Renders the start, play and game over screens with the software backend
+//! and compares them against the reference images in `assets/golden/`.
+//!
+//! `cargo test --test golden` fails when a screen changed and writes the new
+//! image next to the reference as `<screen>.actual.png`.
+//! `BLESS=1 cargo test --test golden` replaces the references instead.
+
+use bevy_ecs::prelude::*;
+use flappy::asset_loader::{AssetLoader, REQUIRED_ASSETS};
+use flappy::background::spawn_background;
+use flappy::draw::{DrawCommand, RenderBackend, render_world};
+use flappy::game::{GameState, GameStates, MatchSettings};
+use flappy::manifest::{MANIFEST_PATH, Manifest, report};
+use flappy::pipe::{Pipe, spawn_pipes};
+use flappy::player::spawn_players;
+use flappy::simulation::insert_simulation_resources;
+use flappy::software::SoftwareBackend;
+use flappy::sprite::animate_sprites;
+use flappy::transform::Position;
+use flappy::ui::{add_ui, update_match_text, update_score_text, update_ui_visibility};
+use macroquad::color::WHITE;
+use macroquad::math::{Rect, Vec2};
+use macroquad::texture::Image;
+
+const GOLDEN_DIR: &str = "assets/golden";
+
+/// Builds a world like the game's after startup for `players` birds, with
+/// the pipes scrolled `scroll` pixels to the left so they are on screen.
+fn headless_world(
+    manifest: &Manifest,
+    game_state: GameStates,
+    players: usize,
+    score: u32,
+    scroll: f32,
+) -> World {
+    let mut world = World::default();
+    insert_simulation_resources(&mut world, 0);
+    let mut assets = AssetLoader::default();
+    assets
+        .load_manifest_headless(manifest)
+        .expect("Cannot load assets");
+    world.insert_resource(assets);
+    world.insert_resource(GameState {
+        game_state,
+        score,
+        ..Default::default()
+    });
+    world.insert_resource(MatchSettings {
+        players,
+        ..Default::default()
+    });
+
+    let mut startup_schedule = Schedule::default();
+    startup_schedule.add_systems((spawn_pipes, spawn_players, spawn_background, add_ui).chain());
+    startup_schedule.run(&mut world);
+
+    let mut pipe_query = world.query_filtered::<&mut Position, With<Pipe>>();
+    for mut position in pipe_query.iter_mut(&mut world) {
+        position.x -= scroll;
+    }
+
+    let mut schedule = Schedule::default();
+    schedule.add_systems((
+        update_score_text,
+        update_ui_visibility,
+        update_match_text,
+        animate_sprites,
+    ));
+    schedule.run(&mut world);
+
+    world
+}
+
+/// Renders `screen` and compares it against its reference image, or
+/// replaces the reference when `BLESS` is set.
+fn check_screen(screen: &str, game_state: GameStates, players: usize, score: u32, scroll: f32) {
+    let json = std::fs::read_to_string(MANIFEST_PATH).expect("Cannot load asset manifest");
+    let manifest = Manifest::load_from_str(&json, &REQUIRED_ASSETS)
+        .unwrap_or_else(|errors| panic!("Invalid asset manifest:\n{}", report(&errors)));
+    let mut world = headless_world(&manifest, game_state, players, score, scroll);
+    let mut backend = SoftwareBackend::game_view(1);
+    backend
+        .load_manifest_textures(&manifest)
+        .expect("Cannot load textures");
+    render_world(&mut world, &mut backend);
+
+    let golden_path = format!("{GOLDEN_DIR}/{screen}.png");
+    if std::env::var_os("BLESS").is_some() {
+        std::fs::create_dir_all(GOLDEN_DIR).expect("Cannot create golden directory");
+        backend.save_png(&golden_path).expect("Cannot save image");
+        return;
+    }
+
+    let matches = std::fs::read(&golden_path)
+        .ok()
+        .and_then(|bytes| Image::from_file_with_format(&bytes, None).ok())
+        .is_some_and(|golden| golden.get_image_data() == backend.pixels());
+    if !matches {
+        let actual_path = format!("{GOLDEN_DIR}/{screen}.actual.png");
+        backend.save_png(&actual_path).expect("Cannot save image");
+        panic!("{screen} differs from {golden_path}, wrote {actual_path}");
+    }
+}
+
+#[test]
+fn start() {
+    check_screen("start", GameStates::PressStart, 1, 0, 0.0);
+}
+
+#[test]
+fn play() {
+    check_screen("play", GameStates::Play, 1, 12, 200.0);
+}
+
+#[test]
+fn game_over() {
+    check_screen("game_over", GameStates::GameOver, 1, 7, 260.0);
+}
+
+#[test]
+fn versus_start() {
+    check_screen("versus_start", GameStates::PressStart, 4, 0, 0.0);
+}
+
+#[test]
+fn versus_game_over() {
+    check_screen("versus_game_over", GameStates::GameOver, 3, 0, 260.0);
+}
+
+#[test]
+fn unknown_textures_are_not_drawn() {
+    let mut backend = SoftwareBackend::game_view(1);
+    backend.clear(WHITE);
+    assert_eq!(backend.texture_size("not_loaded"), Vec2::ZERO);
+
+    backend.draw(&DrawCommand {
+        texture: "not_loaded",
+        dest: Rect::new(0.0, -100.0, 50.0, 50.0),
+        source: None,
+        rotation: 0.0,
+        flip_x: false,
+        flip_y: false,
+        pivot: None,
+        tint: WHITE,
+        layer: 0,
+    });
+    assert!(backend.pixels().iter().all(|pixel| *pixel == [255; 4]));
+}
@@ -0,0 +1,152 @@
+//! Levels that can't be played are refused with a message per problem, and
+//! the shipped ones all load.
+
+use flappy::campaign::Campaign;
+use flappy::game::SCREEN_SIZE;
+use flappy::level::{Level, LevelPipe, MIN_GAP_HEIGHT, Movement};
+use flappy::pipe::{PIPE_WIDTH, PIPES_H_SEPARATION, PIPES_V_SEPARATION};
+use flappy::player::FLOOR_COLLISION_Y;
+
+fn pipe(spacing: f32, gap_y: f32) -> LevelPipe {
+    LevelPipe {
+        spacing,
+        gap_y,
+        gap_height: PIPES_V_SEPARATION,
+        movement: Movement::Still,
+        pickups: Vec::new(),
+    }
+}
+
+/// A playable level of two pipes.
+fn level() -> Level {
+    Level {
+        name: "TEST".to_string(),
+        par: 2,
+        obstacles: vec![
+            pipe(PIPES_H_SEPARATION, -200.0),
+            pipe(PIPES_H_SEPARATION, -150.0),
+        ],
+        finish: PIPES_H_SEPARATION,
+    }
+}
+
+#[test]
+fn a_playable_level_has_no_problems() {
+    assert!(level().problems().is_empty());
+    //the first pipe's spacing doesn't count, it starts at the screen edge
+    let mut level = level();
+    level.obstacles[0].spacing = 0.0;
+    assert!(level.problems().is_empty());
+}
+
+#[test]
+fn pipes_need_room_between_them() {
+    let mut level = level();
+    level.obstacles[1].spacing = PIPE_WIDTH - 1.0;
+    assert_eq!(
+        level.problems(),
+        [format!("pipe 1: spacing must be at least {PIPE_WIDTH}")]
+    );
+}
+
+#[test]
+fn gaps_need_room_for_a_bird() {
+    let mut level = level();
+    level.obstacles[0].gap_height = MIN_GAP_HEIGHT - 1.0;
+    assert_eq!(
+        level.problems(),
+        [format!(
+            "pipe 0: gap_height must be at least {MIN_GAP_HEIGHT}"
+        )]
+    );
+}
+
+#[test]
+fn gaps_stay_on_screen() {
+    let mut level = level();
+    level.obstacles[0].gap_y = -SCREEN_SIZE - 1.0;
+    level.obstacles[1].gap_y = FLOOR_COLLISION_Y - PIPES_V_SEPARATION + 1.0;
+    assert_eq!(
+        level.problems(),
+        [
+            "pipe 0: the gap leaves the screen",
+            "pipe 1: the gap leaves the screen"
+        ]
+    );
+}
+
+#[test]
+fn bouncing_gaps_stay_on_screen_at_both_ends() {
+    let mut level = level();
+    level.obstacles[1].gap_y = -SCREEN_SIZE + 20.0;
+    level.obstacles[1].movement = Movement::Bounce {
+        speed: 30.0,
+        range: 10.0,
+    };
+    assert!(level.problems().is_empty());
+
+    level.obstacles[1].movement = Movement::Bounce {
+        speed: 30.0,
+        range: 30.0,
+    };
+    assert_eq!(level.problems(), ["pipe 1: the gap leaves the screen"]);
+}
+
+#[test]
+fn bounces_need_a_speed_and_a_range() {
+    let mut level = level();
+    level.obstacles[0].movement = Movement::Bounce {
+        speed: 0.0,
+        range: 20.0,
+    };
+    level.obstacles[1].movement = Movement::Bounce {
+        speed: 30.0,
+        range: -20.0,
+    };
+    assert_eq!(
+        level.problems(),
+        [
+            "pipe 0: bounce needs a positive speed and range",
+            "pipe 1: bounce needs a positive speed and range",
+        ]
+    );
+}
+
+#[test]
+fn a_level_needs_pipes_and_a_finish_line() {
+    let level = Level {
+        obstacles: Vec::new(),
+        finish: PIPE_WIDTH - 1.0,
+        ..level()
+    };
+    assert_eq!(
+        level.problems(),
+        [
+            "a level needs at least one pipe".to_string(),
+            format!("finish must be at least {PIPE_WIDTH}"),
+        ]
+    );
+}
+
+#[test]
+fn the_shipped_levels_load() {
+    let mut loaded = 0;
+    for entry in std::fs::read_dir("levels").unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_string_lossy();
+        if !name.ends_with(".json") || name.starts_with("campaign") {
+            continue;
+        }
+        if let Err(error) = Level::load(&path.to_string_lossy()) {
+            panic!("{error}");
+        }
+        loaded += 1;
+    }
+    assert!(loaded > 0);
+}
+
+#[test]
+fn the_shipped_campaign_loads() {
+    let campaign = Campaign::load("levels/campaign.json").unwrap();
+    assert!(!campaign.levels.is_empty());
+}
@@ -0,0 +1,202 @@
+//! The manifest reports every problem at once, each naming the entry to fix.
+
+use flappy::asset_loader::{AssetLoader, REQUIRED_ASSETS};
+use flappy::manifest::{MANIFEST_PATH, Manifest, ManifestError, RequiredAssets};
+use macroquad::math::Rect;
+
+const NOTHING_REQUIRED: RequiredAssets = RequiredAssets {
+    textures: &[],
+    sounds: &[],
+    fonts: &[],
+    clips: &[],
+};
+
+fn problems(json: &str) -> Vec<ManifestError> {
+    Manifest::parse(json).unwrap().validate(&NOTHING_REQUIRED)
+}
+
+#[test]
+fn the_shipped_manifest_is_valid() {
+    let json = std::fs::read_to_string(MANIFEST_PATH).unwrap();
+    let manifest = Manifest::load_from_str(&json, &REQUIRED_ASSETS).unwrap();
+
+    let mut assets = AssetLoader::default();
+    assets.load_manifest_headless(&manifest).unwrap();
+}
+
+#[test]
+fn names_are_unique_within_a_section() {
+    let errors = problems(
+        r#"{
+  "textures": [
+    { "name": "pipe", "path": "assets/textures/pipe.png" },
+    { "name": "pipe", "path": "assets/textures/base.png" }
+  ],
+  "sounds": [
+    { "name": "pipe", "path": "assets/audio/wing.wav" },
+    { "name": "flap", "path": "assets/audio/wing.wav" },
+    { "name": "flap", "path": "assets/audio/wing.wav" }
+  ]
+}"#,
+    );
+    assert_eq!(
+        errors,
+        [
+            ManifestError::Duplicate {
+                section: "texture",
+                name: "pipe".to_string(),
+            },
+            ManifestError::Duplicate {
+                section: "sound",
+                name: "flap".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn files_have_to_exist() {
+    let errors = problems(
+        r#"{
+  "textures": [{ "name": "pipe", "path": "assets/textures/not_there.png" }],
+  "sounds": [{ "name": "flap", "path": "assets/audio/wing.wav" }]
+}"#,
+    );
+    assert_eq!(
+        errors,
+        [ManifestError::MissingFile {
+            section: "texture",
+            name: "pipe".to_string(),
+            path: "assets/textures/not_there.png".to_string(),
+        }]
+    );
+}
+
+#[test]
+fn references_have_to_be_defined() {
+    let errors = problems(
+        r#"{
+  "textures": [{ "name": "font", "path": "assets/textures/font.png" }],
+  "fonts": [{ "name": "pixel", "texture": "fnot", "path": "assets/fonts/pixel.json" }],
+  "sprite_sheets": [{ "name": "birds", "texture": "font", "tile_width": 8, "tile_height": 8 }],
+  "animations": [{ "name": "bird", "sheet": "brids", "clips": [] }]
+}"#,
+    );
+    assert_eq!(
+        errors,
+        [
+            ManifestError::UnknownReference {
+                section: "font",
+                name: "pixel".to_string(),
+                reference: "fnot".to_string(),
+            },
+            ManifestError::UnknownReference {
+                section: "animation",
+                name: "bird".to_string(),
+                reference: "brids".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn clips_need_frames_and_a_frame_rate() {
+    let errors = problems(
+        r#"{
+  "textures": [{ "name": "font", "path": "assets/textures/font.png" }],
+  "sprite_sheets": [{ "name": "birds", "texture": "font", "tile_width": 8, "tile_height": 8 }],
+  "animations": [{ "name": "bird", "sheet": "birds", "clips": [
+    { "name": "flap", "row": 0, "frames": 0, "fps": 10 },
+    { "name": "glide", "row": 1, "frames": 2, "fps": 0 },
+    { "name": "dive", "row": 2, "frames": 2, "fps": 10 }
+  ] }]
+}"#,
+    );
+    let empty: Vec<&str> = errors
+        .iter()
+        .map(|error| match error {
+            ManifestError::EmptyClip { clip, .. } => clip.as_str(),
+            error => panic!("unexpected {error}"),
+        })
+        .collect();
+    assert_eq!(empty, ["flap", "glide"]);
+}
+
+#[test]
+fn the_assets_the_game_uses_have_to_be_defined() {
+    let manifest = Manifest::parse(r#"{ "sounds": [] }"#).unwrap();
+    let errors = manifest.validate(&REQUIRED_ASSETS);
+
+    let missing =
+        REQUIRED_ASSETS.textures.len() + REQUIRED_ASSETS.sounds.len() + REQUIRED_ASSETS.fonts.len();
+    assert_eq!(errors.len(), missing);
+    assert!(errors.contains(&ManifestError::MissingKey {
+        section: "sound",
+        name: "flap_sound".to_string(),
+    }));
+}
+
+#[test]
+fn every_problem_is_reported_at_once() {
+    let errors = problems(
+        r#"{
+  "textures": [
+    { "name": "pipe", "path": "assets/textures/pipe.png" },
+    { "name": "pipe", "path": "assets/textures/not_there.png" }
+  ],
+  "fonts": [{ "name": "pixel", "texture": "fnot", "path": "assets/fonts/pixel.json" }]
+}"#,
+    );
+    assert_eq!(errors.len(), 3);
+}
+
+#[test]
+fn a_broken_manifest_is_a_parse_error() {
+    let error =
+        Manifest::load_from_str(r#"{ "textures": [{ "name": "pipe" }] }"#, &REQUIRED_ASSETS)
+            .unwrap_err();
+    assert!(matches!(error.as_slice(), [ManifestError::Parse(_)]));
+}
+
+#[test]
+fn sprite_sheets_are_cut_one_clip_per_row() {
+    let manifest = Manifest::parse(
+        r#"{
+  "sprite_sheets": [{ "name": "birds", "texture": "font", "tile_width": 16, "tile_height": 12 }],
+  "animations": [{ "name": "bird", "sheet": "birds", "clips": [
+    { "name": "glide", "row": 2, "frames": 3, "fps": 4 }
+  ] }]
+}"#,
+    )
+    .unwrap();
+    let clips = manifest.build_clips(&manifest.animations[0]).unwrap();
+
+    assert_eq!(clips.len(), 1);
+    assert_eq!(clips[0].name, "bird/glide");
+    assert_eq!(clips[0].texture, "font");
+    assert_eq!(clips[0].frames[2].source, Rect::new(32.0, 24.0, 16.0, 12.0));
+    assert_eq!(clips[0].duration(), 0.75);
+}
+
+#[test]
+fn clips_of_the_same_name_collide() {
+    let json = std::fs::read_to_string(MANIFEST_PATH).unwrap().replace(
+        r#""atlases": ["#,
+        r#""atlases": [
+    { "name": "copy", "texture": "sprite_sheet", "path": "assets/textures/sprite_sheet.json" },"#,
+    );
+    let manifest = Manifest::load_from_str(&json, &REQUIRED_ASSETS).unwrap();
+
+    let error = AssetLoader::default()
+        .load_manifest_headless(&manifest)
+        .map(|_| ())
+        .unwrap_err();
+    assert!(
+        error
+            .problems()
+            .iter()
+            .any(|problem| problem.starts_with("clip \"")
+                && problem.ends_with("is defined more than once")),
+        "{error}"
+    );
+}
@@ -0,0 +1,139 @@
+//! Two rollback sessions talking over loopback with delayed and dropped
+//! packets must agree on every confirmed frame.
+
+use std::collections::BTreeSet;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use bevy_ecs::prelude::*;
+use flappy::bot::{AIM, next_gap, should_flap};
+use flappy::net::{LinkConditions, NetplayConfig, RollbackSession};
+use flappy::pipe::Pipe;
+use flappy::player::{PLAYER_SPRITE_SIZES, Player};
+use flappy::simulation::{
+    GameEvent, GameEvents, PlayerInput, TICK, headless_world, simulation_schedule,
+};
+use flappy::transform::{Position, Velocity};
+
+const FRAMES: u32 = 600;
+
+struct Peer {
+    world: World,
+    simulation: Schedule,
+    session: RollbackSession,
+    player: usize,
+    /// Every score announced, as `(player, score)`.
+    scores: BTreeSet<(usize, u32)>,
+    checksums: Vec<(u32, u64)>,
+}
+
+impl Peer {
+    fn new(config: NetplayConfig) -> Self {
+        let mut world = headless_world(config.match_settings(), config.seed);
+        config.assign_controllers(&mut world);
+        Self {
+            world,
+            simulation: simulation_schedule(),
+            session: RollbackSession::new(&config).expect("Cannot bind"),
+            player: config.player,
+            scores: BTreeSet::new(),
+            checksums: Vec::new(),
+        }
+    }
+
+    /// Flaps below the middle of the next gap, a little lower than the
+    /// other bird, so the peer mispredicts it.
+    fn update(&mut self) {
+        let pipes: Vec<(Position, f32)> = self
+            .world
+            .query::<(&Position, &Pipe)>()
+            .iter(&self.world)
+            .map(|(position, pipe)| (*position, pipe.gap_height))
+            .collect();
+        let mut query = self
+            .world
+            .query::<(&Player, &Position, &Velocity, &mut PlayerInput)>();
+        for (player, position, velocity, mut input) in query.iter_mut(&mut self.world) {
+            if player.index != self.player {
+                continue;
+            }
+            let gap = next_gap(pipes.iter().map(|(pipe, gap)| (pipe, *gap)), position.x)
+                .map(|(pipe, gap_height)| (pipe.y, gap_height));
+            let center = position.y + PLAYER_SPRITE_SIZES.y / 2.0;
+            let aim = AIM + self.player as f32 * 0.04;
+            if should_flap(center, velocity.y, gap, aim) {
+                input.flap = true;
+            }
+        }
+        self.session
+            .update(&mut self.world, &mut self.simulation, TICK, FRAMES);
+        for event in self.world.resource_mut::<GameEvents>().0.drain(..) {
+            if let GameEvent::Score { player, score } = event {
+                self.scores.insert((player, score));
+            }
+        }
+        self.checksums.extend(self.session.take_checksums());
+    }
+
+    fn done(&self) -> bool {
+        self.session.confirmed_frame() >= FRAMES && self.session.peer_has_inputs_until(FRAMES)
+    }
+}
+
+#[test]
+fn sessions_agree_after_rollbacks() {
+    let conditions = LinkConditions {
+        latency: Duration::from_millis(30),
+        jitter: Duration::from_millis(10),
+        loss: 0.1,
+    };
+    //both bind any free port and learn the other's once it is bound
+    let any: SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let mut peers: Vec<Peer> = (0..2)
+        .map(|player| {
+            Peer::new(NetplayConfig {
+                local: any,
+                peer: any,
+                player,
+                //both birds pass pipes on this layout
+                seed: 3,
+                conditions,
+            })
+        })
+        .collect();
+    let addresses = peers
+        .iter()
+        .map(|peer| peer.session.local_addr().unwrap())
+        .collect::<Vec<_>>();
+    for (peer, address) in peers.iter_mut().zip(addresses.iter().rev()) {
+        peer.session.set_peer(*address);
+    }
+
+    let start = Instant::now();
+    while !peers.iter().all(Peer::done) {
+        assert!(
+            start.elapsed() < Duration::from_secs(60),
+            "the sessions never finished"
+        );
+        for peer in &mut peers {
+            peer.update();
+        }
+        std::thread::sleep(Duration::from_millis(2));
+    }
+
+    let [first, second] = [&peers[0], &peers[1]];
+    assert!(first.session.stats.rollbacks > 0 && second.session.stats.rollbacks > 0);
+    assert!(!first.checksums.is_empty());
+    assert_eq!(first.checksums, second.checksums);
+
+    //scores that only the corrected timeline has are announced too
+    for peer in &mut peers {
+        assert!(!peer.scores.is_empty());
+        let mut query = peer.world.query::<&Player>();
+        for player in query.iter(&peer.world) {
+            for score in 1..=player.score {
+                assert!(peer.scores.contains(&(player.index, score)));
+            }
+        }
+    }
+}
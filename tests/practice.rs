@@ -0,0 +1,118 @@
+//! Practice runs keep a bounded history to rewind through, and start from
+//! any pipe of the run they practice.
+
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::RunSystemOnce;
+use flappy::game::{GameState, GameStates, MatchSettings};
+use flappy::pipe::{Pipe, move_pipe};
+use flappy::practice::{HISTORY_SECONDS, Practice, REWIND_SECONDS, lay_out};
+use flappy::simulation::{Snapshot, TICKS_PER_SECOND, Tick, headless_world};
+use flappy::transform::Position;
+
+const SEED: u64 = 11;
+
+/// Records a snapshot of every tick from `first` to `last`.
+fn record(practice: &mut Practice, first: u32, last: u32) {
+    let snapshot = Snapshot::take(&mut headless_world(MatchSettings::default(), SEED));
+    for tick in first..=last {
+        practice.record(Snapshot {
+            tick: Tick(tick),
+            ..snapshot.clone()
+        });
+    }
+}
+
+fn ticks(practice: &Practice) -> Vec<u32> {
+    practice
+        .history()
+        .iter()
+        .map(|snapshot| snapshot.tick.0)
+        .collect()
+}
+
+/// The gap of every pipe on screen, left to right.
+fn gaps(world: &mut World) -> Vec<f32> {
+    let mut pipes = pipes(world);
+    pipes.sort_by(|a, b| a.x.total_cmp(&b.x));
+    pipes.iter().map(|pipe| pipe.y).collect()
+}
+
+/// The pipes in the order they were spawned.
+fn pipes(world: &mut World) -> Vec<Position> {
+    let mut pipes: Vec<(Entity, Position)> = world
+        .query_filtered::<(Entity, &Position), With<Pipe>>()
+        .iter(world)
+        .map(|(entity, position)| (entity, *position))
+        .collect();
+    pipes.sort_by_key(|(entity, _)| *entity);
+    pipes
+        .into_iter()
+        .map(|(_entity, position)| position)
+        .collect()
+}
+
+#[test]
+fn the_history_keeps_the_last_seconds() {
+    let kept = HISTORY_SECONDS * TICKS_PER_SECOND;
+    let mut practice = Practice::new(SEED, 0);
+    record(&mut practice, 0, kept + 99);
+
+    assert_eq!(practice.history().len(), kept as usize);
+    assert_eq!(ticks(&practice)[0], 100);
+    assert_eq!(*ticks(&practice).last().unwrap(), kept + 99);
+
+    //a paused game records the same tick again
+    record(&mut practice, kept + 99, kept + 99);
+    assert_eq!(practice.history().len(), kept as usize);
+}
+
+#[test]
+fn a_rewind_goes_back_and_forgets_what_came_after() {
+    let mut practice = Practice::new(SEED, 0);
+    record(&mut practice, 0, 300);
+
+    let snapshot = practice.rewind(Tick(300)).unwrap();
+    let target = 300 - REWIND_SECONDS * TICKS_PER_SECOND;
+    assert_eq!(snapshot.tick.0, target);
+    assert_eq!(*ticks(&practice).last().unwrap(), target);
+
+    //a second rewind goes further back
+    let snapshot = practice.rewind(Tick(target)).unwrap();
+    assert_eq!(snapshot.tick.0, target - REWIND_SECONDS * TICKS_PER_SECOND);
+}
+
+#[test]
+fn a_rewind_past_the_history_stops_at_its_oldest_tick() {
+    let mut practice = Practice::new(SEED, 0);
+    record(&mut practice, 50, 100);
+
+    assert_eq!(practice.rewind(Tick(100)).unwrap().tick.0, 50);
+    assert_eq!(ticks(&practice), vec![50]);
+    assert!(Practice::new(SEED, 0).rewind(Tick(100)).is_none());
+}
+
+#[test]
+fn a_skipped_run_starts_at_the_same_pipe_of_the_whole_run() {
+    let from = 7;
+    let mut skipped = headless_world(MatchSettings::default(), SEED);
+    lay_out(&mut skipped, SEED, from);
+    let skipped_gaps = gaps(&mut skipped);
+
+    //the whole run: the pipes laid out at the start, then the gap each one
+    //draws when it comes back on the right
+    let mut run = headless_world(MatchSettings::default(), SEED);
+    lay_out(&mut run, SEED, 0);
+    run.resource_mut::<GameState>().game_state = GameStates::Play;
+    let mut run_gaps = gaps(&mut run);
+    while run_gaps.len() < from as usize + skipped_gaps.len() {
+        let before = pipes(&mut run);
+        run.run_system_once(move_pipe).unwrap();
+        for (before, pipe) in before.iter().zip(pipes(&mut run)) {
+            if pipe.x > before.x {
+                run_gaps.push(pipe.y);
+            }
+        }
+    }
+
+    assert_eq!(&run_gaps[from as usize..], skipped_gaps.as_slice());
+}
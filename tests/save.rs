@@ -0,0 +1,116 @@
+//! A save state restored into a fresh world plays on exactly like the game
+//! it was taken from, and one that doesn't fit is refused.
+
+use bevy_ecs::prelude::*;
+use flappy::game::{GameState, GameStates, MatchSettings};
+use flappy::pipe::Pipe;
+use flappy::save::{SAVE_STATE_VERSION, SaveState, SavedAnimation};
+use flappy::simulation::{PlayerInput, Snapshot, Tick, headless_world, simulation_schedule};
+
+const SEED: u64 = 7;
+
+fn settings(players: usize) -> MatchSettings {
+    MatchSettings {
+        players,
+        ..Default::default()
+    }
+}
+
+fn world(players: usize) -> World {
+    let mut world = headless_world(settings(players), SEED);
+    world.resource_mut::<GameState>().game_state = GameStates::Play;
+    world
+}
+
+/// Runs `ticks` ticks in which every bird flaps on each 25th tick.
+fn play(world: &mut World, simulation: &mut Schedule, ticks: u32) {
+    for _ in 0..ticks {
+        let flap = world.resource::<Tick>().0.is_multiple_of(25);
+        for mut input in world.query::<&mut PlayerInput>().iter_mut(world) {
+            input.flap |= flap;
+        }
+        simulation.run(world);
+    }
+}
+
+fn save_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("flappy-save-{}-{name}.json", std::process::id()));
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn a_restored_game_plays_on_like_the_original() {
+    let mut simulation = simulation_schedule();
+    let mut original = world(2);
+    play(&mut original, &mut simulation, 90);
+
+    let path = save_path("round-trip");
+    SaveState::take(&mut original).save(&path).unwrap();
+    let mut restored = world(2);
+    SaveState::load(&path)
+        .unwrap()
+        .restore(&mut restored)
+        .unwrap();
+    assert_eq!(
+        Snapshot::take(&mut restored).checksum(),
+        Snapshot::take(&mut original).checksum()
+    );
+
+    let mut restored_simulation = simulation_schedule();
+    play(&mut original, &mut simulation, 120);
+    play(&mut restored, &mut restored_simulation, 120);
+    assert_eq!(
+        Snapshot::take(&mut restored).checksum(),
+        Snapshot::take(&mut original).checksum()
+    );
+    //the birds flew on after the restore, into the first pipe
+    assert!(restored.resource::<Tick>().0 > 90);
+}
+
+#[test]
+fn a_game_with_another_bird_count_is_refused() {
+    let state = SaveState::take(&mut world(2));
+    let mut fewer = world(1);
+    let before = Snapshot::take(&mut fewer);
+
+    assert!(state.restore(&mut fewer).is_err());
+    assert!(state.restore(&mut world(3)).is_err());
+    //nothing changed
+    assert_eq!(Snapshot::take(&mut fewer), before);
+}
+
+#[test]
+fn a_game_with_another_pipe_count_is_refused() {
+    let mut state = SaveState::take(&mut world(1));
+    state.pipes.pop();
+    assert!(state.restore(&mut world(1)).is_err());
+
+    let mut extra = world(1);
+    let pipe = *extra.query::<&Pipe>().iter(&extra).next().unwrap();
+    extra.spawn(pipe);
+    assert!(SaveState::take(&mut world(1)).restore(&mut extra).is_err());
+}
+
+#[test]
+fn an_unknown_clip_is_refused() {
+    let mut state = SaveState::take(&mut world(1));
+    state.birds[0].animation = Some(SavedAnimation {
+        clip: "moonwalk".to_string(),
+        frame: 0,
+        timer: 0.0,
+        playing: true,
+    });
+    let error = state.restore(&mut world(1)).unwrap_err();
+    assert!(error.to_string().contains("moonwalk"), "{error}");
+}
+
+#[test]
+fn a_file_from_another_version_is_refused() {
+    let mut state = SaveState::take(&mut world(1));
+    state.version = SAVE_STATE_VERSION + 1;
+    let path = save_path("version");
+    state.save(&path).unwrap();
+
+    let error = SaveState::load(&path).unwrap_err();
+    assert!(error.to_string().contains("version"), "{error}");
+}
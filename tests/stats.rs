@@ -0,0 +1,186 @@
+//! The lifetime stats count every round once, and export them in the format
+//! the analysts read.
+
+use std::collections::BTreeMap;
+
+use bevy_ecs::prelude::*;
+use bevy_ecs::system::RunSystemOnce;
+use flappy::game::{GameState, GameStates};
+use flappy::player::{Controller, Player};
+use flappy::simulation::{CrashCause, GameEvent, GameEvents, TICKS_PER_SECOND, Tick};
+use flappy::stats::{Deaths, LifetimeStats, Stats, record_stats};
+
+fn stats_path(name: &str) -> String {
+    let path =
+        std::env::temp_dir().join(format!("flappy-stats-{}-{name}.json", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path.to_str().unwrap().to_string()
+}
+
+/// A world with a keyboard bird 0 and a bot bird 1.
+fn world(path: &str) -> World {
+    let mut world = World::default();
+    world.insert_resource(GameState::default());
+    world.insert_resource(GameEvents::default());
+    world.insert_resource(Tick::default());
+    world.insert_resource(LifetimeStats::load(path).unwrap());
+    world.spawn((Player::new(0), Controller::Keyboard));
+    world.spawn((Player::new(1), Controller::Bot));
+    world
+}
+
+/// Plays a round of `seconds` in which the keyboard bird flaps `flaps` times
+/// and crashes into `cause` with `score`, next to a bot with a better score.
+fn play_round(world: &mut World, seconds: u32, flaps: usize, cause: CrashCause, score: u32) {
+    world.resource_mut::<GameState>().game_state = GameStates::Play;
+    let mut events = vec![GameEvent::Flap { player: 0, y: 0.0 }; flaps];
+    events.push(GameEvent::Flap { player: 1, y: 0.0 });
+    world.resource_mut::<GameEvents>().0 = events;
+    world.run_system_once(record_stats).unwrap();
+
+    let mut query = world.query::<&mut Player>();
+    for mut player in query.iter_mut(world) {
+        player.score = score + player.index as u32 * 10;
+    }
+    *world.resource_mut::<Tick>() = Tick(seconds * TICKS_PER_SECOND);
+    world.resource_mut::<GameState>().game_state = GameStates::GameOver;
+    world.resource_mut::<GameEvents>().0 = vec![
+        GameEvent::Crash {
+            player: 0,
+            x: 50.0,
+            y: -100.0,
+            cause,
+        },
+        GameEvent::Crash {
+            player: 1,
+            x: 50.0,
+            y: -100.0,
+            cause: CrashCause::Floor,
+        },
+    ];
+    world.run_system_once(record_stats).unwrap();
+    //the game over screen lasts many frames, the round counts once
+    world.resource_mut::<GameEvents>().0.clear();
+    world.run_system_once(record_stats).unwrap();
+}
+
+#[test]
+fn rounds_are_recorded_once_for_the_keyboard_birds() {
+    let path = stats_path("record");
+    let mut world = world(&path);
+    play_round(&mut world, 4, 3, CrashCause::PipeTop, 2);
+    play_round(&mut world, 10, 5, CrashCause::Floor, 7);
+    play_round(&mut world, 2, 1, CrashCause::PipeTop, 2);
+
+    let stats = world.resource::<LifetimeStats>().stats.clone();
+    assert_eq!(stats.games_played, 3);
+    assert_eq!(stats.total_score, 11);
+    assert_eq!(stats.best_score, 7);
+    assert_eq!(stats.total_flaps, 9);
+    //ticks add up to seconds with the rounding of f32
+    assert!((stats.seconds_played - 16.0).abs() < 1e-4);
+    assert!((stats.longest_run - 10.0).abs() < 1e-4);
+    assert_eq!(
+        stats.deaths,
+        Deaths {
+            pipe_top: 2,
+            pipe_bottom: 0,
+            floor: 1,
+            obstacle: 0,
+        }
+    );
+    assert_eq!(stats.scores, BTreeMap::from([(2, 2), (7, 1)]));
+    assert!((stats.average_score() - 11.0 / 3.0).abs() < 1e-9);
+
+    //saved after every round
+    assert_eq!(Stats::load(&path).unwrap(), stats);
+}
+
+#[test]
+fn no_rounds_average_zero() {
+    assert_eq!(Stats::default().average_score(), 0.0);
+}
+
+#[test]
+fn histogram_splits_the_scores_into_even_ranges() {
+    let stats = Stats {
+        best_score: 11,
+        scores: BTreeMap::from([(0, 2), (3, 1), (7, 1), (11, 1)]),
+        ..Default::default()
+    };
+    assert_eq!(
+        stats.histogram(),
+        [
+            (0, 1, 2),
+            (2, 3, 1),
+            (4, 5, 0),
+            (6, 7, 1),
+            (8, 9, 0),
+            (10, 11, 1),
+        ]
+    );
+    let few = Stats {
+        best_score: 2,
+        scores: BTreeMap::from([(1, 4)]),
+        ..Default::default()
+    };
+    assert_eq!(few.histogram(), [(0, 0, 0), (1, 1, 4), (2, 2, 0)]);
+}
+
+fn sample() -> Stats {
+    Stats {
+        games_played: 3,
+        total_score: 11,
+        best_score: 7,
+        total_flaps: 9,
+        seconds_played: 16.0,
+        longest_run: 10.0,
+        deaths: Deaths {
+            pipe_top: 2,
+            pipe_bottom: 0,
+            floor: 1,
+            obstacle: 0,
+        },
+        scores: BTreeMap::from([(2, 2), (7, 1)]),
+    }
+}
+
+#[test]
+fn csv_has_a_row_per_stat_and_score() {
+    assert_eq!(
+        sample().to_csv(),
+        "stat,value\n\
+         games_played,3\n\
+         total_score,11\n\
+         average_score,3.67\n\
+         best_score,7\n\
+         total_flaps,9\n\
+         seconds_played,16.00\n\
+         longest_run,10.00\n\
+         deaths_pipe_top,2\n\
+         deaths_pipe_bottom,0\n\
+         deaths_floor,1\n\
+         deaths_obstacle,0\n\
+         rounds_with_score_2,2\n\
+         rounds_with_score_7,1\n"
+    );
+}
+
+#[test]
+fn json_keeps_the_field_names() {
+    let json = serde_json::to_value(sample()).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "games_played": 3,
+            "total_score": 11,
+            "best_score": 7,
+            "total_flaps": 9,
+            "seconds_played": 16.0,
+            "longest_run": 10.0,
+            "deaths": { "pipe_top": 2, "pipe_bottom": 0, "floor": 1, "obstacle": 0 },
+            "scores": { "2": 2, "7": 1 },
+        })
+    );
+    assert_eq!(serde_json::from_value::<Stats>(json).unwrap(), sample());
+}
@@ -0,0 +1,97 @@
+//! Bitmap fonts refuse malformed descriptions and lay out text glyph by
+//! glyph, with spacing and kerning.
+
+use flappy::text::{BitmapFont, FontDescription, TextAlign};
+use macroquad::math::{Rect, Vec2};
+
+/// Glyphs 8 pixels wide that advance 6, with a kerning pair and one pixel of
+/// spacing.
+fn description(kerning: &str) -> FontDescription {
+    serde_json::from_str(&format!(
+        r#"{{
+  "line_height": 10,
+  "spacing": 1,
+  "kerning": [{kerning}],
+  "glyphs": [
+    {{ "char": "A", "x": 0, "y": 0, "width": 8, "height": 10, "advance": 6 }},
+    {{ "char": "V", "x": 8, "y": 0, "width": 8, "height": 10, "advance": 6 }},
+    {{ "char": "1", "x": 16, "y": 0, "width": 4, "height": 10, "advance": 4 }}
+  ]
+}}"#
+    ))
+    .unwrap()
+}
+
+fn font() -> BitmapFont {
+    BitmapFont::new("font", description(r#"{ "pair": "AV", "offset": -2 }"#)).unwrap()
+}
+
+#[test]
+fn malformed_kerning_is_refused() {
+    let error =
+        BitmapFont::new("font", description(r#"{ "pair": "AVA", "offset": -2 }"#)).unwrap_err();
+    assert!(error.contains("AVA"), "{error}");
+
+    let error =
+        BitmapFont::new("font", description(r#"{ "pair": "AX", "offset": -2 }"#)).unwrap_err();
+    assert!(error.contains("'X'"), "{error}");
+}
+
+#[test]
+fn a_glyph_described_twice_is_refused() {
+    let mut description = description("");
+    description.glyphs.push(description.glyphs[0].clone());
+    assert!(BitmapFont::new("font", description).is_err());
+}
+
+#[test]
+fn the_shipped_fonts_load() {
+    for path in ["assets/fonts/pixel.json", "assets/fonts/score.json"] {
+        let json = std::fs::read_to_string(path).unwrap();
+        let description: FontDescription = serde_json::from_str(&json).unwrap();
+        BitmapFont::new("font", description).unwrap();
+    }
+}
+
+#[test]
+fn text_is_measured_from_the_first_to_the_last_pixel() {
+    let font = font();
+    //6 + 1 + 6 + 1 + 8, the last glyph as wide as its source
+    assert_eq!(font.measure("A1A", 0.0, 1.0), Vec2 { x: 20.0, y: 10.0 });
+    //kerned 2 pixels closer
+    assert_eq!(font.measure("AV", 0.0, 1.0).x, 13.0);
+    //letter spacing goes between glyphs and everything scales
+    assert_eq!(font.measure("AV", 2.0, 2.0), Vec2 { x: 30.0, y: 20.0 });
+    //the widest line, and a line height per line
+    assert_eq!(font.measure("A\nAAA", 0.0, 1.0), Vec2 { x: 22.0, y: 20.0 });
+}
+
+#[test]
+fn glyphs_are_placed_along_the_line() {
+    let placed = font().layout("AV1", TextAlign::Left, 0.0, 1.0);
+
+    let x: Vec<f32> = placed.iter().map(|glyph| glyph.dest.x).collect();
+    assert_eq!(x, [0.0, 5.0, 12.0]);
+    assert_eq!(placed[1].source, Rect::new(8.0, 0.0, 8.0, 10.0));
+    assert_eq!(placed[2].dest, Rect::new(12.0, 0.0, 4.0, 10.0));
+}
+
+#[test]
+fn lines_are_aligned_on_the_origin() {
+    let font = font();
+    let width = font.measure("AA", 0.0, 1.0).x;
+
+    let centred = font.layout("AA\nA", TextAlign::Center, 0.0, 1.0);
+    assert_eq!(centred[0].dest.x, -width / 2.0);
+    assert_eq!(centred[2].dest, Rect::new(-4.0, 10.0, 8.0, 10.0));
+
+    let right = font.layout("AA", TextAlign::Right, 0.0, 1.0);
+    assert_eq!(right[1].dest.x + right[1].dest.w, 0.0);
+}
+
+#[test]
+fn lowercase_uses_the_capitals_and_unknown_characters_take_no_room() {
+    let font = font();
+    assert_eq!(font.layout("av", TextAlign::Left, 0.0, 1.0).len(), 2);
+    assert_eq!(font.measure("A?A", 0.0, 1.0), font.measure("AA", 0.0, 1.0));
+}